@@ -3,9 +3,11 @@
 //! Questo modulo implementa un sistema di throttling adattivo che:
 //!
 //! - monitora la latenza dei tick del cervello locale,
+//! - monitora il riempimento della coda di input utente (backpressure),
 //! - applica un PID controller per regolare l'intensità computazionale,
 //! - definisce livelli di throttle (Normal / Throttled / Survival),
-//! - protegge il sistema da overload tramite guardrail su CPU/RAM.
+//! - protegge il sistema da overload tramite guardrail su CPU, RAM e
+//!   pressione di sistema ([`system_load`]).
 //!
 //! # Livelli di throttle
 //!
@@ -25,20 +27,104 @@
 //!
 //! L'intensità controlla quante operazioni background vengono eseguite
 //! per tick (1.0 = tutte, 0.5 = metà, 0.0 = nessuna).
+//!
+//! Il livello finale è il più severo tra quello dato dalla latenza e quello
+//! dato dalla coda di input ([`AdaptiveThrottle::record_queue_depth`]): una
+//! coda che si riempie libera tempo per la corsia critical anche quando i
+//! singoli tick sono ancora veloci.
+//!
+//! # Isteresi
+//!
+//! Per evitare oscillazioni ogni livello ha una soglia di ingresso e una di
+//! uscita (es. si entra in Survival oltre `survival_threshold_ms` e se ne
+//! esce solo sotto `survival_exit_ms`) e un tempo minimo di permanenza: il
+//! livello sale subito, ma scende solo dopo `throttled_min_dwell` /
+//! `survival_min_dwell`. Ogni cambio di livello viene notificato come
+//! [`ThrottleTransition`] ([`AdaptiveThrottle::subscribe_transitions`]).
+//!
+//! # Guardrail di sistema
+//!
+//! Se è installato un [`ProcSampler`] ([`AdaptiveThrottle::set_sampler`]),
+//! [`AdaptiveThrottle::update`] campiona `/proc` ogni
+//! [`SYSTEM_SAMPLE_INTERVAL`] e i [`SystemGuardrails`] del profilo impongono
+//! un livello minimo: CPU satura, poca RAM disponibile o pressione (PSI) alta
+//! portano a Throttled o Survival indipendentemente dalla latenza.
+//!
+//! # Persistenza
+//!
+//! Lo stato del controller ([`ThrottleState`]: livello, intensità, integrale
+//! del PID, media della latenza) viene salvato periodicamente in
+//! `<data_dir>/throttle.json` e ripristinato al bootstrap, così un nodo
+//! riavviato in Survival non riparte a piena intensità. Uno stato più
+//! vecchio di [`MAX_STATE_AGE`] viene ignorato.
+//!
+//! # Autotuning
+//!
+//! I guadagni del PID possono essere stimati offline da una traccia di
+//! latenze registrata sul nodo ([`AdaptiveThrottle::start_trace`],
+//! [`autotune::tune`]) oppure aggiustati online, a piccoli passi ed entro
+//! limiti fissi attorno al preset ([`AdaptiveThrottle::set_online_tuning`]).
+//! I guadagni raggiunti online fanno parte del [`ThrottleState`] persistito.
+//!
+//! # Tempo
+//!
+//! Il `dt` del PID, i tempi minimi di permanenza e la cadenza di
+//! campionamento di `/proc` sono misurati sul [`Clock`](crate::clock::Clock)
+//! del throttle ([`AdaptiveThrottle::with_clock`]): con un
+//! [`ManualClock`](crate::clock::ManualClock) una traccia di latenze viene
+//! riprodotta in modo esattamente deterministico.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::node_profile::NodeProfile;
 
-use std::time::{Duration, Instant};
+pub mod autotune;
+pub mod system_load;
 
-use crate::node_profile::NodeProfile;
+pub use autotune::{LatencyTrace, OnlineTuner, PidGains, TraceSample};
+pub use system_load::{ProcSampler, SystemGuardrails, SystemLoad};
+
+/// Nome del file con lo stato del throttle dentro il `data_dir` del nodo.
+pub const THROTTLE_STATE_FILE: &str = "throttle.json";
+
+/// Età oltre la quale uno stato salvato non viene più ripristinato.
+pub const MAX_STATE_AGE: Duration = Duration::from_secs(15 * 60);
+
+/// Intervallo minimo tra due campioni del carico di sistema.
+pub const SYSTEM_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Intervallo tra due salvataggi periodici dello stato.
+pub const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Fattore di smoothing della media mobile esponenziale della latenza.
+const EMA_ALPHA: f64 = 0.1;
+
+/// Intervallo minimo (secondi) usato per il termine derivativo del PID.
+///
+/// Con tick ravvicinati il jitter sub-millisecondo della latenza, diviso per
+/// un `dt` di pochi millisecondi, produrrebbe derivate enormi e porterebbe
+/// l'intensità a zero anche a latenze minime.
+const MIN_DERIVATIVE_DT: f64 = 0.1;
 
 /// Livello di throttling corrente del nodo.
 ///
 /// Definisce quanto aggressivamente il sistema deve ridurre il carico
-/// per mantenere la latenza target.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// per mantenere la latenza target. I livelli sono ordinati per severità
+/// (`Normal < Throttled < Survival`).
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
 pub enum ThrottleLevel {
     /// Operazione normale: tutte le corsie attive.
     ///
     /// La latenza è entro il target, il sistema è stabile.
+    #[default]
     Normal,
 
     /// Throttling parziale: riduzione delle operazioni background.
@@ -71,12 +157,6 @@ impl ThrottleLevel {
     }
 }
 
-impl Default for ThrottleLevel {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
-
 impl std::fmt::Display for ThrottleLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.as_str())
@@ -112,59 +192,159 @@ pub struct ThrottleConfig {
     /// Latenza massima prima di passare a Survival mode, in ms.
     pub survival_threshold_ms: f64,
 
+    /// Latenza sotto la quale si esce da Survival mode, in ms
+    /// (`<= survival_threshold_ms`).
+    pub survival_exit_ms: f64,
+
     /// Soglia di intensità sotto la quale si va in Throttled mode.
     pub throttled_threshold: f64,
+
+    /// Intensità da raggiungere per uscire da Throttled mode
+    /// (`>= throttled_threshold`).
+    pub throttled_exit_threshold: f64,
+
+    /// Permanenza minima in Throttled mode prima di scendere a Normal.
+    pub throttled_min_dwell: Duration,
+
+    /// Permanenza minima in Survival mode prima di scendere di livello.
+    pub survival_min_dwell: Duration,
+
+    /// Riempimento della coda di input (0.0-1.0) da cui si va in Throttled mode.
+    pub queue_throttled_ratio: f64,
+
+    /// Riempimento della coda di input (0.0-1.0) da cui si va in Survival mode.
+    pub queue_survival_ratio: f64,
+
+    /// Soglie sul carico di sistema (CPU, RAM, PSI).
+    pub guardrails: SystemGuardrails,
 }
 
 impl ThrottleConfig {
     /// Preset per nodi Heavy: latenza target bassa, reazione aggressiva.
     #[must_use]
-    pub fn heavy() -> Self {
+    pub const fn heavy() -> Self {
         Self {
             target_latency_ms: 10.0,
             kp: 0.05,
             ki: 0.001,
             kd: 0.01,
             survival_threshold_ms: 100.0,
+            survival_exit_ms: 60.0,
             throttled_threshold: 0.7,
+            throttled_exit_threshold: 0.8,
+            throttled_min_dwell: Duration::from_secs(1),
+            survival_min_dwell: Duration::from_secs(3),
+            queue_throttled_ratio: 0.75,
+            queue_survival_ratio: 0.95,
+            guardrails: SystemGuardrails::heavy(),
         }
     }
 
     /// Preset per nodi Desktop: latenza target moderata, reazione equilibrata.
     #[must_use]
-    pub fn desktop() -> Self {
+    pub const fn desktop() -> Self {
         Self {
             target_latency_ms: 50.0,
             kp: 0.03,
             ki: 0.0005,
             kd: 0.005,
             survival_threshold_ms: 200.0,
+            survival_exit_ms: 120.0,
             throttled_threshold: 0.6,
+            throttled_exit_threshold: 0.75,
+            throttled_min_dwell: Duration::from_secs(2),
+            survival_min_dwell: Duration::from_secs(5),
+            queue_throttled_ratio: 0.5,
+            queue_survival_ratio: 0.9,
+            guardrails: SystemGuardrails::desktop(),
         }
     }
 
     /// Preset per nodi Mobile: latenza target alta, conservazione risorse.
     #[must_use]
-    pub fn mobile() -> Self {
+    pub const fn mobile() -> Self {
         Self {
             target_latency_ms: 200.0,
             kp: 0.02,
             ki: 0.0002,
             kd: 0.002,
             survival_threshold_ms: 500.0,
+            survival_exit_ms: 300.0,
             throttled_threshold: 0.5,
+            throttled_exit_threshold: 0.65,
+            throttled_min_dwell: Duration::from_secs(3),
+            survival_min_dwell: Duration::from_secs(10),
+            queue_throttled_ratio: 0.25,
+            queue_survival_ratio: 0.75,
+            guardrails: SystemGuardrails::mobile(),
         }
     }
 
     /// Seleziona automaticamente il preset in base al profilo del nodo.
     #[must_use]
-    pub fn for_profile(profile: &NodeProfile) -> Self {
+    pub const fn for_profile(profile: &NodeProfile) -> Self {
         match profile {
             NodeProfile::HeavyGpu | NodeProfile::HeavyCpu => Self::heavy(),
             NodeProfile::Desktop => Self::desktop(),
             NodeProfile::Mobile => Self::mobile(),
         }
     }
+
+    /// Guadagni del PID.
+    #[must_use]
+    pub const fn gains(&self) -> PidGains {
+        PidGains {
+            kp: self.kp,
+            ki: self.ki,
+            kd: self.kd,
+        }
+    }
+
+    /// Sostituisce i guadagni del PID.
+    pub const fn set_gains(&mut self, gains: PidGains) {
+        self.kp = gains.kp;
+        self.ki = gains.ki;
+        self.kd = gains.kd;
+    }
+
+    /// Verifica che i valori siano coerenti.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna un errore che nomina il primo campo non valido.
+    pub fn validate(&self) -> Result<()> {
+        ensure_positive("target_latency_ms", self.target_latency_ms)?;
+        ensure_non_negative("kp", self.kp)?;
+        ensure_non_negative("ki", self.ki)?;
+        ensure_non_negative("kd", self.kd)?;
+        ensure_positive("survival_threshold_ms", self.survival_threshold_ms)?;
+        ensure_within(
+            "survival_exit_ms",
+            self.survival_exit_ms,
+            0.0,
+            self.survival_threshold_ms,
+        )?;
+        ensure_within("throttled_threshold", self.throttled_threshold, 0.0, 1.0)?;
+        ensure_within(
+            "throttled_exit_threshold",
+            self.throttled_exit_threshold,
+            self.throttled_threshold,
+            1.0,
+        )?;
+        ensure_within(
+            "queue_throttled_ratio",
+            self.queue_throttled_ratio,
+            0.0,
+            1.0,
+        )?;
+        ensure_within(
+            "queue_survival_ratio",
+            self.queue_survival_ratio,
+            self.queue_throttled_ratio,
+            1.0,
+        )?;
+        self.guardrails.validate()
+    }
 }
 
 impl Default for ThrottleConfig {
@@ -173,6 +353,132 @@ impl Default for ThrottleConfig {
     }
 }
 
+/// Tempi misurati durante un singolo tick del nodo.
+///
+/// Il totale è misurato end-to-end e include anche l'overhead fuori dalle
+/// corsie (aggiornamento throttle, accounting), quindi in generale
+/// `total >= critical + background`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickTiming {
+    /// Durata complessiva del tick.
+    pub total: Duration,
+    /// Tempo speso nella corsia critical (I/O utente, inferenza, policy).
+    pub critical: Duration,
+    /// Tempo speso nelle corsie non critiche (training, snapshot, meta, update).
+    pub background: Duration,
+}
+
+/// Segnale che ha determinato un cambio di livello.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum TransitionCause {
+    /// Latenza dei tick (soglia di Survival o intensità del PID).
+    Latency,
+    /// Riempimento della coda di input.
+    QueuePressure,
+    /// Guardrail sul carico di sistema.
+    SystemLoad,
+    /// Tutti i segnali sono rientrati: ritorno a Normal.
+    Recovered,
+}
+
+impl TransitionCause {
+    /// Restituisce una stringa human-readable della causa.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Latency => "latency",
+            Self::QueuePressure => "queue pressure",
+            Self::SystemLoad => "system load",
+            Self::Recovered => "recovered",
+        }
+    }
+}
+
+impl std::fmt::Display for TransitionCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cambio di livello del throttle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ThrottleTransition {
+    /// Livello precedente.
+    pub from: ThrottleLevel,
+    /// Nuovo livello.
+    pub to: ThrottleLevel,
+    /// Segnale che ha causato il cambio.
+    pub cause: TransitionCause,
+    /// Tick (vedi [`AdaptiveThrottle::tick_count`]) in cui è avvenuto.
+    pub tick: u64,
+}
+
+/// Stato persistibile di un [`AdaptiveThrottle`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThrottleState {
+    /// Istante del salvataggio (millisecondi Unix).
+    pub saved_ms: u64,
+    /// Livello di throttle.
+    pub level: ThrottleLevel,
+    /// Intensità computazionale (0.0-1.0).
+    pub intensity: f64,
+    /// Integrale dell'errore del PID.
+    pub integral: f64,
+    /// Ultimo errore del PID (per il termine derivativo).
+    pub last_error: f64,
+    /// Media mobile della latenza, in millisecondi.
+    pub avg_latency_ms: f64,
+    /// Campioni di latenza registrati.
+    pub latency_samples: u64,
+    /// Guadagni raggiunti dal tuning online, se attivo.
+    #[serde(default)]
+    pub tuned_gains: Option<PidGains>,
+}
+
+impl ThrottleState {
+    /// Età dello stato rispetto all'orologio di sistema (zero se salvato
+    /// "nel futuro", es. dopo una correzione dell'orologio).
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        Duration::from_millis(unix_ms().saturating_sub(self.saved_ms))
+    }
+
+    /// Legge lo stato da `path`; `None` se il file non esiste.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o non è uno stato valido.
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        let raw = match tokio::fs::read(path).await {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read throttle state {}", path.display()))
+            }
+        };
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .with_context(|| format!("Invalid throttle state {}", path.display()))
+    }
+
+    /// Scrive lo stato in `path` (in modo atomico, via file temporaneo).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non può essere scritto.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        let raw = serde_json::to_vec_pretty(self).context("Unable to encode throttle state")?;
+        tokio::fs::write(&tmp, raw)
+            .await
+            .with_context(|| format!("Unable to write throttle state {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .with_context(|| format!("Unable to persist throttle state {}", path.display()))
+    }
+}
+
 /// Sistema di throttling adattivo basato su PID controller.
 ///
 /// Monitora la latenza dei tick e regola l'intensità computazionale
@@ -180,9 +486,17 @@ impl Default for ThrottleConfig {
 #[derive(Debug)]
 pub struct AdaptiveThrottle {
     config: ThrottleConfig,
+    /// Configurazione impostata esplicitamente: non segue più il profilo.
+    config_pinned: bool,
     current_level: ThrottleLevel,
     intensity: f64,
 
+    // Isteresi
+    /// Livello richiesto dalla sola latenza (con le sue soglie di uscita).
+    latency_level: ThrottleLevel,
+    level_since: Instant,
+    transition_subscribers: Vec<mpsc::UnboundedSender<ThrottleTransition>>,
+
     // PID state
     integral: f64,
     last_error: f64,
@@ -190,8 +504,27 @@ pub struct AdaptiveThrottle {
 
     // Metriche
     tick_count: u64,
+    latency_samples: u64,
     last_latency_ms: f64,
     avg_latency_ms: f64,
+    last_critical_ms: f64,
+    last_background_ms: f64,
+    queue_depth: usize,
+    queue_capacity: usize,
+
+    // Carico di sistema
+    sampler: Option<ProcSampler>,
+    last_sample: Option<Instant>,
+    system_load: SystemLoad,
+
+    // Autotuning
+    tuner: Option<OnlineTuner>,
+    /// Guadagni del tuning online ripristinati da uno stato salvato, in
+    /// attesa che il tuning venga attivato.
+    saved_gains: Option<PidGains>,
+    trace: Option<LatencyTrace>,
+
+    clock: SharedClock,
 }
 
 impl AdaptiveThrottle {
@@ -204,34 +537,161 @@ impl AdaptiveThrottle {
     /// Crea un nuovo throttle con configurazione custom.
     #[must_use]
     pub fn with_config(config: ThrottleConfig) -> Self {
+        Self::with_clock(config, SystemClock::shared())
+    }
+
+    /// Crea un nuovo throttle che misura il tempo su `clock` (es. un
+    /// [`ManualClock`](crate::clock::ManualClock) nei test).
+    #[must_use]
+    pub fn with_clock(config: ThrottleConfig, clock: SharedClock) -> Self {
+        let now = clock.now();
         Self {
             config,
+            config_pinned: false,
             current_level: ThrottleLevel::Normal,
             intensity: 1.0,
+            latency_level: ThrottleLevel::Normal,
+            level_since: now,
+            transition_subscribers: Vec::new(),
             integral: 0.0,
             last_error: 0.0,
-            last_update: Instant::now(),
+            last_update: now,
             tick_count: 0,
+            latency_samples: 0,
             last_latency_ms: 0.0,
             avg_latency_ms: 0.0,
+            last_critical_ms: 0.0,
+            last_background_ms: 0.0,
+            queue_depth: 0,
+            queue_capacity: 0,
+            sampler: None,
+            last_sample: None,
+            system_load: SystemLoad::default(),
+            tuner: None,
+            saved_gains: None,
+            trace: None,
+            clock,
         }
     }
 
-    /// Aggiorna il throttle in base al profilo del nodo.
+    /// Sostituisce l'orologio. I riferimenti temporali (permanenza nel
+    /// livello, `dt` del PID, ultimo campione di sistema) ripartono dal
+    /// nuovo orologio.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        let now = clock.now();
+        self.clock = clock;
+        self.level_since = now;
+        self.last_update = now;
+        self.last_sample = None;
+    }
+
+    /// Orologio usato dal throttle.
+    #[must_use]
+    pub const fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Registra un destinatario dei cambi di livello; i ricevitori chiusi
+    /// vengono rimossi automaticamente.
+    pub fn subscribe_transitions(&mut self) -> mpsc::UnboundedReceiver<ThrottleTransition> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.transition_subscribers.push(tx);
+        rx
+    }
+
+    /// Installa il campionatore del carico di sistema usato dai guardrail.
+    pub fn set_sampler(&mut self, sampler: ProcSampler) {
+        self.sampler = Some(sampler);
+        self.last_sample = None;
+    }
+
+    /// Configurazione corrente (inclusi i guadagni aggiustati dal tuning
+    /// online).
+    #[must_use]
+    pub const fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Sostituisce la configurazione (es. il preset del profilo con gli
+    /// override di `samaritan.yaml`). Da qui in poi
+    /// [`AdaptiveThrottle::update`] non la riallinea più al preset del
+    /// profilo; il tuning online, se attivo, riparte dai nuovi guadagni.
+    pub fn set_config(&mut self, config: ThrottleConfig) {
+        self.config = config;
+        self.config_pinned = true;
+        if self.tuner.is_some() {
+            self.tuner = Some(OnlineTuner::new(self.config.gains()));
+        }
+    }
+
+    /// Attiva o disattiva il tuning online dei guadagni del PID.
     ///
-    /// Chiamato all'inizio di ogni tick per aggiornare la configurazione
-    /// e calcolare il nuovo livello di throttle.
+    /// Il tuner parte dai guadagni correnti e li tiene entro
+    /// [`autotune::MIN_GAIN_FACTOR`]-[`autotune::MAX_GAIN_FACTOR`] volte
+    /// quelli di partenza; se uno stato ripristinato conteneva guadagni
+    /// aggiustati, riprende da quelli. Disattivandolo si torna ai guadagni
+    /// di partenza.
+    pub fn set_online_tuning(&mut self, enabled: bool) {
+        match (enabled, self.tuner.take()) {
+            (true, None) => {
+                self.tuner = Some(OnlineTuner::new(self.config.gains()));
+                self.resume_saved_gains();
+            }
+            (true, tuner) => self.tuner = tuner,
+            (false, Some(tuner)) => self.config.set_gains(tuner.base()),
+            (false, None) => {}
+        }
+    }
+
+    /// Tuner online attivo, se presente.
+    #[must_use]
+    pub const fn online_tuner(&self) -> Option<&OnlineTuner> {
+        self.tuner.as_ref()
+    }
+
+    /// Inizia a registrare una traccia di latenze per il tuning offline
+    /// (vedi [`autotune::tune`]), fino a `max_samples` campioni.
+    pub fn start_trace(&mut self, max_samples: usize) {
+        self.trace = Some(LatencyTrace::with_capacity(max_samples));
+    }
+
+    /// Ferma la registrazione e restituisce la traccia, se era attiva.
+    pub const fn take_trace(&mut self) -> Option<LatencyTrace> {
+        self.trace.take()
+    }
+
+    /// Aggiorna il throttle in base al profilo del nodo e al carico di
+    /// sistema.
     ///
-    /// In una versione futura, potrebbe anche leggere metriche di sistema
-    /// (CPU%, RAM%) per decisioni più informate.
+    /// Chiamato all'inizio di ogni tick per aggiornare la configurazione
+    /// e calcolare il nuovo livello di throttle. Se è installato un
+    /// [`ProcSampler`], ogni [`SYSTEM_SAMPLE_INTERVAL`] legge un nuovo
+    /// campione e riapplica i guardrail.
     pub fn update(&mut self, profile: &NodeProfile) {
-        // Aggiorna config se il profilo è cambiato (raro ma possibile)
+        // Aggiorna config se il profilo è cambiato (raro ma possibile),
+        // salvo che sia stata impostata esplicitamente
         let expected_config = ThrottleConfig::for_profile(profile);
-        if (self.config.target_latency_ms - expected_config.target_latency_ms).abs() > 1.0 {
+        if !self.config_pinned
+            && (self.config.target_latency_ms - expected_config.target_latency_ms).abs() > 1.0
+        {
             self.config = expected_config;
+            if self.tuner.is_some() {
+                self.tuner = Some(OnlineTuner::new(self.config.gains()));
+            }
         }
 
         self.tick_count = self.tick_count.wrapping_add(1);
+
+        if let Some(sampler) = &mut self.sampler {
+            let due = self
+                .last_sample
+                .is_none_or(|last| self.clock.elapsed(last) >= SYSTEM_SAMPLE_INTERVAL);
+            if due {
+                self.system_load = sampler.sample();
+                self.last_sample = Some(self.clock.now());
+                self.update_throttle_level();
+            }
+        }
     }
 
     /// Registra la latenza di un tick e aggiorna il PID controller.
@@ -242,23 +702,99 @@ impl AdaptiveThrottle {
         let latency_ms = duration_to_ms(latency);
         self.last_latency_ms = latency_ms;
 
-        // Aggiorna media mobile esponenziale (EMA)
-        const ALPHA: f64 = 0.1;
-        if self.tick_count == 1 {
+        // Aggiorna media mobile esponenziale (EMA), inizializzata dal primo campione
+        if self.latency_samples == 0 {
             self.avg_latency_ms = latency_ms;
         } else {
-            self.avg_latency_ms = (1.0 - ALPHA) * self.avg_latency_ms + ALPHA * latency_ms;
+            self.avg_latency_ms =
+                (1.0 - EMA_ALPHA).mul_add(self.avg_latency_ms, EMA_ALPHA * latency_ms);
         }
+        self.latency_samples = self.latency_samples.wrapping_add(1);
 
         // Calcola nuovo livello e intensità via PID
         self.update_pid_controller(latency_ms);
         self.update_throttle_level();
     }
 
+    /// Registra i tempi di un tick, separati per corsia.
+    ///
+    /// Il PID viene alimentato con la latenza totale (vedi
+    /// [`AdaptiveThrottle::record_tick_latency`]); i tempi per corsia sono
+    /// conservati per metriche e diagnostica.
+    pub fn record_tick_timing(&mut self, timing: &TickTiming) {
+        self.last_critical_ms = duration_to_ms(timing.critical);
+        self.last_background_ms = duration_to_ms(timing.background);
+        self.record_tick_latency(timing.total);
+    }
+
+    /// Istantanea dello stato del controller, da persistere.
+    #[must_use]
+    pub fn state(&self) -> ThrottleState {
+        ThrottleState {
+            saved_ms: unix_ms(),
+            level: self.current_level,
+            intensity: self.intensity,
+            integral: self.integral,
+            last_error: self.last_error,
+            avg_latency_ms: self.avg_latency_ms,
+            latency_samples: self.latency_samples,
+            tuned_gains: self.tuner.as_ref().map(OnlineTuner::gains),
+        }
+    }
+
+    /// Ripristina uno stato salvato, se non più vecchio di `max_age`.
+    ///
+    /// Ritorna `false` (lasciando il throttle invariato) se lo stato è
+    /// scaduto: dopo una lunga pausa il carico del nodo non è più quello
+    /// misurato allora.
+    pub fn restore(&mut self, state: &ThrottleState, max_age: Duration) -> bool {
+        if state.age() > max_age {
+            return false;
+        }
+        self.current_level = state.level;
+        self.latency_level = state.level;
+        // La permanenza minima riparte dal ripristino.
+        self.level_since = self.clock.now();
+        self.intensity = state.intensity.clamp(0.0, 1.0);
+        self.integral = state.integral.clamp(-10.0, 10.0);
+        self.last_error = state.last_error;
+        self.avg_latency_ms = state.avg_latency_ms;
+        self.latency_samples = state.latency_samples;
+        // I guadagni salvati valgono solo col tuning online attivo (anche
+        // se attivato dopo il ripristino), entro i limiti del tuner.
+        self.saved_gains = state.tuned_gains;
+        self.resume_saved_gains();
+        // Il tempo trascorso da spenti non conta nel `dt` del PID.
+        self.last_update = self.clock.now();
+        true
+    }
+
+    /// Applica i guadagni in attesa da [`AdaptiveThrottle::restore`], se il
+    /// tuning online è attivo.
+    fn resume_saved_gains(&mut self) {
+        if let Some(tuner) = &mut self.tuner {
+            if let Some(gains) = self.saved_gains.take() {
+                self.config.set_gains(tuner.resume(gains));
+            }
+        }
+    }
+
+    /// Registra la profondità della coda di input utente.
+    ///
+    /// Viene considerata al prossimo aggiornamento del livello (vedi
+    /// [`AdaptiveThrottle::record_tick_latency`]): va quindi chiamato prima
+    /// di registrare i tempi del tick.
+    pub const fn record_queue_depth(&mut self, depth: usize, capacity: usize) {
+        self.queue_depth = depth;
+        self.queue_capacity = capacity;
+    }
+
     /// Aggiorna il PID controller in base alla latenza misurata.
     fn update_pid_controller(&mut self, latency_ms: f64) {
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_update).as_secs_f64();
+        let now = self.clock.now();
+        let dt = now
+            .saturating_duration_since(self.last_update)
+            .as_secs_f64();
         self.last_update = now;
 
         // Evita divisione per zero o dt troppo piccoli
@@ -266,49 +802,104 @@ impl AdaptiveThrottle {
             return;
         }
 
-        // Errore: positivo se siamo più veloci del target (buono),
-        //         negativo se siamo più lenti (cattivo)
-        let error = self.config.target_latency_ms - latency_ms;
-
-        // Termine proporzionale
-        let p = self.config.kp * error;
-
-        // Termine integrale (con anti-windup: clamp a ±10)
-        self.integral += error * dt;
-        self.integral = self.integral.clamp(-10.0, 10.0);
-        let i = self.config.ki * self.integral;
-
-        // Termine derivativo
-        let derivative = (error - self.last_error) / dt;
-        let d = self.config.kd * derivative;
-
-        self.last_error = error;
-
-        // Output PID: quanto dobbiamo aggiustare l'intensità
-        let output = p + i + d;
+        if let Some(trace) = &mut self.trace {
+            trace.record(TraceSample {
+                dt_ms: dt * 1_000.0,
+                intensity: self.intensity,
+                latency_ms,
+            });
+        }
 
-        // Intensità: 1.0 = piena potenza, 0.0 = nulla
-        // Partiamo da 1.0 e sottraiamo l'output se negativo (latenza alta)
-        self.intensity = (1.0 + output * 0.1).clamp(0.0, 1.0);
+        self.intensity = pid_step(
+            &self.config,
+            &mut self.integral,
+            &mut self.last_error,
+            latency_ms,
+            dt,
+        );
+
+        if let Some(tuner) = &mut self.tuner {
+            if let Some(gains) = tuner.observe(self.last_error / self.config.target_latency_ms) {
+                self.config.set_gains(gains);
+            }
+        }
     }
 
-    /// Aggiorna il livello di throttle in base all'intensità e alle soglie.
+    /// Aggiorna il livello di throttle in base a intensità, coda e soglie.
     fn update_throttle_level(&mut self) {
-        // Survival: latenza oltre la soglia critica
-        if self.last_latency_ms > self.config.survival_threshold_ms {
-            self.current_level = ThrottleLevel::Survival;
-            self.intensity = 0.0; // forza intensità a zero
-            return;
+        let config = &self.config;
+        let latency_level = if self.last_latency_ms > config.survival_threshold_ms
+            || (self.latency_level == ThrottleLevel::Survival
+                && self.last_latency_ms > config.survival_exit_ms)
+        {
+            // Survival: latenza oltre la soglia critica (o non ancora rientrata)
+            ThrottleLevel::Survival
+        } else if self.intensity < config.throttled_threshold
+            || (self.latency_level >= ThrottleLevel::Throttled
+                && self.intensity < config.throttled_exit_threshold)
+        {
+            // Throttled: intensità sotto soglia (o non ancora risalita)
+            ThrottleLevel::Throttled
+        } else {
+            ThrottleLevel::Normal
+        };
+        self.latency_level = latency_level;
+
+        let pressure = self.queue_pressure();
+        let queue_level = if pressure >= self.config.queue_survival_ratio {
+            ThrottleLevel::Survival
+        } else if pressure >= self.config.queue_throttled_ratio {
+            ThrottleLevel::Throttled
+        } else {
+            ThrottleLevel::Normal
+        };
+
+        let system_level = self.config.guardrails.level(&self.system_load);
+
+        let target = latency_level.max(queue_level).max(system_level);
+        let cause = if target == ThrottleLevel::Normal {
+            TransitionCause::Recovered
+        } else if system_level == target {
+            TransitionCause::SystemLoad
+        } else if queue_level == target {
+            TransitionCause::QueuePressure
+        } else {
+            TransitionCause::Latency
+        };
+
+        // Si sale subito; si scende solo dopo la permanenza minima.
+        let min_dwell = match self.current_level {
+            ThrottleLevel::Normal => Duration::ZERO,
+            ThrottleLevel::Throttled => self.config.throttled_min_dwell,
+            ThrottleLevel::Survival => self.config.survival_min_dwell,
+        };
+        if target > self.current_level
+            || (target < self.current_level && self.clock.elapsed(self.level_since) >= min_dwell)
+        {
+            self.transition_to(target, cause);
         }
 
-        // Throttled: intensità sotto soglia
-        if self.intensity < self.config.throttled_threshold {
-            self.current_level = ThrottleLevel::Throttled;
-            return;
+        if self.current_level == ThrottleLevel::Survival {
+            self.intensity = 0.0; // forza intensità a zero
         }
+    }
 
-        // Normal: tutto ok
-        self.current_level = ThrottleLevel::Normal;
+    /// Passa a `level` e notifica i sottoscrittori.
+    fn transition_to(&mut self, level: ThrottleLevel, cause: TransitionCause) {
+        let transition = ThrottleTransition {
+            from: self.current_level,
+            to: level,
+            cause,
+            tick: self.tick_count,
+        };
+        info!(
+            "Throttle {} -> {} ({cause})",
+            transition.from, transition.to
+        );
+        self.current_level = level;
+        self.level_since = self.clock.now();
+        self.transition_subscribers
+            .retain(|tx| tx.send(transition).is_ok());
     }
 
     /// Restituisce `true` se le operazioni background sono permesse.
@@ -316,7 +907,7 @@ impl AdaptiveThrottle {
     /// Usato dal [`NeuroNode::tick`] per decidere se eseguire training,
     /// snapshot, meta-observer, ecc.
     #[must_use]
-    pub fn allow_background(&self) -> bool {
+    pub const fn allow_background(&self) -> bool {
         self.current_level.allows_background()
     }
 
@@ -341,6 +932,42 @@ impl AdaptiveThrottle {
         self.last_latency_ms
     }
 
+    /// Restituisce il tempo speso nella corsia critical nell'ultimo tick, in ms.
+    #[must_use]
+    pub const fn last_critical_latency_ms(&self) -> f64 {
+        self.last_critical_ms
+    }
+
+    /// Restituisce il tempo speso nelle corsie background nell'ultimo tick, in ms.
+    #[must_use]
+    pub const fn last_background_latency_ms(&self) -> f64 {
+        self.last_background_ms
+    }
+
+    /// Restituisce il riempimento della coda di input (0.0-1.0).
+    #[must_use]
+    pub fn queue_pressure(&self) -> f64 {
+        if self.queue_capacity == 0 {
+            return 0.0;
+        }
+        // Le code hanno dimensioni ben sotto 2^52: nessuna perdita di precisione.
+        #[allow(clippy::cast_precision_loss)]
+        let pressure = self.queue_depth as f64 / self.queue_capacity as f64;
+        pressure.min(1.0)
+    }
+
+    /// Restituisce il numero di input utente in coda all'ultima rilevazione.
+    #[must_use]
+    pub const fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// Restituisce l'ultimo campione del carico di sistema.
+    #[must_use]
+    pub const fn system_load(&self) -> &SystemLoad {
+        &self.system_load
+    }
+
     /// Restituisce la media mobile della latenza, in millisecondi.
     #[must_use]
     pub const fn avg_latency_ms(&self) -> f64 {
@@ -356,13 +983,22 @@ impl AdaptiveThrottle {
     /// Reset completo del throttle (utile per test o dopo un restart).
     pub fn reset(&mut self) {
         self.current_level = ThrottleLevel::Normal;
+        self.latency_level = ThrottleLevel::Normal;
+        self.level_since = self.clock.now();
         self.intensity = 1.0;
         self.integral = 0.0;
         self.last_error = 0.0;
-        self.last_update = Instant::now();
+        self.last_update = self.level_since;
         self.tick_count = 0;
+        self.latency_samples = 0;
         self.last_latency_ms = 0.0;
         self.avg_latency_ms = 0.0;
+        self.last_critical_ms = 0.0;
+        self.last_background_ms = 0.0;
+        self.queue_depth = 0;
+        self.queue_capacity = 0;
+        self.last_sample = None;
+        self.system_load = SystemLoad::default();
     }
 }
 
@@ -372,16 +1008,84 @@ impl Default for AdaptiveThrottle {
     }
 }
 
+/// Un passo del PID: aggiorna `integral` e `last_error` e restituisce la
+/// nuova intensità. Condiviso con la simulazione di [`autotune`].
+fn pid_step(
+    config: &ThrottleConfig,
+    integral: &mut f64,
+    last_error: &mut f64,
+    latency_ms: f64,
+    dt: f64,
+) -> f64 {
+    // Errore: positivo se siamo più veloci del target (buono),
+    //         negativo se siamo più lenti (cattivo)
+    let error = config.target_latency_ms - latency_ms;
+
+    // Termine proporzionale
+    let p = config.kp * error;
+
+    // Termine integrale (con anti-windup: clamp a ±10)
+    *integral += error * dt;
+    *integral = integral.clamp(-10.0, 10.0);
+    let i = config.ki * *integral;
+
+    // Termine derivativo
+    let derivative = (error - *last_error) / dt.max(MIN_DERIVATIVE_DT);
+    let d = config.kd * derivative;
+
+    *last_error = error;
+
+    // Output PID: quanto dobbiamo aggiustare l'intensità
+    let output = p + i + d;
+
+    // Intensità: 1.0 = piena potenza, 0.0 = nulla
+    // Partiamo da 1.0 e sottraiamo l'output se negativo (latenza alta)
+    (1.0 + output).clamp(0.0, 1.0)
+}
+
+/// Verifica che `value` sia in `[min, max]` (un NaN non lo è).
+fn ensure_within(field: &str, value: f64, min: f64, max: f64) -> Result<()> {
+    if !(min..=max).contains(&value) {
+        bail!("{field} must be between {min} and {max} (got {value})");
+    }
+    Ok(())
+}
+
+/// Verifica che `value` sia un numero finito non negativo.
+fn ensure_non_negative(field: &str, value: f64) -> Result<()> {
+    if !(value.is_finite() && value >= 0.0) {
+        bail!("{field} must be a non-negative number (got {value})");
+    }
+    Ok(())
+}
+
+/// Verifica che `value` sia un numero finito maggiore di zero.
+fn ensure_positive(field: &str, value: f64) -> Result<()> {
+    if !(value.is_finite() && value > 0.0) {
+        bail!("{field} must be a positive number (got {value})");
+    }
+    Ok(())
+}
+
 /// Converte una [`Duration`] in millisecondi (f64).
 fn duration_to_ms(d: Duration) -> f64 {
-    let secs = d.as_secs() as f64;
-    let nanos = f64::from(d.subsec_nanos());
-    secs * 1_000.0 + nanos / 1_000_000.0
+    d.as_secs_f64() * 1_000.0
+}
+
+/// Istante corrente in millisecondi Unix.
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+        })
 }
 
 #[cfg(test)]
+#[allow(clippy::float_cmp)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
     use std::time::Duration;
 
     #[test]
@@ -412,6 +1116,10 @@ mod tests {
         // Survival threshold più basso per Heavy
         assert!(heavy.survival_threshold_ms < desktop.survival_threshold_ms);
         assert!(desktop.survival_threshold_ms < mobile.survival_threshold_ms);
+
+        for preset in [heavy, desktop, mobile] {
+            preset.validate().unwrap();
+        }
     }
 
     #[test]
@@ -438,18 +1146,14 @@ mod tests {
 
     #[test]
     fn high_latency_triggers_throttling() {
-        let mut throttle = AdaptiveThrottle::new();
-        let high_latency = Duration::from_millis(100); // sopra i 50ms di default
+        let (mut throttle, clock) = simulated(ThrottleConfig::desktop());
 
-        // Simula diversi tick con latenza alta
-        for _ in 0..10 {
-            throttle.record_tick_latency(high_latency);
-            std::thread::sleep(Duration::from_millis(10));
-        }
+        // 100ms: sopra il target di 50ms, sotto la soglia di Survival (200ms).
+        // Già il solo termine proporzionale (0.03·-50) satura l'intensità.
+        let levels = replay(&mut throttle, &clock, &[100; 10]);
 
-        // Dovremmo essere in throttled o survival
-        assert_ne!(throttle.current_level(), ThrottleLevel::Normal);
-        assert!(throttle.current_intensity() < 0.7);
+        assert_eq!(levels, [ThrottleLevel::Throttled; 10]);
+        assert_eq!(throttle.current_intensity(), 0.0);
     }
 
     #[test]
@@ -470,7 +1174,7 @@ mod tests {
         let mut throttle = AdaptiveThrottle::new();
 
         // Porta il throttle in survival
-        let bad_latency = Duration::from_millis(1000);
+        let bad_latency = Duration::from_secs(1);
         throttle.record_tick_latency(bad_latency);
         assert_eq!(throttle.current_level(), ThrottleLevel::Survival);
 
@@ -495,19 +1199,304 @@ mod tests {
     }
 
     #[test]
-    fn pid_controller_reacts_to_consistent_error() {
+    fn record_tick_timing_tracks_lanes_and_feeds_pid() {
         let mut throttle = AdaptiveThrottle::new();
 
-        // Simula 20 tick con latenza moderatamente alta
-        for _ in 0..20 {
-            let latency = Duration::from_millis(70); // sopra target di 50ms
-            throttle.record_tick_latency(latency);
-            std::thread::sleep(Duration::from_millis(5)); // dt realistico
+        throttle.record_tick_timing(&TickTiming {
+            total: Duration::from_millis(120),
+            critical: Duration::from_millis(90),
+            background: Duration::from_millis(25),
+        });
+
+        assert!((throttle.last_latency_ms() - 120.0).abs() < 0.1);
+        assert!((throttle.last_critical_latency_ms() - 90.0).abs() < 0.1);
+        assert!((throttle.last_background_latency_ms() - 25.0).abs() < 0.1);
+        assert!((throttle.avg_latency_ms() - 120.0).abs() < 0.1);
+    }
+
+    /// Preset Desktop senza permanenza minima, per testare le sole soglie.
+    fn desktop_without_dwell() -> ThrottleConfig {
+        ThrottleConfig {
+            throttled_min_dwell: Duration::ZERO,
+            survival_min_dwell: Duration::ZERO,
+            ..ThrottleConfig::desktop()
+        }
+    }
+
+    /// Desktop con un PID solo proporzionale: `intensity = 1 + 0.01 * (50 - latency)`.
+    fn proportional_desktop() -> ThrottleConfig {
+        ThrottleConfig {
+            kp: 0.01,
+            ki: 0.0,
+            kd: 0.0,
+            ..desktop_without_dwell()
         }
+    }
+
+    /// Intervallo simulato tra due tick in [`replay`].
+    const TICK: Duration = Duration::from_millis(100);
+
+    /// Throttle con `config` su un orologio simulato.
+    fn simulated(config: ThrottleConfig) -> (AdaptiveThrottle, ManualClock) {
+        let clock = ManualClock::new();
+        (AdaptiveThrottle::with_clock(config, clock.shared()), clock)
+    }
+
+    /// Registra una traccia di latenze (ms), un tick ogni [`TICK`] di tempo
+    /// simulato, e restituisce i livelli risultanti.
+    fn replay(
+        throttle: &mut AdaptiveThrottle,
+        clock: &ManualClock,
+        trace: &[u64],
+    ) -> Vec<ThrottleLevel> {
+        trace
+            .iter()
+            .map(|&ms| {
+                clock.advance(TICK);
+                throttle.record_tick_latency(Duration::from_millis(ms));
+                throttle.current_level()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queue_pressure_raises_throttle_level() {
+        // Desktop: 0.5 / 0.9
+        let (mut throttle, clock) = simulated(desktop_without_dwell());
+
+        throttle.record_queue_depth(40, 64);
+        assert_eq!(
+            replay(&mut throttle, &clock, &[1]),
+            [ThrottleLevel::Throttled]
+        );
+        // I tick veloci tengono l'intensità piena: il livello dipende dalla coda.
+        assert_eq!(throttle.current_intensity(), 1.0);
+
+        throttle.record_queue_depth(60, 64);
+        assert_eq!(
+            replay(&mut throttle, &clock, &[1]),
+            [ThrottleLevel::Survival]
+        );
+        assert!(!throttle.allow_background());
+        assert!((throttle.queue_pressure() - 60.0 / 64.0).abs() < 1e-9);
+
+        throttle.record_queue_depth(0, 64);
+        assert_eq!(replay(&mut throttle, &clock, &[1]), [ThrottleLevel::Normal]);
+        assert_eq!(throttle.current_intensity(), 1.0);
+    }
+
+    #[test]
+    fn system_guardrails_override_fast_ticks() {
+        let root = std::env::temp_dir().join(format!("samaritan-proc-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("stat"), "cpu  1 0 1 8 0 0 0 0 0 0\n").unwrap();
+        // 2% di RAM disponibile: sotto la soglia di Survival Desktop (5%).
+        std::fs::write(
+            root.join("meminfo"),
+            "MemTotal: 1000000 kB\nMemAvailable: 20000 kB\n",
+        )
+        .unwrap();
+
+        let mut throttle = AdaptiveThrottle::new();
+        throttle.set_sampler(ProcSampler::with_root(&root));
+        throttle.update(&NodeProfile::Desktop);
+        assert_eq!(throttle.current_level(), ThrottleLevel::Survival);
+        assert_eq!(throttle.system_load().mem_available, Some(0.02));
+
+        throttle.record_tick_latency(Duration::from_millis(1));
+        assert_eq!(throttle.current_level(), ThrottleLevel::Survival);
 
-        // L'intensità dovrebbe essere diminuita dal PID
-        assert!(throttle.current_intensity() < 1.0);
-        // La media dovrebbe convergere verso 70ms
-        assert!((throttle.avg_latency_ms() - 70.0).abs() < 5.0);
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn levels_have_separate_enter_and_exit_thresholds() {
+        use ThrottleLevel::{Normal, Survival, Throttled};
+
+        // Throttled: si entra con intensità < 0.6, si esce con >= 0.75.
+        let (mut throttle, clock) = simulated(proportional_desktop());
+        let mut transitions = throttle.subscribe_transitions();
+        assert_eq!(
+            replay(&mut throttle, &clock, &[10, 100, 80, 80, 70, 80]),
+            [Normal, Throttled, Throttled, Throttled, Normal, Normal]
+        );
+
+        // Survival: si entra oltre 200ms, si esce sotto 120ms.
+        assert_eq!(
+            replay(&mut throttle, &clock, &[250, 150, 190, 110, 40]),
+            [Survival, Survival, Survival, Throttled, Normal]
+        );
+
+        let events: Vec<_> = std::iter::from_fn(|| transitions.try_recv().ok())
+            .map(|t| (t.from, t.to, t.cause))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (Normal, Throttled, TransitionCause::Latency),
+                (Throttled, Normal, TransitionCause::Recovered),
+                (Normal, Survival, TransitionCause::Latency),
+                (Survival, Throttled, TransitionCause::Latency),
+                (Throttled, Normal, TransitionCause::Recovered),
+            ]
+        );
+    }
+
+    #[test]
+    fn levels_are_held_for_the_minimum_dwell_time() {
+        let config = ThrottleConfig {
+            survival_min_dwell: Duration::from_millis(500),
+            ..proportional_desktop()
+        };
+        let (mut throttle, clock) = simulated(config);
+        let mut transitions = throttle.subscribe_transitions();
+
+        // Un solo tick lento: Survival resta attivo finché la permanenza
+        // minima non è trascorsa, nonostante i tick veloci successivi.
+        let levels = replay(&mut throttle, &clock, &[500, 10, 10]);
+        assert!(levels.iter().all(|&level| level == ThrottleLevel::Survival));
+        assert_eq!(throttle.current_intensity(), 0.0);
+
+        clock.advance(Duration::from_millis(400));
+        assert_eq!(
+            replay(&mut throttle, &clock, &[10]),
+            [ThrottleLevel::Normal]
+        );
+
+        let first = transitions.try_recv().unwrap();
+        assert_eq!(
+            (first.from, first.to, first.cause),
+            (
+                ThrottleLevel::Normal,
+                ThrottleLevel::Survival,
+                TransitionCause::Latency
+            )
+        );
+        assert_eq!(transitions.try_recv().unwrap().to, ThrottleLevel::Normal);
+        assert!(transitions.try_recv().is_err());
+    }
+
+    #[test]
+    fn simulated_clock_replays_the_exact_intensity_curve() {
+        use ThrottleLevel::{Normal, Throttled};
+
+        // Desktop (target 50ms, Throttled per almeno 2s) con guadagni tondi.
+        let config = ThrottleConfig {
+            kp: 0.01,
+            ki: 0.001,
+            kd: 0.001,
+            ..ThrottleConfig::desktop()
+        };
+        let (mut throttle, clock) = simulated(config);
+        let mut intensities = Vec::new();
+        let mut levels = Vec::new();
+        for ms in [50, 70, 90, 80, 60] {
+            levels.extend(replay(&mut throttle, &clock, &[ms]));
+            intensities.push(throttle.current_intensity());
+        }
+
+        // dt = 0.1s: p = 0.01·e, i = 0.001·Σe·dt, d = 0.001·Δe/dt.
+        let expected = [
+            1.0,
+            1.0 - 0.2 - 0.002 - 0.2,
+            1.0 - 0.4 - 0.006 - 0.2,
+            1.0 - 0.3 - 0.009 + 0.1,
+            1.0, // 1 - 0.1 - 0.010 + 0.2, saturato
+        ];
+        for (actual, expected) in intensities.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-12, "{intensities:?}");
+        }
+        // 0.791 basterebbe a uscire da Throttled, ma la permanenza minima no.
+        assert_eq!(levels, [Normal, Throttled, Throttled, Throttled, Throttled]);
+
+        // Dopo 2s senza tick il `dt` è 2.1s e il livello può scendere.
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(replay(&mut throttle, &clock, &[50]), [Normal]);
+        let expected = 1.0 - 0.01 + 0.01 / 2.1; // i = 0.001·(-10), d = 0.001·10/2.1
+        assert!((throttle.current_intensity() - expected).abs() < 1e-12);
+        assert_eq!(clock.offset(), Duration::from_millis(2_600));
+    }
+
+    #[tokio::test]
+    async fn state_survives_restart_unless_stale() {
+        let dir = std::env::temp_dir().join(format!("samaritan-throttle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(THROTTLE_STATE_FILE);
+        assert!(ThrottleState::load(&path).await.unwrap().is_none());
+
+        let mut throttle = AdaptiveThrottle::new();
+        throttle.record_tick_latency(Duration::from_millis(500));
+        assert_eq!(throttle.current_level(), ThrottleLevel::Survival);
+        throttle.state().save(&path).await.unwrap();
+
+        let saved = ThrottleState::load(&path).await.unwrap().unwrap();
+        let mut restarted = AdaptiveThrottle::new();
+        assert!(restarted.restore(&saved, MAX_STATE_AGE));
+        assert_eq!(restarted.current_level(), ThrottleLevel::Survival);
+        assert_eq!(restarted.current_intensity(), 0.0);
+        assert_eq!(restarted.avg_latency_ms(), throttle.avg_latency_ms());
+
+        let stale = ThrottleState {
+            saved_ms: saved.saved_ms - 2 * 60 * 1_000,
+            ..saved
+        };
+        let mut restarted = AdaptiveThrottle::new();
+        assert!(!restarted.restore(&stale, Duration::from_secs(60)));
+        assert_eq!(restarted.current_level(), ThrottleLevel::Normal);
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(ThrottleState::load(&path).await.is_err());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn online_tuning_and_traces_follow_the_pid() {
+        let preset = ThrottleConfig::desktop().gains();
+        let (mut throttle, clock) = simulated(ThrottleConfig::desktop());
+        throttle.start_trace(3);
+        throttle.set_online_tuning(true);
+
+        // Latenza sempre oltre il target per una finestra intera.
+        replay(&mut throttle, &clock, &[120; autotune::ONLINE_WINDOW]);
+        let tuned = throttle.config().gains();
+        assert!(tuned.kp > preset.kp && tuned.ki > preset.ki, "{tuned}");
+        assert_eq!(throttle.online_tuner().unwrap().adjustments(), 1);
+
+        let trace = throttle.take_trace().unwrap();
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.samples()[0].intensity, 1.0);
+        assert!((trace.samples()[0].dt_ms - 100.0).abs() < 1e-9);
+        assert_eq!(trace.samples()[0].latency_ms, 120.0);
+        assert!(throttle.take_trace().is_none());
+
+        // I guadagni raggiunti sopravvivono al riavvio solo col tuning attivo.
+        let state = throttle.state();
+        assert_eq!(state.tuned_gains, Some(tuned));
+        let mut restarted = AdaptiveThrottle::new();
+        restarted.restore(&state, MAX_STATE_AGE);
+        assert_eq!(restarted.config().gains(), preset);
+        restarted.set_online_tuning(true);
+        assert_eq!(restarted.config().gains(), tuned);
+
+        throttle.set_online_tuning(false);
+        assert_eq!(throttle.config().gains(), preset);
+        assert_eq!(throttle.state().tuned_gains, None);
+    }
+
+    #[test]
+    fn pid_controller_reacts_to_consistent_error() {
+        let (mut throttle, clock) = simulated(ThrottleConfig::desktop());
+
+        // 20 tick a 70ms, 20ms sopra il target Desktop.
+        let levels = replay(&mut throttle, &clock, &[70; 20]);
+
+        // p = 0.03·-20, l'integrale (-20·0.1 a tick) satura a -10 dal quinto
+        // tick e la derivata è nulla dopo il primo.
+        let expected = 1.0 - 0.6 - 0.005; // i = 0.0005·-10
+        assert!((throttle.current_intensity() - expected).abs() < 1e-12);
+        assert_eq!(levels, [ThrottleLevel::Throttled; 20]);
+        // L'EMA parte dal primo campione e con latenza costante non si sposta.
+        assert_eq!(throttle.avg_latency_ms(), 70.0);
     }
 }
@@ -0,0 +1,658 @@
+//! PID gain autotuning.
+//!
+//! I guadagni dei preset di [`ThrottleConfig`] sono scelti a mano; questo
+//! modulo li adatta al nodo reale in due modi.
+//!
+//! **Offline** ([`tune`]): da una [`LatencyTrace`] registrata sul nodo
+//! ([`super::AdaptiveThrottle::start_trace`]) stima un [`PlantModel`] del
+//! primo ordine,
+//!
+//! ```text
+//! latency[k] = a * latency[k-1] + b * intensity[k] + c
+//! ```
+//!
+//! e cerca su una griglia di guadagni (entro [`MIN_GAIN_FACTOR`] -
+//! [`MAX_GAIN_FACTOR`] volte quelli di partenza) quelli che minimizzano il
+//! costo di una simulazione a circuito chiuso: carico registrato, sovraccarico
+//! e ritorno al carico registrato. Il costo pesa la latenza oltre il target,
+//! l'intensità persa e le oscillazioni dell'intensità. Lo stesso tuning è
+//! esposto dal binario `samaritan-autotune`.
+//!
+//! La traccia deve contenere un periodo in cui l'intensità varia (il nodo è
+//! stato sotto carico): a intensità costante il modello non è stimabile.
+//!
+//! **Online** ([`OnlineTuner`]): osserva l'errore di latenza a finestre di
+//! [`ONLINE_WINDOW`] tick e corregge i guadagni di [`ONLINE_STEP`] per
+//! finestra: li abbassa se l'errore oscilla, li alza se la latenza resta
+//! sopra il target, sempre entro gli stessi limiti.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use super::{pid_step, ThrottleConfig};
+
+/// Frazione minima dei guadagni di partenza raggiungibile dal tuning.
+pub const MIN_GAIN_FACTOR: f64 = 0.25;
+
+/// Multiplo massimo dei guadagni di partenza raggiungibile dal tuning.
+pub const MAX_GAIN_FACTOR: f64 = 4.0;
+
+/// Campioni minimi di una traccia per stimare il modello.
+pub const MIN_TRACE_SAMPLES: usize = 20;
+
+/// Tick osservati dal tuner online prima di ogni correzione.
+pub const ONLINE_WINDOW: usize = 50;
+
+/// Correzione relativa dei guadagni per finestra del tuner online.
+pub const ONLINE_STEP: f64 = 0.05;
+
+/// Escursione minima dell'intensità in una traccia utilizzabile.
+const MIN_INTENSITY_SPREAD: f64 = 0.1;
+
+/// Inerzia massima del modello stimato (oltre, la simulazione diverge).
+const MAX_INERTIA: f64 = 0.95;
+
+/// Moltiplicatori dei guadagni provati dalla ricerca offline.
+const SEARCH_FACTORS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// Passi simulati per ciascuna fase (carico, sovraccarico, recupero).
+const PHASE_STEPS: usize = 200;
+
+/// Latenza a piena intensità durante la fase di sovraccarico, in multipli
+/// del target.
+const SIMULATED_OVERLOAD: f64 = 2.0;
+
+/// Peso della latenza oltre il target (relativa al target) nel costo.
+const LATENESS_WEIGHT: f64 = 4.0;
+
+/// Peso delle variazioni di intensità tra due tick nel costo.
+const CHATTER_WEIGHT: f64 = 2.0;
+
+/// Errore relativo sotto il quale il tuner online lo considera rumore.
+const ERROR_DEADBAND: f64 = 0.1;
+
+/// Il tuner online considera oscillante una finestra con almeno un cambio
+/// di segno dell'errore ogni `OSCILLATION_PERIOD` tick.
+const OSCILLATION_PERIOD: usize = 5;
+
+/// Guadagni del PID.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PidGains {
+    /// Coefficiente proporzionale.
+    pub kp: f64,
+    /// Coefficiente integrale.
+    pub ki: f64,
+    /// Coefficiente derivativo.
+    pub kd: f64,
+}
+
+impl PidGains {
+    /// Limita ogni guadagno a [`MIN_GAIN_FACTOR`]-[`MAX_GAIN_FACTOR`] volte
+    /// quello di `base`.
+    #[must_use]
+    pub fn clamp_to(self, base: Self) -> Self {
+        let bound =
+            |gain: f64, base: f64| gain.clamp(base * MIN_GAIN_FACTOR, base * MAX_GAIN_FACTOR);
+        Self {
+            kp: bound(self.kp, base.kp),
+            ki: bound(self.ki, base.ki),
+            kd: bound(self.kd, base.kd),
+        }
+    }
+}
+
+impl fmt::Display for PidGains {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "kp={} ki={} kd={}", self.kp, self.ki, self.kd)
+    }
+}
+
+/// Campione di una traccia di latenze: un tick del nodo.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TraceSample {
+    /// Tempo dal campione precedente, in millisecondi.
+    pub dt_ms: f64,
+    /// Intensità applicata durante il tick.
+    pub intensity: f64,
+    /// Latenza del tick, in millisecondi.
+    pub latency_ms: f64,
+}
+
+/// Traccia di latenze registrata su un nodo, serializzata in JSONL (un
+/// [`TraceSample`] per riga).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyTrace {
+    samples: Vec<TraceSample>,
+    max_samples: usize,
+}
+
+impl LatencyTrace {
+    /// Traccia vuota che accetta al più `max_samples` campioni.
+    #[must_use]
+    pub const fn with_capacity(max_samples: usize) -> Self {
+        Self {
+            samples: Vec::new(),
+            max_samples,
+        }
+    }
+
+    /// Traccia con i campioni indicati.
+    #[must_use]
+    pub const fn from_samples(samples: Vec<TraceSample>) -> Self {
+        Self {
+            samples,
+            max_samples: usize::MAX,
+        }
+    }
+
+    /// Aggiunge un campione; oltre la capacità viene ignorato.
+    pub fn record(&mut self, sample: TraceSample) {
+        if self.samples.len() < self.max_samples {
+            self.samples.push(sample);
+        }
+    }
+
+    /// Campioni registrati.
+    #[must_use]
+    pub fn samples(&self) -> &[TraceSample] {
+        &self.samples
+    }
+
+    /// Numero di campioni.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Restituisce `true` se la traccia è vuota.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Decodifica una traccia JSONL; righe vuote e commenti (`#`) vengono
+    /// ignorati.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se una riga non è un [`TraceSample`] valido.
+    pub fn parse(raw: &str) -> Result<Self> {
+        raw.lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.trim();
+                !line.is_empty() && !line.starts_with('#')
+            })
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Invalid trace sample at line {}", index + 1))
+            })
+            .collect::<Result<_>>()
+            .map(Self::from_samples)
+    }
+
+    /// Codifica la traccia in JSONL.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se un campione non è serializzabile (es. latenza non
+    /// finita).
+    pub fn to_jsonl(&self) -> Result<String> {
+        let mut raw = String::new();
+        for sample in &self.samples {
+            raw.push_str(&serde_json::to_string(sample).context("Unable to encode trace sample")?);
+            raw.push('\n');
+        }
+        Ok(raw)
+    }
+
+    /// Legge una traccia JSONL da disco.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o contiene campioni non
+    /// validi.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let raw = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Unable to read latency trace {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("Invalid latency trace {}", path.display()))
+    }
+
+    /// Scrive la traccia in JSONL su disco.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non può essere scritto.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        tokio::fs::write(path, self.to_jsonl()?)
+            .await
+            .with_context(|| format!("Unable to write latency trace {}", path.display()))
+    }
+}
+
+/// Modello del primo ordine della latenza di un nodo in funzione
+/// dell'intensità (vedi la documentazione del modulo).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlantModel {
+    /// Quanto la latenza di un tick dipende da quella del precedente (`a`).
+    pub inertia: f64,
+    /// Latenza aggiunta per unità di intensità, in ms (`b`).
+    pub intensity_gain_ms: f64,
+    /// Latenza indipendente dall'intensità, in ms (`c`).
+    pub base_ms: f64,
+    /// Intervallo medio tra due tick nella traccia, in secondi.
+    pub tick_secs: f64,
+}
+
+impl PlantModel {
+    /// Stima il modello da una traccia ai minimi quadrati.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la traccia è troppo corta, se l'intensità non varia
+    /// abbastanza o se la latenza non cresce con l'intensità.
+    pub fn fit(trace: &LatencyTrace) -> Result<Self> {
+        let samples = trace.samples();
+        if samples.len() < MIN_TRACE_SAMPLES {
+            bail!(
+                "Latency trace too short: {} samples, need at least {MIN_TRACE_SAMPLES}",
+                samples.len()
+            );
+        }
+        let (low, high) = samples.iter().fold((f64::MAX, f64::MIN), |(low, high), s| {
+            (low.min(s.intensity), high.max(s.intensity))
+        });
+        if high - low < MIN_INTENSITY_SPREAD {
+            bail!(
+                "Latency trace does not excite the node: intensity must vary by at least \
+                 {MIN_INTENSITY_SPREAD} while recording (got {:.2})",
+                high - low
+            );
+        }
+
+        // Equazioni normali per i regressori [latency[k-1], intensity[k], 1].
+        let mut normal = [[0.0; 3]; 3];
+        let mut rhs = [0.0; 3];
+        let mut count = 0.0;
+        let mut dt_ms = 0.0;
+        for pair in samples.windows(2) {
+            let x = [pair[0].latency_ms, pair[1].intensity, 1.0];
+            for (row, &xr) in x.iter().enumerate() {
+                for (col, &xc) in x.iter().enumerate() {
+                    normal[row][col] += xr * xc;
+                }
+                rhs[row] += xr * pair[1].latency_ms;
+            }
+            count += 1.0;
+            dt_ms += pair[1].dt_ms;
+        }
+        let [inertia, intensity_gain_ms, base_ms] = solve3(normal, rhs).context(
+            "Latency trace does not excite the node: latency and intensity are collinear",
+        )?;
+        if intensity_gain_ms <= 0.0 {
+            bail!("Latency does not grow with intensity in the trace, nothing to tune");
+        }
+
+        Ok(Self {
+            inertia: inertia.clamp(0.0, MAX_INERTIA),
+            intensity_gain_ms,
+            base_ms,
+            tick_secs: (dt_ms / count / 1_000.0).max(1e-3),
+        })
+    }
+
+    /// Latenza del prossimo tick.
+    #[must_use]
+    pub fn next_latency(&self, last_ms: f64, intensity: f64) -> f64 {
+        self.inertia
+            .mul_add(
+                last_ms,
+                self.intensity_gain_ms.mul_add(intensity, self.base_ms),
+            )
+            .max(0.0)
+    }
+
+    /// Latenza a regime con intensità costante.
+    #[must_use]
+    pub fn steady_latency(&self, intensity: f64) -> f64 {
+        self.intensity_gain_ms.mul_add(intensity, self.base_ms) / (1.0 - self.inertia)
+    }
+}
+
+impl fmt::Display for PlantModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "latency = {:.3} * previous + {:.2} ms * intensity + {:.2} ms (tick {:.0} ms)",
+            self.inertia,
+            self.intensity_gain_ms,
+            self.base_ms,
+            self.tick_secs * 1_000.0
+        )
+    }
+}
+
+/// Risultato del tuning offline.
+#[derive(Debug, Clone)]
+pub struct TuneReport {
+    /// Configurazione di partenza con i guadagni migliori trovati.
+    pub config: ThrottleConfig,
+    /// Modello stimato dalla traccia.
+    pub model: PlantModel,
+    /// Costo simulato con i guadagni di partenza.
+    pub baseline_cost: f64,
+    /// Costo simulato con i guadagni trovati (`<= baseline_cost`).
+    pub cost: f64,
+}
+
+impl fmt::Display for TuneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "model: {}", self.model)?;
+        writeln!(f, "cost:  {:.4} -> {:.4}", self.baseline_cost, self.cost)?;
+        write!(f, "gains: {}", self.config.gains())
+    }
+}
+
+/// Cerca i guadagni migliori per il nodo che ha prodotto `trace`, partendo
+/// da `base` (di solito il preset del suo profilo).
+///
+/// A parità di costo vengono preferiti i guadagni di partenza.
+///
+/// # Errors
+///
+/// Ritorna errore se dalla traccia non si riesce a stimare un modello
+/// (vedi [`PlantModel::fit`]).
+pub fn tune(trace: &LatencyTrace, base: &ThrottleConfig) -> Result<TuneReport> {
+    let model = PlantModel::fit(trace)?;
+    let base_gains = base.gains();
+    let baseline_cost = simulate(&model, base);
+
+    let mut best = (baseline_cost, base_gains);
+    for kp in SEARCH_FACTORS {
+        for ki in SEARCH_FACTORS {
+            for kd in SEARCH_FACTORS {
+                let gains = PidGains {
+                    kp: base_gains.kp * kp,
+                    ki: base_gains.ki * ki,
+                    kd: base_gains.kd * kd,
+                };
+                let mut candidate = base.clone();
+                candidate.set_gains(gains);
+                let cost = simulate(&model, &candidate);
+                if cost < best.0 - 1e-9 {
+                    best = (cost, gains);
+                }
+            }
+        }
+    }
+
+    let mut config = base.clone();
+    config.set_gains(best.1);
+    Ok(TuneReport {
+        config,
+        model,
+        baseline_cost,
+        cost: best.0,
+    })
+}
+
+/// Simula il PID di `config` sul modello e restituisce il costo medio per
+/// tick.
+fn simulate(model: &PlantModel, config: &ThrottleConfig) -> f64 {
+    let target = config.target_latency_ms;
+    let overload_ms = (SIMULATED_OVERLOAD.mul_add(target, -model.steady_latency(1.0))).max(0.0);
+    let overloaded = PlantModel {
+        base_ms: overload_ms.mul_add(1.0 - model.inertia, model.base_ms),
+        ..*model
+    };
+
+    let mut latency = model.steady_latency(1.0);
+    let mut intensity = 1.0;
+    let (mut integral, mut last_error) = (0.0, 0.0);
+    let mut cost = 0.0;
+    let mut steps = 0.0;
+    for phase in [model, &overloaded, model] {
+        for _ in 0..PHASE_STEPS {
+            latency = phase.next_latency(latency, intensity);
+            let next = pid_step(
+                config,
+                &mut integral,
+                &mut last_error,
+                latency,
+                model.tick_secs,
+            );
+            let lateness = ((latency - target) / target).max(0.0);
+            cost += CHATTER_WEIGHT.mul_add(
+                (next - intensity).abs(),
+                LATENESS_WEIGHT.mul_add(lateness, 1.0 - next),
+            );
+            steps += 1.0;
+            intensity = next;
+        }
+    }
+    cost / steps
+}
+
+/// Risolve un sistema 3x3 per eliminazione gaussiana con pivoting; `None`
+/// se la matrice è (quasi) singolare.
+fn solve3(mut matrix: [[f64; 3]; 3], mut rhs: [f64; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot =
+            (col..3).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        let scale = matrix
+            .iter()
+            .flatten()
+            .fold(0.0_f64, |max, v| max.max(v.abs()));
+        if matrix[pivot][col].abs() <= scale * 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let pivot_row = matrix[col];
+        for row in col + 1..3 {
+            let factor = matrix[row][col] / pivot_row[col];
+            for (value, pivot) in matrix[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    let mut solution = [0.0; 3];
+    for row in (0..3).rev() {
+        let known: f64 = (row + 1..3).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+/// Tuning online dei guadagni, a piccoli passi ed entro limiti sicuri
+/// attorno ai guadagni di partenza.
+#[derive(Debug, Clone)]
+pub struct OnlineTuner {
+    base: PidGains,
+    gains: PidGains,
+    window: Vec<f64>,
+    adjustments: u64,
+}
+
+impl OnlineTuner {
+    /// Tuner che parte da (e resta attorno a) `base`.
+    #[must_use]
+    pub fn new(base: PidGains) -> Self {
+        Self {
+            base,
+            gains: base,
+            window: Vec::with_capacity(ONLINE_WINDOW),
+            adjustments: 0,
+        }
+    }
+
+    /// Guadagni di partenza.
+    #[must_use]
+    pub const fn base(&self) -> PidGains {
+        self.base
+    }
+
+    /// Guadagni correnti.
+    #[must_use]
+    pub const fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    /// Correzioni applicate dall'avvio.
+    #[must_use]
+    pub const fn adjustments(&self) -> u64 {
+        self.adjustments
+    }
+
+    /// Riprende da guadagni salvati (limitati attorno a quelli di partenza)
+    /// e li restituisce.
+    pub fn resume(&mut self, gains: PidGains) -> PidGains {
+        self.gains = gains.clamp_to(self.base);
+        self.window.clear();
+        self.gains
+    }
+
+    /// Registra l'errore relativo di un tick, `(target - latency) / target`.
+    ///
+    /// Alla fine di ogni finestra di [`ONLINE_WINDOW`] tick restituisce i
+    /// nuovi guadagni, se sono cambiati.
+    pub fn observe(&mut self, error: f64) -> Option<PidGains> {
+        self.window.push(error);
+        if self.window.len() < ONLINE_WINDOW {
+            return None;
+        }
+        let window = std::mem::take(&mut self.window);
+
+        let significant: Vec<f64> = window
+            .iter()
+            .copied()
+            .filter(|error| error.abs() > ERROR_DEADBAND)
+            .collect();
+        let crossings = significant
+            .windows(2)
+            .filter(|pair| pair[0].signum() != pair[1].signum())
+            .count();
+        let late = significant.iter().filter(|&&error| error < 0.0).count();
+
+        let factor = if crossings * OSCILLATION_PERIOD >= window.len() {
+            // L'errore cambia segno di continuo: il PID reagisce troppo.
+            1.0 - ONLINE_STEP
+        } else if late * 2 >= window.len() {
+            // Latenza stabilmente sopra il target: il PID reagisce troppo poco.
+            1.0 + ONLINE_STEP
+        } else {
+            return None;
+        };
+        let next = PidGains {
+            kp: self.gains.kp * factor,
+            ki: self.gains.ki * factor,
+            kd: self.gains.kd,
+        }
+        .clamp_to(self.base);
+        if next == self.gains {
+            return None;
+        }
+        self.gains = next;
+        self.adjustments += 1;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    const PLANT: PlantModel = PlantModel {
+        inertia: 0.6,
+        intensity_gain_ms: 60.0,
+        base_ms: 8.0,
+        tick_secs: 0.05,
+    };
+
+    /// Traccia senza rumore del modello [`PLANT`], con intensità a onda
+    /// quadra.
+    fn square_wave_trace() -> LatencyTrace {
+        let mut latency = PLANT.steady_latency(1.0);
+        let samples = (0..80)
+            .map(|k| {
+                let intensity = if (k / 10) % 2 == 0 { 1.0 } else { 0.3 };
+                latency = PLANT.next_latency(latency, intensity);
+                TraceSample {
+                    dt_ms: 50.0,
+                    intensity,
+                    latency_ms: latency,
+                }
+            })
+            .collect();
+        LatencyTrace::from_samples(samples)
+    }
+
+    #[test]
+    fn plant_model_is_recovered_from_a_trace() {
+        let trace = square_wave_trace();
+        let raw = trace.to_jsonl().unwrap();
+        assert_eq!(LatencyTrace::parse(&raw).unwrap().len(), trace.len());
+
+        let model = PlantModel::fit(&trace).unwrap();
+        assert!((model.inertia - PLANT.inertia).abs() < 1e-6, "{model}");
+        assert!((model.intensity_gain_ms - PLANT.intensity_gain_ms).abs() < 1e-4);
+        assert!((model.base_ms - PLANT.base_ms).abs() < 1e-4);
+        assert!((model.tick_secs - PLANT.tick_secs).abs() < 1e-9);
+
+        let flat = LatencyTrace::from_samples(vec![
+            TraceSample {
+                dt_ms: 50.0,
+                intensity: 1.0,
+                latency_ms: 40.0,
+            };
+            MIN_TRACE_SAMPLES
+        ]);
+        let err = PlantModel::fit(&flat).unwrap_err();
+        assert!(err.to_string().contains("intensity must vary"), "{err}");
+        assert!(PlantModel::fit(&LatencyTrace::from_samples(Vec::new())).is_err());
+        assert!(LatencyTrace::parse("{\"dt_ms\": 1}\n").is_err());
+    }
+
+    #[test]
+    fn offline_tuning_improves_on_the_preset_within_bounds() {
+        let base = ThrottleConfig::desktop();
+        let report = tune(&square_wave_trace(), &base).unwrap();
+
+        assert!(report.cost < report.baseline_cost, "{report}");
+        let gains = report.config.gains();
+        assert_ne!(gains, base.gains());
+        assert_eq!(gains.clamp_to(base.gains()), gains);
+        assert_eq!(report.config.target_latency_ms, base.target_latency_ms);
+    }
+
+    #[test]
+    fn online_tuner_moves_gains_slowly_within_bounds() {
+        let base = ThrottleConfig::desktop().gains();
+        let mut tuner = OnlineTuner::new(base);
+
+        // Errore che cambia segno a ogni tick: guadagni ridotti del 5%.
+        let oscillating = (0..ONLINE_WINDOW).map(|k| if k % 2 == 0 { 0.5 } else { -0.5 });
+        let updates: Vec<_> = oscillating.filter_map(|e| tuner.observe(e)).collect();
+        assert_eq!(updates.len(), 1);
+        assert!((updates[0].kp / base.kp - 0.95).abs() < 1e-12);
+        assert_eq!(updates[0].kd, base.kd);
+
+        // Errore piccolo: nessuna correzione.
+        assert!((0..ONLINE_WINDOW).all(|_| tuner.observe(0.05).is_none()));
+
+        // Latenza sempre oltre il target: i guadagni salgono fino al limite.
+        for _ in 0..ONLINE_WINDOW * 100 {
+            tuner.observe(-0.5);
+        }
+        assert_eq!(tuner.gains().kp, base.kp * MAX_GAIN_FACTOR);
+        assert_eq!(tuner.gains().ki, base.ki * MAX_GAIN_FACTOR);
+
+        let resumed = tuner.resume(PidGains { kp: 0.0, ..base });
+        assert_eq!(resumed.kp, base.kp * MIN_GAIN_FACTOR);
+    }
+}
@@ -0,0 +1,441 @@
+//! System load sampling from `/proc` (Linux).
+//!
+//! [`ProcSampler`] legge:
+//!
+//! - `/proc/stat`: uso della CPU, come differenza tra due campioni;
+//! - `/proc/meminfo`: frazione di RAM disponibile (`MemAvailable / MemTotal`);
+//! - `/proc/pressure/{cpu,memory,io}`: Pressure Stall Information, valore
+//!   `some avg10` (percentuale di tempo con almeno un task in attesa).
+//!
+//! I file mancanti (kernel senza PSI, sistemi non Linux) producono campi
+//! `None`, ignorati dai guardrail. La radice è iniettabile
+//! ([`ProcSampler::with_root`]) per i test.
+//!
+//! I [`SystemGuardrails`] di [`super::ThrottleConfig`] traducono un
+//! [`SystemLoad`] in un livello minimo di throttle.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+use super::{ensure_within, ThrottleLevel};
+
+/// Radice di default del filesystem `proc`.
+pub const PROC_ROOT: &str = "/proc";
+
+/// Carico di sistema misurato da un [`ProcSampler`].
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct SystemLoad {
+    /// Frazione di CPU occupata (0.0-1.0) dal campione precedente; `None`
+    /// al primo campione.
+    pub cpu_usage: Option<f64>,
+    /// Frazione di RAM disponibile (0.0-1.0).
+    pub mem_available: Option<f64>,
+    /// PSI CPU, `some avg10` (0-100).
+    pub cpu_pressure: Option<f64>,
+    /// PSI memoria, `some avg10` (0-100).
+    pub memory_pressure: Option<f64>,
+    /// PSI I/O, `some avg10` (0-100).
+    pub io_pressure: Option<f64>,
+}
+
+/// Soglie di carico di sistema oltre le quali il throttle viene forzato.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemGuardrails {
+    /// Uso della CPU (0.0-1.0) da cui si va in Throttled mode.
+    pub cpu_throttled_ratio: f64,
+    /// Uso della CPU (0.0-1.0) da cui si va in Survival mode.
+    pub cpu_survival_ratio: f64,
+    /// RAM disponibile (0.0-1.0) sotto la quale si va in Throttled mode.
+    pub mem_available_throttled_ratio: f64,
+    /// RAM disponibile (0.0-1.0) sotto la quale si va in Survival mode.
+    pub mem_available_survival_ratio: f64,
+    /// PSI memoria o I/O (0-100) da cui si va in Throttled mode.
+    pub pressure_throttled_pct: f64,
+    /// PSI memoria o I/O (0-100) da cui si va in Survival mode.
+    pub pressure_survival_pct: f64,
+    /// PSI CPU (0-100) da cui si va in Throttled mode.
+    pub cpu_pressure_throttled_pct: f64,
+    /// PSI CPU (0-100) da cui si va in Survival mode.
+    pub cpu_pressure_survival_pct: f64,
+}
+
+impl SystemGuardrails {
+    /// Preset per nodi Heavy: macchine dedicate, tollerano un carico alto.
+    #[must_use]
+    pub const fn heavy() -> Self {
+        Self {
+            cpu_throttled_ratio: 0.9,
+            cpu_survival_ratio: 0.98,
+            mem_available_throttled_ratio: 0.1,
+            mem_available_survival_ratio: 0.03,
+            pressure_throttled_pct: 25.0,
+            pressure_survival_pct: 60.0,
+            cpu_pressure_throttled_pct: 50.0,
+            cpu_pressure_survival_pct: 85.0,
+        }
+    }
+
+    /// Preset per nodi Desktop: lasciano margine alle applicazioni utente.
+    #[must_use]
+    pub const fn desktop() -> Self {
+        Self {
+            cpu_throttled_ratio: 0.85,
+            cpu_survival_ratio: 0.97,
+            mem_available_throttled_ratio: 0.15,
+            mem_available_survival_ratio: 0.05,
+            pressure_throttled_pct: 20.0,
+            pressure_survival_pct: 50.0,
+            cpu_pressure_throttled_pct: 40.0,
+            cpu_pressure_survival_pct: 75.0,
+        }
+    }
+
+    /// Preset per nodi Mobile: cedono presto per preservare batteria e RAM.
+    #[must_use]
+    pub const fn mobile() -> Self {
+        Self {
+            cpu_throttled_ratio: 0.75,
+            cpu_survival_ratio: 0.95,
+            mem_available_throttled_ratio: 0.2,
+            mem_available_survival_ratio: 0.08,
+            pressure_throttled_pct: 10.0,
+            pressure_survival_pct: 40.0,
+            cpu_pressure_throttled_pct: 25.0,
+            cpu_pressure_survival_pct: 60.0,
+        }
+    }
+
+    /// Verifica che le soglie siano coerenti (le soglie Survival più severe
+    /// di quelle Throttled).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna un errore che nomina il primo campo non valido (es.
+    /// `guardrails.cpu_survival_ratio`).
+    pub fn validate(&self) -> Result<()> {
+        ensure_within(
+            "guardrails.cpu_throttled_ratio",
+            self.cpu_throttled_ratio,
+            0.0,
+            1.0,
+        )?;
+        ensure_within(
+            "guardrails.cpu_survival_ratio",
+            self.cpu_survival_ratio,
+            self.cpu_throttled_ratio,
+            1.0,
+        )?;
+        ensure_within(
+            "guardrails.mem_available_throttled_ratio",
+            self.mem_available_throttled_ratio,
+            0.0,
+            1.0,
+        )?;
+        ensure_within(
+            "guardrails.mem_available_survival_ratio",
+            self.mem_available_survival_ratio,
+            0.0,
+            self.mem_available_throttled_ratio,
+        )?;
+        ensure_within(
+            "guardrails.pressure_throttled_pct",
+            self.pressure_throttled_pct,
+            0.0,
+            100.0,
+        )?;
+        ensure_within(
+            "guardrails.pressure_survival_pct",
+            self.pressure_survival_pct,
+            self.pressure_throttled_pct,
+            100.0,
+        )?;
+        ensure_within(
+            "guardrails.cpu_pressure_throttled_pct",
+            self.cpu_pressure_throttled_pct,
+            0.0,
+            100.0,
+        )?;
+        ensure_within(
+            "guardrails.cpu_pressure_survival_pct",
+            self.cpu_pressure_survival_pct,
+            self.cpu_pressure_throttled_pct,
+            100.0,
+        )
+    }
+
+    /// Livello minimo di throttle imposto da `load`.
+    #[must_use]
+    pub fn level(&self, load: &SystemLoad) -> ThrottleLevel {
+        let above = |value: Option<f64>, threshold: f64| value.is_some_and(|v| v >= threshold);
+        let below = |value: Option<f64>, threshold: f64| value.is_some_and(|v| v <= threshold);
+        let pressure = match (load.memory_pressure, load.io_pressure) {
+            (Some(memory), Some(io)) => Some(memory.max(io)),
+            (memory, io) => memory.or(io),
+        };
+
+        if above(load.cpu_usage, self.cpu_survival_ratio)
+            || below(load.mem_available, self.mem_available_survival_ratio)
+            || above(pressure, self.pressure_survival_pct)
+            || above(load.cpu_pressure, self.cpu_pressure_survival_pct)
+        {
+            ThrottleLevel::Survival
+        } else if above(load.cpu_usage, self.cpu_throttled_ratio)
+            || below(load.mem_available, self.mem_available_throttled_ratio)
+            || above(pressure, self.pressure_throttled_pct)
+            || above(load.cpu_pressure, self.cpu_pressure_throttled_pct)
+        {
+            ThrottleLevel::Throttled
+        } else {
+            ThrottleLevel::Normal
+        }
+    }
+}
+
+impl Default for SystemGuardrails {
+    fn default() -> Self {
+        Self::desktop()
+    }
+}
+
+/// Contatori cumulativi della CPU (in jiffies) letti da `/proc/stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// Campionatore del carico di sistema da `/proc`.
+#[derive(Debug, Clone)]
+pub struct ProcSampler {
+    root: PathBuf,
+    last_cpu: Option<CpuTimes>,
+}
+
+impl ProcSampler {
+    /// Campionatore su [`PROC_ROOT`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_root(PROC_ROOT)
+    }
+
+    /// Campionatore su una radice alternativa (es. directory di test con
+    /// `stat`, `meminfo` e `pressure/`).
+    #[must_use]
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            last_cpu: None,
+        }
+    }
+
+    /// Restituisce `true` se la radice espone almeno `/proc/stat`.
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        self.root.join("stat").is_file()
+    }
+
+    /// Legge un nuovo campione.
+    pub fn sample(&mut self) -> SystemLoad {
+        let cpu = self.read("stat").as_deref().and_then(parse_cpu_times);
+        let cpu_usage = match (self.last_cpu, cpu) {
+            (Some(last), Some(now)) if now.total > last.total => {
+                let busy = now.busy.saturating_sub(last.busy);
+                // Differenze di jiffies tra due campioni: ben sotto 2^52.
+                #[allow(clippy::cast_precision_loss)]
+                let usage = busy as f64 / (now.total - last.total) as f64;
+                Some(usage.clamp(0.0, 1.0))
+            }
+            _ => None,
+        };
+        if cpu.is_some() {
+            self.last_cpu = cpu;
+        }
+
+        SystemLoad {
+            cpu_usage,
+            mem_available: self
+                .read("meminfo")
+                .as_deref()
+                .and_then(parse_mem_available),
+            cpu_pressure: self.read_pressure("cpu"),
+            memory_pressure: self.read_pressure("memory"),
+            io_pressure: self.read_pressure("io"),
+        }
+    }
+
+    fn read(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.root.join(name)).ok()
+    }
+
+    fn read_pressure(&self, resource: &str) -> Option<f64> {
+        self.read(&format!("pressure/{resource}"))
+            .as_deref()
+            .and_then(parse_psi_some_avg10)
+    }
+}
+
+impl Default for ProcSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Legge la riga aggregata `cpu` di `/proc/stat`.
+///
+/// Formato: `cpu user nice system idle iowait irq softirq steal guest guest_nice`;
+/// `guest` è già incluso in `user`, quindi contano solo i primi otto campi.
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if fields.len() < 4 {
+        return None;
+    }
+    let total = fields.iter().sum();
+    let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+/// Calcola `MemAvailable / MemTotal` da `/proc/meminfo`.
+fn parse_mem_available(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|kb| kb.parse::<f64>().ok())
+    };
+    let total = field("MemTotal:").filter(|&total| total > 0.0)?;
+    let available = field("MemAvailable:")?;
+    Some((available / total).min(1.0))
+}
+
+/// Legge `avg10` della riga `some` di un file PSI.
+///
+/// Formato: `some avg10=1.23 avg60=0.50 avg300=0.10 total=12345`.
+fn parse_psi_some_avg10(psi: &str) -> Option<f64> {
+    psi.lines()
+        .find_map(|line| line.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "MemTotal:       16000000 kB\nMemFree:  1000000 kB\n\
+                           MemAvailable:    4000000 kB\n";
+
+    #[test]
+    fn proc_files_are_parsed() {
+        assert_eq!(
+            parse_cpu_times("cpu  10 0 10 70 10 0 0 0 5 0\ncpu0 1 2 3 4\n"),
+            Some(CpuTimes {
+                busy: 20,
+                total: 100
+            })
+        );
+        assert_eq!(parse_cpu_times("intr 1 2 3\n"), None);
+        assert_eq!(parse_mem_available(MEMINFO), Some(0.25));
+        assert_eq!(parse_mem_available("MemTotal: 100 kB\n"), None);
+        assert_eq!(
+            parse_psi_some_avg10(
+                "some avg10=12.50 avg60=3.00 avg300=1.00 total=42\n\
+                 full avg10=80.00 avg60=0.00 avg300=0.00 total=7\n"
+            ),
+            Some(12.5)
+        );
+    }
+
+    #[test]
+    fn sampler_reads_an_injected_root() {
+        let root = std::env::temp_dir().join(format!("samaritan-proc-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("pressure")).unwrap();
+        fs::write(root.join("stat"), "cpu  100 0 100 800 0 0 0 0 0 0\n").unwrap();
+        fs::write(root.join("meminfo"), MEMINFO).unwrap();
+        fs::write(
+            root.join("pressure/memory"),
+            "some avg10=30.00 avg60=0.00 avg300=0.00 total=0\n",
+        )
+        .unwrap();
+
+        let mut sampler = ProcSampler::with_root(&root);
+        assert!(sampler.is_available());
+        let first = sampler.sample();
+        assert_eq!(first.cpu_usage, None);
+        assert_eq!(first.mem_available, Some(0.25));
+        assert_eq!(first.memory_pressure, Some(30.0));
+        assert_eq!(first.io_pressure, None);
+
+        // +300 jiffies occupati su +400 totali.
+        fs::write(root.join("stat"), "cpu  250 0 250 900 0 0 0 0 0 0\n").unwrap();
+        let second = sampler.sample();
+        assert_eq!(second.cpu_usage, Some(0.75));
+
+        assert!(!ProcSampler::with_root(root.join("missing")).is_available());
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn guardrails_map_load_to_levels() {
+        let guardrails = SystemGuardrails::desktop();
+        let idle = SystemLoad {
+            cpu_usage: Some(0.2),
+            mem_available: Some(0.6),
+            ..SystemLoad::default()
+        };
+        assert_eq!(guardrails.level(&idle), ThrottleLevel::Normal);
+        assert_eq!(
+            guardrails.level(&SystemLoad::default()),
+            ThrottleLevel::Normal
+        );
+
+        let busy_cpu = SystemLoad {
+            cpu_usage: Some(0.9),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&busy_cpu), ThrottleLevel::Throttled);
+
+        let io_stall = SystemLoad {
+            io_pressure: Some(55.0),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&io_stall), ThrottleLevel::Survival);
+
+        // La PSI CPU ha soglie proprie, più alte di quelle memoria/I/O.
+        let cpu_stall = SystemLoad {
+            cpu_pressure: Some(30.0),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&cpu_stall), ThrottleLevel::Normal);
+        let cpu_stall = SystemLoad {
+            cpu_pressure: Some(45.0),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&cpu_stall), ThrottleLevel::Throttled);
+        let cpu_stall = SystemLoad {
+            cpu_pressure: Some(80.0),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&cpu_stall), ThrottleLevel::Survival);
+
+        let low_memory = SystemLoad {
+            mem_available: Some(0.02),
+            ..idle
+        };
+        assert_eq!(guardrails.level(&low_memory), ThrottleLevel::Survival);
+    }
+}
@@ -0,0 +1,76 @@
+//! `samaritan-autotune`: stima i guadagni del PID del throttle da una
+//! traccia di latenze registrata su un nodo (vedi `/trace` nella REPL di
+//! `samaritan-node`).
+//!
+//! ```text
+//! samaritan-autotune [--profile Desktop] trace.jsonl
+//! ```
+//!
+//! Il tuning parte dal preset del profilo indicato (default: Desktop) e
+//! stampa il modello stimato, il costo simulato prima e dopo e i guadagni
+//! trovati, anche come sezione `throttle:` di `samaritan.yaml`.
+//! Exit code: `0` in caso di successo, `2` per errori di utilizzo, di
+//! caricamento o se la traccia non è utilizzabile.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use samaritan_core::adaptive_throttle::autotune::tune;
+use samaritan_core::adaptive_throttle::{LatencyTrace, ThrottleConfig};
+use samaritan_core::node_profile::NodeProfile;
+
+const USAGE: &str =
+    "usage: samaritan-autotune [--profile <HeavyGpu|HeavyCpu|Desktop|Mobile>] <trace.jsonl>";
+
+fn main() -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(2);
+        }
+    };
+    match runtime.block_on(run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run() -> Result<()> {
+    let mut profile = NodeProfile::Desktop;
+    let mut trace: Option<PathBuf> = None;
+
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--profile") => {
+                let name = args.next().context(USAGE)?;
+                let name = name.to_str().context(USAGE)?;
+                profile = serde_yaml::from_str(name)
+                    .with_context(|| format!("Unknown profile {name:?}"))?;
+            }
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if trace.is_none() => trace = Some(arg.into()),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let trace = trace.context(USAGE)?;
+
+    let samples = LatencyTrace::load(&trace).await?;
+    let report = tune(&samples, &ThrottleConfig::for_profile(&profile))?;
+
+    println!("profile: {profile:?} ({} samples)", samples.len());
+    println!("{report}");
+
+    let gains = report.config.gains();
+    println!("\nsamaritan.yaml:\nthrottle:");
+    println!("  kp: {}\n  ki: {}\n  kd: {}", gains.kp, gains.ki, gains.kd);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! `samaritan-policy-test`: esegue un corpus golden JSONL contro un file di
+//! policy e stampa il report delle differenze.
+//!
+//! ```text
+//! samaritan-policy-test [--policy policy.yaml] corpus.jsonl
+//! ```
+//!
+//! Senza `--policy` viene provata la policy di default inclusa nel crate.
+//! Exit code: `0` se tutti i casi passano, `1` se ci sono differenze, `2`
+//! per errori di utilizzo o di caricamento.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use samaritan_core::policy_core::regression::{load_corpus, run_corpus};
+use samaritan_core::policy_core::rules::RuleSet;
+
+const USAGE: &str = "usage: samaritan-policy-test [--policy <policy.yaml>] <corpus.jsonl>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<bool> {
+    let mut policy: Option<PathBuf> = None;
+    let mut corpus: Option<PathBuf> = None;
+
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--policy") => {
+                policy = Some(args.next().context(USAGE)?.into());
+            }
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return Ok(true);
+            }
+            _ if corpus.is_none() => corpus = Some(arg.into()),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let corpus = corpus.context(USAGE)?;
+
+    let rules = match &policy {
+        Some(path) => RuleSet::load(path)?,
+        None => RuleSet::default_rules(),
+    };
+    let cases = load_corpus(&corpus)?;
+    let report = run_corpus(&rules, &cases)?;
+
+    print!("{report}");
+    Ok(report.passed())
+}
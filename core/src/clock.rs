@@ -0,0 +1,122 @@
+//! Sorgente di tempo monotona iniettabile.
+//!
+//! [`AdaptiveThrottle`](crate::adaptive_throttle::AdaptiveThrottle) e
+//! [`NeuroNode`](crate::NeuroNode) leggono il tempo da un [`Clock`] invece
+//! che da `Instant::now()`: in produzione si usa [`SystemClock`], nei test e
+//! nelle simulazioni un [`ManualClock`] che avanza solo quando richiesto.
+//! Così il `dt` del PID, i tempi minimi di permanenza e gli intervalli di
+//! salvataggio sono deterministici e una traccia di latenze può essere
+//! riprodotta esattamente.
+//!
+//! Il tempo "di muro" usato per la persistenza (`SystemTime`) resta reale.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Sorgente di istanti monotoni.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Istante corrente secondo questo orologio.
+    fn now(&self) -> Instant;
+
+    /// Tempo trascorso da `earlier` (zero se `earlier` è nel futuro).
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// Orologio condiviso tra i sottosistemi del nodo.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Orologio reale, basato su `Instant::now()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// Orologio reale pronto da condividere.
+    #[must_use]
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Orologio simulato: il tempo avanza solo con [`ManualClock::advance`].
+///
+/// I cloni condividono lo stesso tempo, quindi il test può tenere un clone
+/// e far avanzare l'orologio iniettato nel throttle o nel nodo.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    origin: Instant,
+    offset_nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Crea un orologio fermo all'istante corrente.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            offset_nanos: Arc::default(),
+        }
+    }
+
+    /// Fa avanzare l'orologio di `step`.
+    pub fn advance(&self, step: Duration) {
+        let step = u64::try_from(step.as_nanos()).unwrap_or(u64::MAX);
+        self.offset_nanos.fetch_add(step, Ordering::Relaxed);
+    }
+
+    /// Tempo simulato trascorso dalla creazione dell'orologio.
+    #[must_use]
+    pub fn offset(&self) -> Duration {
+        Duration::from_nanos(self.offset_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Clone dell'orologio pronto da iniettare.
+    #[must_use]
+    pub fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.offset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_moves_only_when_advanced() {
+        let clock = ManualClock::new();
+        let shared = clock.shared();
+        let start = shared.now();
+
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(shared.now(), start);
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(shared.elapsed(start), Duration::from_millis(250));
+        assert_eq!(clock.offset(), Duration::from_millis(250));
+        assert_eq!(
+            shared.elapsed(start + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Control plane over a Unix domain socket.
+//!
+//! Permette a un operatore di ispezionare e pilotare un nodo in esecuzione
+//! senza HTTP. Il socket vive in `<data_dir>/control/control.sock`: la
+//! directory ha permessi `0700` prima che il socket esista, così nessun
+//! altro utente può connettersi nemmeno nell'istante tra la creazione e il
+//! `chmod 0600` del socket. Il protocollo è JSON a righe: una richiesta
+//! per riga, una risposta per riga, più richieste sulla stessa connessione.
+//!
+//! ```text
+//! → {"command": "status"}
+//! ← {"ok": true, "result": {"tick_counter": 42, "uptime_ms": 420, ...}}
+//! → {"command": "rollback", "id": "000001"}
+//! ← {"ok": false, "error": "Unknown snapshot 000001"}
+//! ```
+//!
+//! Comandi ([`ControlCommand`]): `status`, `pause-background`, `resume`,
+//! `snapshot-now`, `rollback` (`id`), `set-strict` (`value`: `on`/`off`),
+//! `reload-policy`.
+//!
+//! Come per [`crate::http_api`], il server non possiede il [`NeuroNode`]:
+//! i comandi vengono eseguiti dal loop del nodo prima del tick successivo
+//! ([`ControlPlane`] è un [`TickHook`]).
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::node::TickHook;
+use crate::policy_core::ReloadOutcome;
+use crate::NeuroNode;
+
+/// Directory privata (`0700`) del socket dentro il `data_dir` del nodo.
+pub const CONTROL_DIR: &str = "control";
+
+/// Nome del socket di controllo dentro [`CONTROL_DIR`].
+pub const CONTROL_SOCKET: &str = "control.sock";
+
+/// Lunghezza massima di una richiesta, in byte.
+pub const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Comando del control plane.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ControlCommand {
+    /// Tick eseguiti, uptime, profilo, livello di throttle e stato delle policy.
+    Status,
+    /// Sospende le corsie normal e background (vedi
+    /// [`crate::scheduler::PriorityScheduler::pause_background`]).
+    PauseBackground,
+    /// Riattiva le corsie sospese da `pause-background`.
+    Resume,
+    /// Crea subito uno snapshot del motore neurale.
+    SnapshotNow,
+    /// Riporta il motore neurale allo snapshot indicato.
+    Rollback {
+        /// Id dello snapshot (vedi `snapshot-now`).
+        id: String,
+    },
+    /// Attiva o disattiva la modalità strict del `PolicyCore`.
+    SetStrict {
+        /// Nuovo stato.
+        value: Switch,
+    },
+    /// Ricarica subito il file di policy.
+    ReloadPolicy,
+}
+
+/// Valore `on`/`off` di un comando.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Switch {
+    /// Attivo.
+    On,
+    /// Disattivo.
+    Off,
+}
+
+impl ControlCommand {
+    /// Decodifica una riga di richiesta.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la riga non è un comando valido.
+    pub fn parse(line: &str) -> Result<Self> {
+        serde_json::from_str(line).context("Invalid control command")
+    }
+}
+
+/// Esegue `command` su `node` e ne restituisce il risultato.
+///
+/// # Errors
+///
+/// Ritorna errore se il comando fallisce (es. snapshot inesistente, file di
+/// policy rifiutato): il nodo resta nello stato precedente.
+pub async fn execute(node: &mut NeuroNode, command: ControlCommand) -> Result<Value> {
+    match command {
+        ControlCommand::Status => Ok(json!({
+            "tick_counter": node.tick_counter,
+            "uptime_ms": u64::try_from(node.uptime().as_millis()).unwrap_or(u64::MAX),
+            "profile": node.profile,
+            "throttle": node.adaptive_throttle.current_level().as_str(),
+            "background_paused": node.scheduler.is_background_paused(),
+            "pending_tasks": node.scheduler.pending_tasks(),
+            "strict_mode": node.policy_core.is_strict_mode(),
+            "policy": node.policy_core.version(),
+        })),
+        ControlCommand::PauseBackground => {
+            node.scheduler.pause_background();
+            info!("Background lanes paused by control-plane");
+            Ok(json!({ "background_paused": true }))
+        }
+        ControlCommand::Resume => {
+            node.scheduler.resume_background();
+            info!("Background lanes resumed by control-plane");
+            Ok(json!({ "background_paused": false }))
+        }
+        ControlCommand::SnapshotNow => {
+            let snapshot = node
+                .snapshot_store
+                .create_snapshot(&node.neural_engine)
+                .await?;
+            Ok(json!(snapshot))
+        }
+        ControlCommand::Rollback { id } => {
+            let snapshot = node
+                .snapshot_store
+                .restore_snapshot(&id, &mut node.neural_engine)
+                .await?;
+            info!("Rolled back to snapshot {id} by control-plane");
+            Ok(json!(snapshot))
+        }
+        ControlCommand::SetStrict { value } => {
+            node.policy_core
+                .set_strict_mode(value == Switch::On, "control-plane");
+            Ok(json!({ "strict_mode": node.policy_core.is_strict_mode() }))
+        }
+        ControlCommand::ReloadPolicy => {
+            let outcome = node.policy_core.reload_now();
+            node.meta_observer.record_policy_reload(&outcome);
+            match outcome {
+                ReloadOutcome::Reloaded(version) => Ok(json!({ "policy": version })),
+                ReloadOutcome::Rejected { error } => Err(anyhow!(error)),
+            }
+        }
+    }
+}
+
+/// Comando in attesa di essere eseguito dal loop del nodo.
+#[derive(Debug)]
+struct ControlRequest {
+    command: ControlCommand,
+    reply: oneshot::Sender<Value>,
+}
+
+/// Lato nodo del control plane: esegue i comandi ricevuti prima di ogni tick.
+#[derive(Debug)]
+pub struct ControlPlane {
+    requests: mpsc::UnboundedReceiver<ControlRequest>,
+}
+
+/// Lato server del control plane: gestisce le connessioni al socket.
+#[derive(Debug, Clone)]
+pub struct ControlServer {
+    requests: mpsc::UnboundedSender<ControlRequest>,
+}
+
+/// Crea la coppia control plane/server collegata da un canale.
+#[must_use]
+pub fn channel() -> (ControlPlane, ControlServer) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (
+        ControlPlane { requests: rx },
+        ControlServer { requests: tx },
+    )
+}
+
+/// Apre il socket di controllo in `data_dir`, rimuovendo un socket rimasto
+/// da un'esecuzione precedente.
+///
+/// # Errors
+///
+/// Ritorna errore se un altro nodo sta già ascoltando sul socket o se il
+/// socket o la sua directory non possono essere creati.
+pub async fn bind(data_dir: &Path) -> Result<UnixListener> {
+    let dir = data_dir.join(CONTROL_DIR);
+    tokio::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .await
+        .with_context(|| format!("Unable to create control dir {}", dir.display()))?;
+    // La directory potrebbe esistere già con permessi più larghi.
+    tokio::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+        .await
+        .with_context(|| format!("Unable to restrict control dir {}", dir.display()))?;
+
+    let path = socket_path(data_dir);
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            bail!("Control socket {} is already in use", path.display());
+        }
+        tokio::fs::remove_file(&path)
+            .await
+            .with_context(|| format!("Unable to remove stale socket {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Unable to bind control socket {}", path.display()))?;
+    tokio::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .await
+        .with_context(|| format!("Unable to restrict control socket {}", path.display()))?;
+    Ok(listener)
+}
+
+/// Percorso del socket di controllo per `data_dir`.
+#[must_use]
+pub fn socket_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(CONTROL_DIR).join(CONTROL_SOCKET)
+}
+
+impl TickHook for ControlPlane {
+    async fn before_tick(&mut self, node: &mut NeuroNode) -> Result<()> {
+        while let Ok(request) = self.requests.try_recv() {
+            let response = match execute(node, request.command).await {
+                Ok(result) => json!({ "ok": true, "result": result }),
+                Err(err) => json!({ "ok": false, "error": format!("{err:#}") }),
+            };
+            // Il client può essersi già disconnesso.
+            request.reply.send(response).ok();
+        }
+        Ok(())
+    }
+}
+
+impl ControlServer {
+    /// Accetta connessioni finché `shutdown` non viene cancellato, poi
+    /// rimuove il socket.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il listener smette di accettare connessioni.
+    pub async fn serve(self, listener: UnixListener, shutdown: CancellationToken) -> Result<()> {
+        let path = listener
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(Path::to_path_buf));
+        if let Some(path) = &path {
+            info!("Control plane listening on {}", path.display());
+        }
+        let result = loop {
+            let stream = tokio::select! {
+                () = shutdown.cancelled() => break Ok(()),
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => break Err(err).context("Unable to accept control connection"),
+                },
+            };
+            let server = self.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                if let Err(err) = server.handle(stream, shutdown).await {
+                    debug!("Control connection failed: {err:#}");
+                }
+            });
+        };
+        if let Some(path) = path {
+            tokio::fs::remove_file(&path).await.ok();
+        }
+        result
+    }
+
+    async fn handle(self, stream: UnixStream, shutdown: CancellationToken) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let mut limited = (&mut reader).take(MAX_REQUEST_BYTES as u64 + 1);
+            let read = tokio::select! {
+                () = shutdown.cancelled() => return Ok(()),
+                read = limited.read_line(&mut line) => read.context("Unable to read control request")?,
+            };
+            if read == 0 {
+                return Ok(());
+            }
+            if read > MAX_REQUEST_BYTES {
+                let response = json!({ "ok": false, "error": "request too large" });
+                writer.write_all(format!("{response}\n").as_bytes()).await?;
+                return Ok(());
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match ControlCommand::parse(&line) {
+                Ok(command) => self.dispatch(command).await,
+                Err(err) => json!({ "ok": false, "error": format!("{err:#}") }),
+            };
+            writer.write_all(format!("{response}\n").as_bytes()).await?;
+        }
+    }
+
+    async fn dispatch(&self, command: ControlCommand) -> Value {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let request = ControlRequest {
+            command,
+            reply: reply_tx,
+        };
+        if self.requests.send(request).is_err() {
+            return json!({ "ok": false, "error": "node stopped" });
+        }
+        reply_rx
+            .await
+            .unwrap_or_else(|_| json!({ "ok": false, "error": "node stopped" }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed_from_json_lines() {
+        assert_eq!(
+            ControlCommand::parse(r#"{"command": "status"}"#).unwrap(),
+            ControlCommand::Status
+        );
+        assert_eq!(
+            ControlCommand::parse(r#"{"command": "rollback", "id": "000001"}"#).unwrap(),
+            ControlCommand::Rollback {
+                id: "000001".to_owned()
+            }
+        );
+        assert_eq!(
+            ControlCommand::parse(r#"{"command": "set-strict", "value": "off"}"#).unwrap(),
+            ControlCommand::SetStrict { value: Switch::Off }
+        );
+
+        for invalid in [
+            "status",
+            r#"{"command": "reboot"}"#,
+            r#"{"command": "rollback"}"#,
+            r#"{"command": "set-strict", "value": "maybe"}"#,
+            r#"{"command": "rollback", "id": "000001", "force": true}"#,
+        ] {
+            assert!(ControlCommand::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
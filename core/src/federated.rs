@@ -1,33 +1,70 @@
 //! Federated learning state.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::PathBuf;
 
+/// Stato locale del Federated Learning (DP-SGD, delta, privacy accountant).
 #[derive(Debug)]
 pub struct FederatedState {
     _data_dir: PathBuf,
+    training_enabled: bool,
 }
 
 impl FederatedState {
+    /// Apre (o crea) lo stato federato sotto `data_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory non può essere creata.
     pub async fn new(data_dir: PathBuf) -> Result<Self> {
-        Ok(Self { _data_dir: data_dir })
+        tokio::fs::create_dir_all(&data_dir)
+            .await
+            .with_context(|| format!("Unable to create federated dir {}", data_dir.display()))?;
+
+        Ok(Self {
+            _data_dir: data_dir,
+            training_enabled: true,
+        })
     }
 
+    /// Restituisce `true` se il training locale è abilitato.
+    ///
+    /// # Errors
+    ///
+    /// Riservato a implementazioni che consultano lo stato su disco.
+    #[allow(clippy::unused_async)]
     pub async fn is_training_enabled(&self) -> Result<bool> {
-        Ok(false)
+        Ok(self.training_enabled)
     }
 
+    /// Esegue un'epoca di training locale con l'intensità indicata (0.0-1.0).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il training fallisce.
+    #[allow(clippy::unused_async)]
     pub async fn run_local_epoch(&mut self, _intensity: f64) -> Result<()> {
         Ok(())
     }
 
-    pub fn should_submit_delta(&self) -> bool {
+    /// Restituisce `true` se c'è un delta pronto per essere inviato.
+    #[must_use]
+    pub const fn should_submit_delta(&self) -> bool {
         false
     }
 
+    /// Calcola, comprime e impacchetta il delta federato corrente.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il calcolo del delta fallisce.
+    #[allow(clippy::unused_async)]
     pub async fn compute_and_package_delta(&mut self) -> Result<crate::net::DeltaMessage> {
         Ok(crate::net::DeltaMessage {})
     }
 
-    pub fn disable_training(&mut self) {}
+    /// Disabilita il training locale (es. da configurazione).
+    pub const fn disable_training(&mut self) {
+        self.training_enabled = false;
+    }
 }
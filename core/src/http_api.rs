@@ -0,0 +1,656 @@
+//! Local HTTP chat API (feature `http-api`).
+//!
+//! Server HTTP/1.1 minimale sul runtime tokio, pensato per l'uso locale
+//! (una richiesta per connessione, `Connection: close`):
+//!
+//! - `POST /api/chat`: body JSON `{"message": "...", "session_id": "..."}`
+//!   (`session_id` opzionale, altrimenti ne viene generata una nuova,
+//!   restituita nell'header `X-Session-Id`; se presente deve essere in
+//!   `[A-Za-z0-9_-]`, al più [`MAX_SESSION_ID_BYTES`] byte, o la richiesta
+//!   viene rifiutata con `400`). La risposta è uno stream
+//!   server-sent events: un evento `partial` per frammento e un evento
+//!   `final` con la decisione del [`crate::policy_core::PolicyCore`]. Se la
+//!   coda di input del nodo è piena la chat viene rifiutata con `503` e
+//!   `Retry-After`;
+//! - `GET /api/stats`: metriche del [`crate::meta_observer::MetaObserver`];
+//! - `GET /api/health`: livello di [`ThrottleLevel`] corrente.
+//!
+//! Una richiesta (request line, header e body) va inviata per intero entro
+//! [`REQUEST_READ_TIMEOUT`] (vedi [`ApiServer::with_read_timeout`]),
+//! altrimenti la connessione viene chiusa con `408`: client inattivi o
+//! lentissimi non tengono occupati i socket.
+//!
+//! Il server non tocca direttamente il [`NeuroNode`]: le chat passano da un
+//! canale che il loop del nodo svuota prima di ogni tick ([`ApiBridge::pump`]),
+//! accodandole nell'[`crate::io_layer::IOLayer`] e sottoscrivendo le risposte
+//! della sessione; stato e metriche vengono pubblicati allo stesso punto.
+//!
+//! # Esempio
+//!
+//! ```no_run
+//! use samaritan_core::http_api::run_node_with_api;
+//! use samaritan_core::node::{build_node, NodeConfig};
+//! use tokio::net::TcpListener;
+//! use tokio_util::sync::CancellationToken;
+//!
+//! # async fn demo() -> anyhow::Result<()> {
+//! let config = NodeConfig::load_default()?;
+//! let node = build_node(&config).await?;
+//! let listener = TcpListener::bind("127.0.0.1:8080").await?;
+//!
+//! let shutdown = CancellationToken::new();
+//! let node =
+//!     run_node_with_api(node, config.runtime.tick_interval(), listener, shutdown).await?;
+//! # drop(node);
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::adaptive_throttle::ThrottleLevel;
+use crate::io_layer::{Delivery, PolicyDecision, ResponseEvent, SessionId};
+use crate::meta_observer::MetaStats;
+use crate::node::{run_node_with_hook, TickHook};
+use crate::NeuroNode;
+
+/// Dimensione massima di request line + header, in byte.
+pub const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// Dimensione massima del body di una richiesta, in byte.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Secondi suggeriti al client (`Retry-After`) quando il nodo è sovraccarico.
+pub const RETRY_AFTER_SECS: u64 = 1;
+
+/// Lunghezza massima di un `session_id` fornito dal client, in byte.
+pub const MAX_SESSION_ID_BYTES: usize = 64;
+
+/// Tempo massimo per ricevere una richiesta completa.
+pub const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Stato del nodo pubblicato a ogni tick per `/api/stats` e `/api/health`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    /// Tick eseguiti.
+    pub ticks: u64,
+    /// Tempo dall'avvio del nodo.
+    pub uptime: Duration,
+    /// Livello di throttle corrente.
+    pub throttle: ThrottleLevel,
+    /// Metriche del `MetaObserver`.
+    pub meta: MetaStats,
+}
+
+impl NodeStatus {
+    /// Istantanea dello stato di `node`.
+    #[must_use]
+    pub fn of(node: &NeuroNode) -> Self {
+        Self {
+            ticks: node.tick_counter,
+            uptime: node.uptime(),
+            throttle: node.adaptive_throttle.current_level(),
+            meta: node.meta_observer.stats(),
+        }
+    }
+}
+
+/// Chat in attesa di essere accodata nell'`IOLayer`.
+#[derive(Debug)]
+struct ChatRequest {
+    session_id: SessionId,
+    message: String,
+    /// Risposte della sessione, o `None` se l'input è stato scartato.
+    reply: oneshot::Sender<Option<mpsc::UnboundedReceiver<ResponseEvent>>>,
+}
+
+/// Lato nodo dell'API: va pompato dal loop del nodo tra un tick e l'altro.
+#[derive(Debug)]
+pub struct ApiBridge {
+    requests: mpsc::UnboundedReceiver<ChatRequest>,
+    status: watch::Sender<NodeStatus>,
+}
+
+/// Lato server dell'API: gestisce le connessioni HTTP.
+#[derive(Debug, Clone)]
+pub struct ApiServer {
+    requests: mpsc::UnboundedSender<ChatRequest>,
+    status: watch::Receiver<NodeStatus>,
+    /// Sessioni con uno stream di risposta aperto.
+    busy: Arc<Mutex<HashSet<SessionId>>>,
+    /// Tempo massimo per ricevere una richiesta completa.
+    read_timeout: Duration,
+}
+
+/// Crea la coppia bridge/server collegata a `node`.
+#[must_use]
+pub fn channel(node: &NeuroNode) -> (ApiBridge, ApiServer) {
+    let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+    let (status_tx, status_rx) = watch::channel(NodeStatus::of(node));
+    (
+        ApiBridge {
+            requests: requests_rx,
+            status: status_tx,
+        },
+        ApiServer {
+            requests: requests_tx,
+            status: status_rx,
+            busy: Arc::new(Mutex::new(HashSet::new())),
+            read_timeout: REQUEST_READ_TIMEOUT,
+        },
+    )
+}
+
+impl ApiBridge {
+    /// Accoda nell'`IOLayer` le chat ricevute e pubblica lo stato del nodo.
+    ///
+    /// La sessione viene sottoscritta prima di accodare il messaggio, così
+    /// lo stream non perde nessun frammento. Se la coda è piena il client
+    /// riceve `None`.
+    pub fn pump(&mut self, node: &mut NeuroNode) {
+        while let Ok(request) = self.requests.try_recv() {
+            let responses = node.io_layer.subscribe_session(request.session_id.clone());
+            let accepted = node
+                .io_layer
+                .submit_session_input(request.session_id, request.message);
+            // Il client può essersi già disconnesso: il ricevitore verrà
+            // rimosso alla prima consegna.
+            request.reply.send(accepted.then_some(responses)).ok();
+        }
+        self.status.send_replace(NodeStatus::of(node));
+    }
+}
+
+impl TickHook for ApiBridge {
+    async fn before_tick(&mut self, node: &mut NeuroNode) -> Result<()> {
+        self.pump(node);
+        Ok(())
+    }
+}
+
+impl ApiServer {
+    /// Imposta il tempo massimo per ricevere una richiesta completa
+    /// (default [`REQUEST_READ_TIMEOUT`]).
+    #[must_use]
+    pub const fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    /// Accetta connessioni finché `shutdown` non viene cancellato.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il listener smette di accettare connessioni.
+    pub async fn serve(self, listener: TcpListener, shutdown: CancellationToken) -> Result<()> {
+        if let Ok(addr) = listener.local_addr() {
+            info!("HTTP API listening on http://{addr}");
+        }
+        loop {
+            let (stream, peer) = tokio::select! {
+                () = shutdown.cancelled() => return Ok(()),
+                accepted = listener.accept() => accepted.context("Unable to accept HTTP connection")?,
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = server.handle(stream).await {
+                    debug!("HTTP connection from {peer} failed: {err:#}");
+                }
+            });
+        }
+    }
+
+    async fn handle(self, stream: TcpStream) -> Result<()> {
+        let mut stream = BufReader::new(stream);
+        let read = tokio::time::timeout(self.read_timeout, read_request(&mut stream)).await;
+        let request = match read {
+            Ok(read) => match read? {
+                Ok(request) => request,
+                Err(status) => return write_error(&mut stream, status, status.reason()).await,
+            },
+            Err(_elapsed) => {
+                let status = Status::RequestTimeout;
+                return write_error(&mut stream, status, status.reason()).await;
+            }
+        };
+
+        match (request.method.as_str(), request.path()) {
+            ("POST", "/api/chat") => self.chat(&mut stream, &request.body).await,
+            ("GET", "/api/stats") => {
+                let status = self.status.borrow().clone();
+                let body = json!({
+                    "ticks": status.ticks,
+                    "uptime_ms": u64::try_from(status.uptime.as_millis()).unwrap_or(u64::MAX),
+                    "throttle": status.throttle.as_str(),
+                    "policy": status.meta.policy,
+                    "policy_reloads": status.meta.policy_reloads,
+                    "policy_reload_failures": status.meta.policy_reload_failures,
+                });
+                write_json(&mut stream, Status::Ok, &body).await
+            }
+            ("GET", "/api/health") => {
+                if self.requests.is_closed() {
+                    return write_error(&mut stream, Status::Unavailable, "node stopped").await;
+                }
+                let level = self.status.borrow().throttle;
+                let body = json!({
+                    "status": "ok",
+                    "throttle": level.as_str(),
+                    "background": level.allows_background(),
+                });
+                write_json(&mut stream, Status::Ok, &body).await
+            }
+            (_, "/api/chat" | "/api/stats" | "/api/health") => {
+                write_error(&mut stream, Status::MethodNotAllowed, "method not allowed").await
+            }
+            _ => write_error(&mut stream, Status::NotFound, "not found").await,
+        }
+    }
+
+    async fn chat<S>(&self, stream: &mut S, body: &[u8]) -> Result<()>
+    where
+        S: AsyncWriteExt + Unpin,
+    {
+        let Ok(body) = serde_json::from_slice::<ChatBody>(body) else {
+            return write_error(stream, Status::BadRequest, "expected {\"message\": string}").await;
+        };
+        if body.message.trim().is_empty() {
+            return write_error(stream, Status::BadRequest, "message must not be empty").await;
+        }
+        if body
+            .session_id
+            .as_ref()
+            .is_some_and(|id| !valid_session_id(id.as_str()))
+        {
+            let message =
+                format!("session_id must be 1-{MAX_SESSION_ID_BYTES} characters in [A-Za-z0-9_-]");
+            return write_error(stream, Status::BadRequest, &message).await;
+        }
+        let session_id = body
+            .session_id
+            .unwrap_or_else(|| SessionId::new(uuid::Uuid::new_v4().to_string()));
+
+        // Una sola risposta in streaming per sessione: gli eventi di due
+        // richieste concorrenti non sarebbero distinguibili.
+        let Some(_busy) = BusyGuard::acquire(&self.busy, &session_id) else {
+            return write_error(stream, Status::Conflict, "session has a reply in progress").await;
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let request = ChatRequest {
+            session_id: session_id.clone(),
+            message: body.message,
+            reply: reply_tx,
+        };
+        if self.requests.send(request).is_err() {
+            return write_error(stream, Status::Unavailable, "node stopped").await;
+        }
+        let mut responses = match reply_rx.await {
+            Ok(Some(responses)) => responses,
+            Ok(None) => {
+                let body = json!({ "error": "node overloaded, retry later" });
+                let headers = format!("Retry-After: {RETRY_AFTER_SECS}\r\n");
+                return write_response(stream, Status::Unavailable, &headers, &body).await;
+            }
+            Err(_) => return write_error(stream, Status::Unavailable, "node stopped").await,
+        };
+
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+             X-Session-Id: {session_id}\r\nConnection: close\r\n\r\n"
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.flush().await?;
+
+        while let Some(event) = responses.recv().await {
+            let last = !matches!(event.delivery, Delivery::Partial(_));
+            let (name, data) = match &event.delivery {
+                Delivery::Partial(text) => ("partial", json!({ "text": text })),
+                Delivery::Final(decision) => ("final", decision_json(decision)),
+                Delivery::Overloaded(message) => ("overloaded", json!({ "error": message })),
+            };
+            stream
+                .write_all(format!("event: {name}\ndata: {data}\n\n").as_bytes())
+                .await?;
+            stream.flush().await?;
+            if last {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Un `session_id` del client finisce in un header e nel log: solo
+/// `[A-Za-z0-9_-]`, al più [`MAX_SESSION_ID_BYTES`] byte.
+fn valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_SESSION_ID_BYTES
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// Esegue il loop del nodo (vedi [`crate::node::run_node`]) servendo l'API
+/// HTTP su `listener` finché `shutdown` non viene cancellato.
+///
+/// # Errors
+///
+/// Ritorna il primo errore fatale di [`NeuroNode::tick`] o del server.
+pub async fn run_node_with_api(
+    node: NeuroNode,
+    tick_interval: Duration,
+    listener: TcpListener,
+    shutdown: CancellationToken,
+) -> Result<NeuroNode> {
+    let (mut bridge, server) = channel(&node);
+    let server_shutdown = shutdown.child_token();
+    let server = tokio::spawn(server.serve(listener, server_shutdown.clone()));
+
+    let node = run_node_with_hook(node, tick_interval, shutdown, &mut bridge).await;
+
+    server_shutdown.cancel();
+    server.await.context("HTTP API task panicked")??;
+    node
+}
+
+/// Body di `POST /api/chat`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChatBody {
+    message: String,
+    #[serde(default)]
+    session_id: Option<SessionId>,
+}
+
+/// Rappresentazione JSON di una decisione finale.
+fn decision_json(decision: &PolicyDecision) -> serde_json::Value {
+    json!({
+        "verdict": decision.verdict,
+        "text": decision.text,
+        "matched_rules": decision.matched_rules,
+        "categories": decision.categories.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "reason": decision.reason.as_ref().map(ToString::to_string),
+        "policy": decision.policy,
+    })
+}
+
+/// Marca una sessione come occupata finché il guard è vivo.
+struct BusyGuard<'a> {
+    busy: &'a Mutex<HashSet<SessionId>>,
+    session_id: SessionId,
+}
+
+impl<'a> BusyGuard<'a> {
+    fn acquire(busy: &'a Mutex<HashSet<SessionId>>, session_id: &SessionId) -> Option<Self> {
+        let inserted = busy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(session_id.clone());
+        inserted.then(|| Self {
+            busy,
+            session_id: session_id.clone(),
+        })
+    }
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.busy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.session_id);
+    }
+}
+
+/// Stati HTTP usati dal server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    RequestTimeout,
+    Conflict,
+    PayloadTooLarge,
+    HeaderTooLarge,
+    Unavailable,
+}
+
+impl Status {
+    const fn code(self) -> u16 {
+        match self {
+            Self::Ok => 200,
+            Self::BadRequest => 400,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::RequestTimeout => 408,
+            Self::Conflict => 409,
+            Self::PayloadTooLarge => 413,
+            Self::HeaderTooLarge => 431,
+            Self::Unavailable => 503,
+        }
+    }
+
+    const fn reason(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::BadRequest => "Bad Request",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::RequestTimeout => "Request Timeout",
+            Self::Conflict => "Conflict",
+            Self::PayloadTooLarge => "Payload Too Large",
+            Self::HeaderTooLarge => "Request Header Fields Too Large",
+            Self::Unavailable => "Service Unavailable",
+        }
+    }
+}
+
+/// Richiesta HTTP già letta.
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    target: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Percorso senza query string.
+    fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(self.target.as_str(), |(path, _)| path)
+    }
+}
+
+/// Legge request line, header e body (`Content-Length`).
+///
+/// L'errore esterno è di I/O; quello interno è lo stato HTTP con cui
+/// rifiutare una richiesta malformata.
+async fn read_request<R>(reader: &mut R) -> Result<std::result::Result<Request, Status>>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    let mut header_bytes = 0;
+    let mut line = String::new();
+    if let Err(status) = read_header_line(reader, &mut line, &mut header_bytes).await? {
+        return Ok(Err(status));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(Err(Status::BadRequest));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Ok(Err(Status::BadRequest));
+    }
+    let (method, target) = (method.to_owned(), target.to_owned());
+
+    let mut content_length = 0;
+    loop {
+        if let Err(status) = read_header_line(reader, &mut line, &mut header_bytes).await? {
+            return Ok(Err(status));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(Status::BadRequest));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            let Ok(length) = value.trim().parse::<usize>() else {
+                return Ok(Err(Status::BadRequest));
+            };
+            content_length = length;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            // Niente chunked encoding: i client devono inviare Content-Length.
+            return Ok(Err(Status::BadRequest));
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Ok(Err(Status::PayloadTooLarge));
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .await
+        .context("Unable to read HTTP request body")?;
+    Ok(Ok(Request {
+        method,
+        target,
+        body,
+    }))
+}
+
+/// Legge una riga della testata, entro [`MAX_HEADER_BYTES`] complessivi.
+async fn read_header_line<R>(
+    reader: &mut R,
+    line: &mut String,
+    header_bytes: &mut usize,
+) -> Result<std::result::Result<(), Status>>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    line.clear();
+    let remaining = MAX_HEADER_BYTES - *header_bytes;
+    let read = reader
+        .take(remaining as u64 + 1)
+        .read_line(line)
+        .await
+        .context("Unable to read HTTP request")?;
+    *header_bytes += read;
+    if *header_bytes > MAX_HEADER_BYTES {
+        return Ok(Err(Status::HeaderTooLarge));
+    }
+    if read == 0 || !line.ends_with('\n') {
+        return Ok(Err(Status::BadRequest));
+    }
+    Ok(Ok(()))
+}
+
+async fn write_json<W>(stream: &mut W, status: Status, body: &serde_json::Value) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    write_response(stream, status, "", body).await
+}
+
+/// Scrive una risposta JSON; `headers` sono righe aggiuntive già terminate
+/// da `\r\n`.
+async fn write_response<W>(
+    stream: &mut W,
+    status: Status,
+    headers: &str,
+    body: &serde_json::Value,
+) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         {headers}Connection: close\r\n\r\n{body}",
+        status.code(),
+        status.reason(),
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn write_error<W>(stream: &mut W, status: Status, message: &str) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    write_json(stream, status, &json!({ "error": message })).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(raw: &str) -> std::result::Result<Request, Status> {
+        read_request(&mut raw.as_bytes()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_are_parsed_with_their_body() {
+        let request = parse(
+            "POST /api/chat?debug=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 16\r\n\r\n\
+             {\"message\":\"hi\"}",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path(), "/api/chat");
+        assert_eq!(request.body, br#"{"message":"hi"}"#);
+    }
+
+    #[tokio::test]
+    async fn malformed_or_oversized_requests_are_rejected() {
+        assert_eq!(parse("GET\r\n\r\n").await, Err(Status::BadRequest));
+        assert_eq!(
+            parse("GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n").await,
+            Err(Status::BadRequest)
+        );
+        assert_eq!(
+            parse(&format!(
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY_BYTES + 1
+            ))
+            .await,
+            Err(Status::PayloadTooLarge)
+        );
+        let long = format!(
+            "GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n",
+            "a".repeat(MAX_HEADER_BYTES)
+        );
+        assert_eq!(parse(&long).await, Err(Status::HeaderTooLarge));
+    }
+
+    #[test]
+    fn session_ids_are_restricted_to_a_safe_charset() {
+        assert!(valid_session_id("alice_01-x"));
+        assert!(valid_session_id(&"a".repeat(MAX_SESSION_ID_BYTES)));
+        assert!(!valid_session_id(""));
+        assert!(!valid_session_id(&"a".repeat(MAX_SESSION_ID_BYTES + 1)));
+        assert!(!valid_session_id("a\r\nX-Injected: 1"));
+        assert!(!valid_session_id("../etc"));
+        assert!(!valid_session_id("àlice"));
+    }
+}
@@ -1,34 +1,634 @@
 //! I/O layer for user interactions.
+//!
+//! Ogni input appartiene a una sessione ([`SessionId`]) con la propria
+//! cronologia limitata ([`session::SessionStore`]), persistita sotto il
+//! `data_dir` dell'I/O. [`IOLayer::prepare_model_inputs`] costruisce il
+//! contesto della sessione e lo converte in [`ModelInput`] tramite il
+//! [`Tokenizer`] condiviso con il [`crate::neural_engine::NeuralEngine`].
+//!
+//! Le risposte (anche parziali, durante lo streaming) tornano alla sessione
+//! di origine: ai sottoscrittori della sessione
+//! ([`IOLayer::subscribe_session`]) e a quelli globali
+//! ([`IOLayer::subscribe_responses`]).
+//!
+//! Gli input in attesa stanno in una coda limitata ([`queue::InputQueue`])
+//! servita a turno tra le sessioni; quando è piena l'input viene scartato e
+//! la sessione riceve [`Delivery::Overloaded`].
 
-use anyhow::Result;
+pub mod queue;
+pub mod session;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::neural_engine::DEFAULT_MAX_NEW_TOKENS;
+use crate::tokenizer::Tokenizer;
+use queue::{InputQueue, Shed};
+use session::{ContextTemplate, Role, SessionStore, Turn, SESSIONS_DIR};
+
+pub use crate::policy_core::PolicyDecision;
+
+/// Numero massimo di token di contesto (cronologia + messaggio) di default.
+pub const DEFAULT_MAX_CONTEXT_TOKENS: usize = 1024;
+
+/// Messaggio consegnato a una sessione il cui input è stato scartato.
+pub const OVERLOADED_MESSAGE: &str = "The node is overloaded, please retry later.";
 
 /// Layer di I/O verso l'utente.
 #[derive(Debug)]
 pub struct IOLayer {
-    _data_dir: PathBuf,
+    tokenizer: Arc<Tokenizer>,
+    generation: GenerationParams,
+    template: ContextTemplate,
+    max_context_tokens: usize,
+    sessions: SessionStore,
+    /// Sessioni con un messaggio utente in attesa di risposta finale.
+    awaiting_reply: HashSet<SessionId>,
+    queue: InputQueue,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Destinatario delle risposte: di una sola sessione o di tutte.
+#[derive(Debug)]
+struct Subscriber {
+    session: Option<SessionId>,
+    tx: mpsc::UnboundedSender<ResponseEvent>,
+}
+
+/// Messaggio utente in coda.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserInput {
+    /// Sessione di provenienza.
+    pub session_id: SessionId,
+    /// Testo del messaggio.
+    pub text: String,
 }
 
 impl IOLayer {
-    pub async fn new(data_dir: PathBuf) -> Result<Self> {
-        Ok(Self { _data_dir: data_dir })
+    /// Crea il layer di I/O con persistenza sotto `data_dir` (le sessioni
+    /// in `data_dir/sessions/`).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory non può essere creata.
+    pub async fn new(data_dir: PathBuf, tokenizer: Arc<Tokenizer>) -> Result<Self> {
+        tokio::fs::create_dir_all(&data_dir)
+            .await
+            .with_context(|| format!("Unable to create io dir {}", data_dir.display()))?;
+
+        Ok(Self {
+            tokenizer,
+            generation: GenerationParams::default(),
+            template: ContextTemplate::default(),
+            max_context_tokens: DEFAULT_MAX_CONTEXT_TOKENS,
+            sessions: SessionStore::open(data_dir.join(SESSIONS_DIR)).await?,
+            awaiting_reply: HashSet::new(),
+            queue: InputQueue::default(),
+            subscribers: Vec::new(),
+        })
+    }
+
+    /// Accoda un input della sessione locale ([`SessionId::LOCAL`]).
+    ///
+    /// Ritorna `false` se l'input è stato scartato (vedi
+    /// [`IOLayer::submit_session_input`]).
+    pub fn submit_user_input(&mut self, input: impl Into<String>) -> bool {
+        self.submit_session_input(SessionId::default(), input)
+    }
+
+    /// Accoda un input di `session_id`, che verrà processato dalla corsia
+    /// critical.
+    ///
+    /// Se la coda o la sessione sono piene l'input viene scartato, la
+    /// sessione riceve [`Delivery::Overloaded`] e viene restituito `false`.
+    pub fn submit_session_input(
+        &mut self,
+        session_id: SessionId,
+        input: impl Into<String>,
+    ) -> bool {
+        match self.queue.push(&session_id, input.into()) {
+            Ok(()) => true,
+            Err(shed) => {
+                let reason = match shed {
+                    Shed::QueueFull => "input queue full",
+                    Shed::SessionFull => "too many pending inputs for session",
+                };
+                warn!("Input for session {session_id} shed: {reason}");
+                self.publish(
+                    session_id,
+                    Delivery::Overloaded(OVERLOADED_MESSAGE.to_owned()),
+                );
+                false
+            }
+        }
+    }
+
+    /// Preleva il prossimo input utente (non bloccante), servendo le
+    /// sessioni a turno.
+    pub fn try_recv_user_input(&mut self) -> Option<UserInput> {
+        self.queue.pop()
+    }
+
+    /// Imposta la capacità della coda di input e il limite per sessione.
+    pub fn set_input_queue_limits(&mut self, capacity: usize, per_session: usize) {
+        self.queue.set_limits(capacity, per_session);
+    }
+
+    /// Input in coda.
+    #[must_use]
+    pub const fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Capacità della coda di input.
+    #[must_use]
+    pub const fn queue_capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+
+    /// Input scartati per sovraccarico dall'avvio.
+    #[must_use]
+    pub const fn shed_inputs(&self) -> u64 {
+        self.queue.shed_count()
+    }
+
+    /// Cronologie delle sessioni.
+    #[must_use]
+    pub const fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    /// Cronologie delle sessioni (es. per cambiarne il limite).
+    pub const fn sessions_mut(&mut self) -> &mut SessionStore {
+        &mut self.sessions
+    }
+
+    /// Imposta il formato del contesto costruito dalla cronologia.
+    pub fn set_context_template(&mut self, template: ContextTemplate) {
+        self.template = template;
+    }
+
+    /// Imposta il numero massimo di token di contesto: i turni più vecchi
+    /// vengono esclusi finché il prompt non rientra nel limite (il
+    /// messaggio corrente è sempre incluso).
+    pub const fn set_max_context_tokens(&mut self, max_tokens: usize) {
+        self.max_context_tokens = max_tokens;
+    }
+
+    /// Tokenizer usato per codificare gli input.
+    #[must_use]
+    pub const fn tokenizer(&self) -> &Arc<Tokenizer> {
+        &self.tokenizer
+    }
+
+    /// Parametri di generazione applicati ai nuovi input.
+    #[must_use]
+    pub const fn generation_params(&self) -> &GenerationParams {
+        &self.generation
+    }
+
+    /// Imposta i parametri di generazione per i prossimi input.
+    pub fn set_generation_params(&mut self, params: GenerationParams) {
+        self.generation = params;
+    }
+
+    /// Converte l'input utente nel formato atteso dal modello.
+    ///
+    /// Il prompt è costruito dalla cronologia della sessione più il nuovo
+    /// messaggio (vedi [`ContextTemplate`]), entro il limite di token di
+    /// contesto; il messaggio viene poi aggiunto alla cronologia.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se l'input non può essere codificato.
+    pub async fn prepare_model_inputs(&mut self, input: UserInput) -> Result<ModelInput> {
+        let UserInput { session_id, text } = input;
+        let history = self.sessions.history(&session_id).await;
+
+        // Esclude i turni più vecchi finché il contesto non rientra nel limite.
+        let mut skip = 0;
+        let token_ids = loop {
+            let prompt = self.template.render(history.turns.iter().skip(skip), &text);
+            let token_ids = self
+                .tokenizer
+                .encode(&prompt)
+                .context("Unable to tokenize user input")?;
+            if token_ids.len() <= self.max_context_tokens || skip >= history.turns.len() {
+                break token_ids;
+            }
+            skip += 1;
+        };
+
+        self.sessions
+            .push(
+                &session_id,
+                Turn {
+                    role: Role::User,
+                    text,
+                },
+            )
+            .await;
+        self.awaiting_reply.insert(session_id.clone());
+
+        Ok(ModelInput::new(
+            session_id,
+            token_ids,
+            self.generation.clone(),
+        ))
+    }
+
+    /// Registra un nuovo destinatario di tutte le risposte.
+    ///
+    /// Ogni sottoscrittore riceve tutti gli eventi consegnati dopo la
+    /// registrazione; i ricevitori chiusi vengono rimossi automaticamente.
+    pub fn subscribe_responses(&mut self) -> mpsc::UnboundedReceiver<ResponseEvent> {
+        self.subscribe(None)
+    }
+
+    /// Registra un destinatario delle sole risposte di `session_id`.
+    pub fn subscribe_session(
+        &mut self,
+        session_id: SessionId,
+    ) -> mpsc::UnboundedReceiver<ResponseEvent> {
+        self.subscribe(Some(session_id))
+    }
+
+    fn subscribe(&mut self, session: Option<SessionId>) -> mpsc::UnboundedReceiver<ResponseEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(Subscriber { session, tx });
+        rx
     }
 
-    pub fn try_recv_user_input(&mut self) -> Option<String> {
-        None
+    /// Consegna alla sessione un frammento di risposta o la decisione
+    /// finale del [`crate::policy_core::PolicyCore`].
+    ///
+    /// Una decisione finale chiude il turno: se consentita, il suo testo
+    /// entra nella cronologia come risposta; se bloccante, il messaggio
+    /// utente che l'ha provocata viene rimosso. La cronologia viene poi
+    /// persistita; un errore di scrittura riguarda solo la sessione e viene
+    /// registrato nel log.
+    pub async fn deliver_to_user(&mut self, session_id: &SessionId, delivery: Delivery) {
+        if let Delivery::Final(decision) = &delivery {
+            self.close_turn(session_id, decision).await;
+        }
+        self.publish(session_id.clone(), delivery);
     }
 
-    pub fn prepare_model_inputs(&self, _input: String) -> Result<ModelInput> {
-        Ok(ModelInput {})
+    /// Inoltra `delivery` ai sottoscrittori interessati a `session_id`.
+    fn publish(&mut self, session_id: SessionId, delivery: Delivery) {
+        let event = ResponseEvent {
+            session_id,
+            delivery,
+        };
+        self.subscribers.retain(|subscriber| {
+            subscriber
+                .session
+                .as_ref()
+                .is_some_and(|session| *session != event.session_id)
+                || subscriber.tx.send(event.clone()).is_ok()
+        });
     }
 
-    pub async fn deliver_to_user(&mut self, _decision: PolicyDecision) -> Result<()> {
-        Ok(())
+    /// Aggiorna e persiste la cronologia dopo la decisione finale.
+    async fn close_turn(&mut self, session_id: &SessionId, decision: &PolicyDecision) {
+        if !self.awaiting_reply.remove(session_id) {
+            // Prompt rifiutato prima dell'inferenza: non è mai entrato in cronologia.
+            return;
+        }
+        let limit = self.sessions.history_limit();
+        let history = self.sessions.history(session_id).await;
+        if decision.is_blocked() {
+            if history
+                .turns
+                .back()
+                .is_some_and(|turn| turn.role == Role::User)
+            {
+                history.turns.pop_back();
+            }
+        } else {
+            history.push(
+                Turn {
+                    role: Role::Assistant,
+                    text: decision.text.clone(),
+                },
+                limit,
+            );
+        }
+        if let Err(err) = self.sessions.persist(session_id).await {
+            warn!("Unable to persist history of session {session_id}: {err:#}");
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct ModelInput {}
+/// Identificativo della sessione utente a cui appartiene una richiesta.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionId(String);
 
-#[derive(Debug)]
-pub struct PolicyDecision {}
+impl SessionId {
+    /// Sessione locale di default (es. console del nodo).
+    pub const LOCAL: &'static str = "local";
+
+    /// Crea un identificativo di sessione.
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Restituisce l'identificativo come stringa.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        Self::new(Self::LOCAL)
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Parametri di generazione di una richiesta.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationParams {
+    /// Numero massimo di token da generare.
+    pub max_new_tokens: usize,
+    /// Temperatura di campionamento; `0.0` = decoding greedy.
+    pub temperature: f32,
+    /// Seed del campionamento (se `None`, casuale per richiesta).
+    pub seed: Option<u64>,
+    /// Sequenze che terminano la generazione (escluse dall'output).
+    pub stop_sequences: Vec<String>,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: DEFAULT_MAX_NEW_TOKENS,
+            temperature: 0.0,
+            seed: None,
+            stop_sequences: Vec::new(),
+        }
+    }
+}
+
+/// Input pronto per il motore neurale.
+#[derive(Debug, Clone, Default)]
+pub struct ModelInput {
+    /// Token del prompt, nel vocabolario del modello.
+    pub token_ids: Vec<u32>,
+    /// Maschera di attenzione (1 = token valido, 0 = padding).
+    pub attention_mask: Vec<u8>,
+    /// Sessione che ha originato la richiesta.
+    pub session_id: SessionId,
+    /// Parametri di generazione.
+    pub params: GenerationParams,
+}
+
+impl ModelInput {
+    /// Crea un input senza padding (maschera di attenzione tutta a 1).
+    #[must_use]
+    pub fn new(session_id: SessionId, token_ids: Vec<u32>, params: GenerationParams) -> Self {
+        Self {
+            attention_mask: vec![1; token_ids.len()],
+            token_ids,
+            session_id,
+            params,
+        }
+    }
+}
+
+/// Contenuto consegnato all'utente.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// Testo parziale di una risposta in streaming.
+    Partial(String),
+    /// Decisione finale: chiude la risposta. Il suo testo è quello
+    /// autorevole e sostituisce i frammenti parziali già consegnati (es.
+    /// dopo una redazione o un rifiuto).
+    Final(PolicyDecision),
+    /// Input scartato perché il nodo è sovraccarico: non seguirà alcuna
+    /// risposta.
+    Overloaded(String),
+}
+
+/// Evento di risposta indirizzato a una sessione.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseEvent {
+    /// Sessione destinataria.
+    pub session_id: SessionId,
+    /// Contenuto consegnato.
+    pub delivery: Delivery,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy_core::Stage;
+
+    #[tokio::test]
+    async fn prepare_model_inputs_tokenizes_the_prompt() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        io.set_generation_params(GenerationParams {
+            max_new_tokens: 8,
+            ..GenerationParams::default()
+        });
+
+        let input = io
+            .prepare_model_inputs(UserInput {
+                session_id: SessionId::default(),
+                text: "ciao".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(input.token_ids, vec![99, 105, 97, 111]);
+        assert_eq!(input.attention_mask, vec![1; 4]);
+        assert_eq!(input.session_id.as_str(), SessionId::LOCAL);
+        assert_eq!(input.params.max_new_tokens, 8);
+        assert_eq!(
+            io.tokenizer().decode(&input.token_ids, true).unwrap(),
+            "ciao"
+        );
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn full_queue_sheds_input_with_overloaded_delivery() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        io.set_input_queue_limits(4, 1);
+        let alice = SessionId::new("alice");
+        let mut rx = io.subscribe_session(alice.clone());
+
+        assert!(io.submit_session_input(alice.clone(), "first"));
+        assert!(!io.submit_session_input(alice.clone(), "second"));
+        assert!(io.submit_session_input(SessionId::new("bob"), "hello"));
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ResponseEvent {
+                session_id: alice,
+                delivery: Delivery::Overloaded(OVERLOADED_MESSAGE.to_owned()),
+            }
+        );
+        assert!(rx.try_recv().is_err());
+        assert_eq!((io.queue_len(), io.shed_inputs()), (2, 1));
+        assert_eq!(io.try_recv_user_input().unwrap().text, "first");
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn deliveries_reach_live_subscribers() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        let mut rx = io.subscribe_responses();
+        drop(io.subscribe_responses());
+
+        let session = SessionId::default();
+        io.deliver_to_user(&session, Delivery::Partial("ci".to_owned()))
+            .await;
+        io.deliver_to_user(&session, Delivery::Final(PolicyDecision::allow("ciao")))
+            .await;
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.delivery, Delivery::Partial("ci".to_owned()));
+        let last = rx.recv().await.unwrap();
+        assert_eq!(last.session_id, session);
+        assert_eq!(
+            last.delivery,
+            Delivery::Final(PolicyDecision::allow("ciao"))
+        );
+        assert_eq!(io.subscribers.len(), 1);
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    fn input(session: &str, text: &str) -> UserInput {
+        UserInput {
+            session_id: SessionId::new(session),
+            text: text.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn context_includes_session_history_within_budget() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        let alice = SessionId::new("alice");
+
+        io.prepare_model_inputs(input("alice", "ciao"))
+            .await
+            .unwrap();
+        io.deliver_to_user(&alice, Delivery::Final(PolicyDecision::allow("salve")))
+            .await;
+        // Un'altra sessione non vede la cronologia di `alice`.
+        let other = io.prepare_model_inputs(input("bob", "hey")).await.unwrap();
+        assert_eq!(
+            io.tokenizer().decode(&other.token_ids, true).unwrap(),
+            "hey"
+        );
+
+        let next = io
+            .prepare_model_inputs(input("alice", "come va?"))
+            .await
+            .unwrap();
+        assert_eq!(next.session_id, alice);
+        assert_eq!(
+            io.tokenizer().decode(&next.token_ids, true).unwrap(),
+            "ciao\nsalve\ncome va?"
+        );
+
+        // Oltre il limite di token i turni più vecchi vengono esclusi.
+        io.set_max_context_tokens(12);
+        let trimmed = io.prepare_model_inputs(input("alice", "ok")).await.unwrap();
+        assert_eq!(
+            io.tokenizer().decode(&trimmed.token_ids, true).unwrap(),
+            "come va?\nok"
+        );
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn responses_are_routed_to_their_session() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        let mut alice_rx = io.subscribe_session(SessionId::new("alice"));
+        let mut all_rx = io.subscribe_responses();
+
+        for session in ["bob", "alice"] {
+            io.deliver_to_user(
+                &SessionId::new(session),
+                Delivery::Partial(session.to_owned()),
+            )
+            .await;
+        }
+
+        let event = alice_rx.recv().await.unwrap();
+        assert_eq!(event.delivery, Delivery::Partial("alice".to_owned()));
+        assert!(alice_rx.try_recv().is_err());
+        assert_eq!(all_rx.recv().await.unwrap().session_id.as_str(), "bob");
+        assert_eq!(all_rx.recv().await.unwrap().session_id.as_str(), "alice");
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn history_survives_restart_and_skips_blocked_turns() {
+        let data_dir = std::env::temp_dir().join(format!("samaritan-io-{}", uuid::Uuid::new_v4()));
+        let alice = SessionId::new("alice");
+        {
+            let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+                .await
+                .unwrap();
+            io.prepare_model_inputs(input("alice", "ciao"))
+                .await
+                .unwrap();
+            io.deliver_to_user(&alice, Delivery::Final(PolicyDecision::allow("salve")))
+                .await;
+            io.prepare_model_inputs(input("alice", "proibito"))
+                .await
+                .unwrap();
+            io.deliver_to_user(
+                &alice,
+                Delivery::Final(PolicyDecision::refuse(Stage::Output)),
+            )
+            .await;
+        }
+
+        let mut io = IOLayer::new(data_dir.clone(), Arc::new(Tokenizer::byte_level()))
+            .await
+            .unwrap();
+        let turns = &io.sessions_mut().history(&alice).await.turns;
+        let texts: Vec<_> = turns.iter().map(|turn| turn.text.as_str()).collect();
+        assert_eq!(texts, ["ciao", "salve"]);
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+}
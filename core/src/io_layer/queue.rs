@@ -0,0 +1,181 @@
+//! Bounded, fair input queue.
+//!
+//! Gli input restano in una coda per sessione; [`InputQueue::pop`] serve le
+//! sessioni a turno (round-robin), così una sessione molto attiva non
+//! ritarda le altre. La coda ha una capacità totale e un limite per
+//! sessione: oltre questi l'input viene scartato (load shedding) e il
+//! chiamante deve avvisare l'utente.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::{SessionId, UserInput};
+
+/// Capacità totale di default della coda di input.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+/// Numero massimo di default di input in coda per singola sessione.
+pub const DEFAULT_SESSION_QUEUE_LIMIT: usize = 8;
+
+/// Motivo per cui un input è stato scartato.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shed {
+    /// La coda ha raggiunto la capacità totale.
+    QueueFull,
+    /// La sessione ha già troppi input in attesa.
+    SessionFull,
+}
+
+/// Coda di input limitata con servizio round-robin tra sessioni.
+#[derive(Debug)]
+pub struct InputQueue {
+    capacity: usize,
+    session_limit: usize,
+    pending: HashMap<SessionId, VecDeque<String>>,
+    /// Sessioni con input in coda, nell'ordine in cui verranno servite.
+    rotation: VecDeque<SessionId>,
+    len: usize,
+    shed: u64,
+}
+
+impl InputQueue {
+    /// Crea una coda con i limiti indicati (almeno 1 ciascuno).
+    #[must_use]
+    pub fn new(capacity: usize, session_limit: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            session_limit: session_limit.max(1),
+            pending: HashMap::new(),
+            rotation: VecDeque::new(),
+            len: 0,
+            shed: 0,
+        }
+    }
+
+    /// Modifica i limiti; gli input già in coda non vengono scartati.
+    pub fn set_limits(&mut self, capacity: usize, session_limit: usize) {
+        self.capacity = capacity.max(1);
+        self.session_limit = session_limit.max(1);
+    }
+
+    /// Accoda `text` per `session_id`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna [`Shed`] se la coda o la sessione sono piene: l'input non
+    /// viene accodato.
+    pub fn push(&mut self, session_id: &SessionId, text: String) -> Result<(), Shed> {
+        let shed = if self.len >= self.capacity {
+            Some(Shed::QueueFull)
+        } else if self
+            .pending
+            .get(session_id)
+            .is_some_and(|queue| queue.len() >= self.session_limit)
+        {
+            Some(Shed::SessionFull)
+        } else {
+            None
+        };
+        if let Some(reason) = shed {
+            self.shed += 1;
+            return Err(reason);
+        }
+
+        let queue = self.pending.entry(session_id.clone()).or_default();
+        if queue.is_empty() {
+            self.rotation.push_back(session_id.clone());
+        }
+        queue.push_back(text);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Preleva il prossimo input, dalla prossima sessione di turno.
+    pub fn pop(&mut self) -> Option<UserInput> {
+        let session_id = self.rotation.pop_front()?;
+        let queue = self.pending.get_mut(&session_id)?;
+        let text = queue.pop_front()?;
+        if queue.is_empty() {
+            self.pending.remove(&session_id);
+        } else {
+            self.rotation.push_back(session_id.clone());
+        }
+        self.len -= 1;
+        Some(UserInput { session_id, text })
+    }
+
+    /// Input in coda.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Restituisce `true` se non ci sono input in coda.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Capacità totale.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Limite di input in coda per sessione.
+    #[must_use]
+    pub const fn session_limit(&self) -> usize {
+        self.session_limit
+    }
+
+    /// Input scartati dall'avvio.
+    #[must_use]
+    pub const fn shed_count(&self) -> u64 {
+        self.shed
+    }
+}
+
+impl Default for InputQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_CAPACITY, DEFAULT_SESSION_QUEUE_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(queue: &mut InputQueue, session: &str, text: &str) -> Result<(), Shed> {
+        queue.push(&SessionId::new(session), text.to_owned())
+    }
+
+    #[test]
+    fn sessions_are_served_round_robin() {
+        let mut queue = InputQueue::default();
+        for text in ["a1", "a2", "a3"] {
+            push(&mut queue, "alice", text).unwrap();
+        }
+        push(&mut queue, "bob", "b1").unwrap();
+        push(&mut queue, "carol", "c1").unwrap();
+
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|input| input.text)
+            .collect();
+        assert_eq!(order, ["a1", "b1", "c1", "a2", "a3"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn overflow_is_shed_per_session_and_globally() {
+        let mut queue = InputQueue::new(3, 2);
+        push(&mut queue, "alice", "a1").unwrap();
+        push(&mut queue, "alice", "a2").unwrap();
+        assert_eq!(push(&mut queue, "alice", "a3"), Err(Shed::SessionFull));
+        push(&mut queue, "bob", "b1").unwrap();
+        assert_eq!(push(&mut queue, "carol", "c1"), Err(Shed::QueueFull));
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.shed_count(), 2);
+        queue.pop();
+        push(&mut queue, "carol", "c1").unwrap();
+    }
+}
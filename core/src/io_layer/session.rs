@@ -0,0 +1,420 @@
+//! Conversation sessions.
+//!
+//! Ogni sessione ha una cronologia limitata di turni (utente / assistente),
+//! caricata pigramente e persistita come JSON in
+//! `<io data_dir>/sessions/<sha256(session_id)>.json`. Il [`ContextTemplate`]
+//! trasforma cronologia e nuovo messaggio nel prompt del modello.
+//!
+//! In memoria restano al più [`DEFAULT_MAX_SESSIONS`] cronologie: oltre,
+//! quella usata meno di recente viene scritta su disco e scartata, così un
+//! turno non ancora persistito non va perso. Un file di
+//! sessione illeggibile o corrotto riguarda solo la sua sessione, che
+//! riparte da una cronologia vuota.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::SessionId;
+
+/// Sottodirectory del `data_dir` dell'I/O che contiene le sessioni.
+pub const SESSIONS_DIR: &str = "sessions";
+
+/// Numero massimo di turni conservati per sessione (default).
+pub const DEFAULT_HISTORY_TURNS: usize = 32;
+
+/// Numero massimo di cronologie tenute in memoria (default).
+pub const DEFAULT_MAX_SESSIONS: usize = 1024;
+
+/// Autore di un turno di conversazione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Messaggio dell'utente.
+    User,
+    /// Risposta consegnata all'utente.
+    Assistant,
+}
+
+/// Turno di conversazione.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Turn {
+    /// Autore.
+    pub role: Role,
+    /// Testo (già filtrato dal `PolicyCore`).
+    pub text: String,
+}
+
+/// Cronologia persistita di una sessione.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct History {
+    /// Sessione a cui appartiene la cronologia.
+    pub session_id: SessionId,
+    /// Turni, dal più vecchio al più recente.
+    pub turns: VecDeque<Turn>,
+}
+
+impl History {
+    /// Aggiunge un turno, scartando i più vecchi oltre `limit`.
+    pub fn push(&mut self, turn: Turn, limit: usize) {
+        self.turns.push_back(turn);
+        while self.turns.len() > limit {
+            self.turns.pop_front();
+        }
+    }
+}
+
+/// Formato del prompt costruito dalla cronologia.
+///
+/// Ogni turno diventa `prefisso + testo`, e i turni sono uniti da
+/// `separator`; dopo il messaggio corrente viene aggiunto
+/// `separator + assistant_prefix` solo se `assistant_prefix` non è vuoto.
+/// Con i valori di default (prefissi vuoti) il primo messaggio di una
+/// sessione viene passato al modello così com'è.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextTemplate {
+    /// Prefisso dei turni utente (es. `"User: "`).
+    pub user_prefix: String,
+    /// Prefisso dei turni dell'assistente (es. `"Assistant: "`).
+    pub assistant_prefix: String,
+    /// Separatore tra i turni.
+    pub separator: String,
+}
+
+impl Default for ContextTemplate {
+    fn default() -> Self {
+        Self {
+            user_prefix: String::new(),
+            assistant_prefix: String::new(),
+            separator: "\n".to_owned(),
+        }
+    }
+}
+
+impl ContextTemplate {
+    /// Costruisce il prompt da `turns` (già selezionati) e dal messaggio
+    /// corrente.
+    #[must_use]
+    pub fn render<'a>(&self, turns: impl IntoIterator<Item = &'a Turn>, current: &str) -> String {
+        let mut parts: Vec<String> = turns
+            .into_iter()
+            .map(|turn| {
+                let prefix = match turn.role {
+                    Role::User => &self.user_prefix,
+                    Role::Assistant => &self.assistant_prefix,
+                };
+                format!("{prefix}{}", turn.text)
+            })
+            .collect();
+        parts.push(format!("{}{current}", self.user_prefix));
+        if !self.assistant_prefix.is_empty() {
+            parts.push(self.assistant_prefix.trim_end().to_owned());
+        }
+        parts.join(&self.separator)
+    }
+}
+
+/// Cronologie delle sessioni, caricate da disco alla prima richiesta.
+#[derive(Debug)]
+pub struct SessionStore {
+    dir: PathBuf,
+    limit: usize,
+    max_sessions: usize,
+    uses: u64,
+    sessions: HashMap<SessionId, Cached>,
+}
+
+/// Cronologia in memoria con l'istante (in accessi) dell'ultimo uso.
+#[derive(Debug)]
+struct Cached {
+    history: History,
+    used: u64,
+}
+
+impl SessionStore {
+    /// Apre (o crea) l'archivio delle sessioni in `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory non può essere creata.
+    pub async fn open(dir: PathBuf) -> Result<Self> {
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Unable to create sessions dir {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            limit: DEFAULT_HISTORY_TURNS,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            uses: 0,
+            sessions: HashMap::new(),
+        })
+    }
+
+    /// Numero massimo di turni conservati per sessione.
+    #[must_use]
+    pub const fn history_limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Imposta il numero massimo di turni per sessione (almeno 1).
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        for cached in self.sessions.values_mut() {
+            while cached.history.turns.len() > self.limit {
+                cached.history.turns.pop_front();
+            }
+        }
+    }
+
+    /// Numero massimo di cronologie tenute in memoria.
+    #[must_use]
+    pub const fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    /// Imposta il numero massimo di cronologie in memoria (almeno 1).
+    pub async fn set_max_sessions(&mut self, max_sessions: usize) {
+        self.max_sessions = max_sessions.max(1);
+        while self.sessions.len() > self.max_sessions {
+            self.evict_least_recent().await;
+        }
+    }
+
+    /// Cronologia di `session_id`, caricata da disco se necessario.
+    ///
+    /// Se il file della sessione non è leggibile la sessione riparte da
+    /// una cronologia vuota (l'errore viene solo registrato nel log).
+    pub async fn history(&mut self, session_id: &SessionId) -> &mut History {
+        self.uses += 1;
+        let used = self.uses;
+        let loaded = if self.sessions.contains_key(session_id) {
+            None
+        } else {
+            while self.sessions.len() >= self.max_sessions {
+                self.evict_least_recent().await;
+            }
+            Some(self.load(session_id).await)
+        };
+        let cached = self
+            .sessions
+            .entry(session_id.clone())
+            .or_insert_with(|| Cached {
+                history: loaded.unwrap_or_default(),
+                used,
+            });
+        cached.used = used;
+        &mut cached.history
+    }
+
+    /// Aggiunge un turno alla sessione (senza persisterlo).
+    pub async fn push(&mut self, session_id: &SessionId, turn: Turn) {
+        let limit = self.limit;
+        self.history(session_id).await.push(turn, limit);
+    }
+
+    /// Scrive su disco la cronologia di `session_id`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non può essere scritto.
+    pub async fn persist(&self, session_id: &SessionId) -> Result<()> {
+        let Some(Cached { history, .. }) = self.sessions.get(session_id) else {
+            return Ok(());
+        };
+        let path = self.path(session_id);
+        let tmp = path.with_extension("json.tmp");
+        let raw = serde_json::to_vec_pretty(history).context("Unable to encode session")?;
+        tokio::fs::write(&tmp, raw)
+            .await
+            .with_context(|| format!("Unable to write session {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .with_context(|| format!("Unable to persist session {}", path.display()))
+    }
+
+    /// Sessioni caricate in memoria.
+    pub fn loaded(&self) -> impl Iterator<Item = &SessionId> {
+        self.sessions.keys()
+    }
+
+    /// Scrive su disco e scarta dalla memoria la cronologia usata meno di
+    /// recente. Se la scrittura fallisce la cronologia viene scartata
+    /// comunque (l'errore viene solo registrato nel log).
+    async fn evict_least_recent(&mut self) {
+        let oldest = self
+            .sessions
+            .iter()
+            .min_by_key(|(_, cached)| cached.used)
+            .map(|(session_id, _)| session_id.clone());
+        if let Some(session_id) = oldest {
+            if let Err(err) = self.persist(&session_id).await {
+                warn!("Unable to persist evicted session {session_id}: {err:#}");
+            }
+            self.sessions.remove(&session_id);
+        }
+    }
+
+    /// Carica la cronologia di `session_id`, vuota se il file manca o non
+    /// è valido.
+    async fn load(&self, session_id: &SessionId) -> History {
+        let path = self.path(session_id);
+        match read_history(&path).await {
+            Ok(Some(mut history)) => {
+                while history.turns.len() > self.limit {
+                    history.turns.pop_front();
+                }
+                history
+            }
+            Ok(None) => History {
+                session_id: session_id.clone(),
+                turns: VecDeque::new(),
+            },
+            Err(err) => {
+                warn!("Session {session_id} starts with an empty history: {err:#}");
+                History {
+                    session_id: session_id.clone(),
+                    turns: VecDeque::new(),
+                }
+            }
+        }
+    }
+
+    /// File della sessione: il nome è lo sha256 dell'id, così qualunque id
+    /// produce un nome di file valido, di lunghezza fissa, che non può
+    /// uscire dalla directory.
+    fn path(&self, session_id: &SessionId) -> PathBuf {
+        session_path(&self.dir, session_id)
+    }
+}
+
+fn session_path(dir: &Path, session_id: &SessionId) -> PathBuf {
+    let digest = Sha256::digest(session_id.as_str().as_bytes());
+    dir.join(format!("{}.json", hex::encode(digest)))
+}
+
+/// Legge un file di sessione (`None` se non esiste).
+async fn read_history(path: &Path) -> Result<Option<History>> {
+    let raw = match tokio::fs::read(path).await {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Unable to read session {}", path.display()))
+        }
+    };
+    serde_json::from_slice(&raw)
+        .map(Some)
+        .with_context(|| format!("Invalid session file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(role: Role, text: &str) -> Turn {
+        Turn {
+            role,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn template_renders_history_then_current_message() {
+        let turns = [turn(Role::User, "ciao"), turn(Role::Assistant, "salve")];
+
+        assert_eq!(
+            ContextTemplate::default().render(&turns, "come va?"),
+            "ciao\nsalve\ncome va?"
+        );
+        assert_eq!(ContextTemplate::default().render(&[], "primo"), "primo");
+
+        let chat = ContextTemplate {
+            user_prefix: "User: ".to_owned(),
+            assistant_prefix: "Assistant: ".to_owned(),
+            separator: "\n".to_owned(),
+        };
+        assert_eq!(
+            chat.render(&turns, "come va?"),
+            "User: ciao\nAssistant: salve\nUser: come va?\nAssistant:"
+        );
+    }
+
+    #[tokio::test]
+    async fn histories_are_bounded_and_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!("samaritan-sessions-{}", uuid::Uuid::new_v4()));
+        let alice = SessionId::new("alice/../x");
+        let bob = SessionId::new("bob");
+
+        let mut store = SessionStore::open(dir.clone()).await.unwrap();
+        store.set_history_limit(2);
+        for text in ["1", "2", "3"] {
+            store.push(&alice, turn(Role::User, text)).await;
+        }
+        store.push(&bob, turn(Role::User, "b")).await;
+        store.persist(&alice).await.unwrap();
+
+        let mut reopened = SessionStore::open(dir.clone()).await.unwrap();
+        let history = reopened.history(&alice).await;
+        assert_eq!(history.session_id, alice);
+        assert_eq!(
+            history
+                .turns
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["2", "3"]
+        );
+        // `bob` non è stato persistito.
+        assert!(reopened.history(&bob).await.turns.is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn long_ids_and_corrupt_files_only_affect_their_session() {
+        let dir = std::env::temp_dir().join(format!("samaritan-sessions-{}", uuid::Uuid::new_v4()));
+        let long = SessionId::new("x".repeat(500));
+        let corrupt = SessionId::new("corrupt");
+
+        let mut store = SessionStore::open(dir.clone()).await.unwrap();
+        store.push(&long, turn(Role::User, "ciao")).await;
+        store.persist(&long).await.unwrap();
+        std::fs::write(session_path(&dir, &corrupt), "{not json").unwrap();
+
+        let mut reopened = SessionStore::open(dir.clone()).await.unwrap();
+        assert_eq!(reopened.history(&long).await.turns.len(), 1);
+        let history = reopened.history(&corrupt).await;
+        assert_eq!(history.session_id, corrupt);
+        assert!(history.turns.is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_histories_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("samaritan-sessions-{}", uuid::Uuid::new_v4()));
+        let [a, b, c] = ["a", "b", "c"].map(SessionId::new);
+
+        let mut store = SessionStore::open(dir.clone()).await.unwrap();
+        store.set_max_sessions(2).await;
+        store.push(&a, turn(Role::User, "1")).await;
+        store.push(&b, turn(Role::User, "2")).await;
+        store.history(&a).await;
+        store.push(&c, turn(Role::User, "3")).await;
+
+        let mut loaded: Vec<&str> = store.loaded().map(SessionId::as_str).collect();
+        loaded.sort_unstable();
+        assert_eq!(loaded, vec!["a", "c"]);
+
+        // `b` non era mai stato persistito: l'evizione lo scrive su disco.
+        assert_eq!(
+            store.history(&b).await.turns,
+            VecDeque::from([turn(Role::User, "2")])
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
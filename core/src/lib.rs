@@ -1,9 +1,9 @@
-//! Samaritan 1.5 — Heavy/Core NeuroNode library.
+//! Samaritan 1.5 — Heavy/Core `NeuroNode` library.
 //!
-//! Questo crate implementa il **NeuroNode** completo per il profilo Heavy/Core:
+//! Questo crate implementa il **`NeuroNode`** completo per il profilo Heavy/Core:
 //!
 //! - runtime a tick con corsie (critical / background),
-//! - motore neurale (`NeuralEngine<OnnxBackend>`),
+//! - motore neurale (`NeuralEngine`, backend ONNX o CPU di riferimento),
 //! - `PolicyCore` per sicurezza e governance,
 //! - stato federato con DP e Secure Aggregation,
 //! - `MetaObserver` e `MetaBrain` per meta-livello,
@@ -42,10 +42,12 @@
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
+use futures::StreamExt;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 use uuid::Uuid;
 
@@ -53,10 +55,14 @@ use uuid::Uuid;
 pub mod node_profile;
 /// Modulo che implementa il motore neurale (ONNX backend Heavy/Core).
 pub mod neural_engine;
+/// Modulo che implementa il tokenizer (`tokenizer.json` accanto al modello).
+pub mod tokenizer;
 /// Modulo che contiene il core delle policy di sicurezza e governance.
 pub mod policy_core;
 /// Modulo per I/O verso l'utente (chat, stream, ecc.).
 pub mod io_layer;
+/// Modulo con la sorgente di tempo iniettabile (reale o simulata).
+pub mod clock;
 /// Modulo per throttling adattivo basato su latenza / carico.
 pub mod adaptive_throttle;
 /// Modulo che implementa lo scheduler a priorità (critical/normal/background).
@@ -75,18 +81,30 @@ pub mod snapshot_store;
 pub mod update_agent;
 /// Modulo di glue per configurazione e loop di esecuzione del nodo.
 pub mod node;
-
-use adaptive_throttle::AdaptiveThrottle;
+/// Modulo con il control plane su socket Unix per i nodi in esecuzione.
+#[cfg(unix)]
+pub mod control;
+/// Modulo con l'API HTTP locale (chat, statistiche, health).
+#[cfg(feature = "http-api")]
+pub mod http_api;
+
+use adaptive_throttle::{
+    AdaptiveThrottle, ProcSampler, ThrottleState, TickTiming, MAX_STATE_AGE, STATE_SAVE_INTERVAL,
+    THROTTLE_STATE_FILE,
+};
+use clock::{SharedClock, SystemClock};
 use federated::FederatedState;
-use io_layer::IOLayer;
+use io_layer::PolicyDecision;
+use io_layer::{Delivery, IOLayer, ModelInput, SessionId, UserInput};
 use meta_brain::MetaBrain;
 use meta_observer::MetaObserver;
 use net::{DeltaMessage, NetClient};
-use neural_engine::{NeuralEngine, OnnxBackend};
+use neural_engine::{DynBackend, ModelOutput, NeuralEngine, StreamEvent};
 use node_profile::{NodeProfile, NodeProfileDetector};
 use policy_core::PolicyCore;
-use scheduler::PriorityScheduler;
+use scheduler::{Lane, PriorityScheduler, ScheduledWork, TaskKind};
 use snapshot_store::SnapshotStore;
+use tokenizer::Tokenizer;
 use update_agent::UpdateAgent;
 
 /// Identificativo univoco di un nodo.
@@ -110,15 +128,15 @@ pub type TickResult = Result<()>;
 pub struct NeuroNode {
     /// Identificativo del nodo, persistente su disco.
     pub id: NodeId,
-    /// Profilo del nodo (HeavyGpu, HeavyCpu, Desktop, ecc.).
+    /// Profilo del nodo (`HeavyGpu`, `HeavyCpu`, `Desktop`, ecc.).
     pub profile: NodeProfile,
 
     /// Core delle policy (sicurezza, privacy, governance).
     pub policy_core: PolicyCore,
     /// Meta-brain (ADR, distillazione, pruning, ecc.).
     pub meta_brain: MetaBrain,
-    /// Motore neurale principale basato su ONNX.
-    pub neural_engine: NeuralEngine<OnnxBackend>,
+    /// Motore neurale principale (backend scelto in base al modello).
+    pub neural_engine: NeuralEngine,
     /// Strato di I/O verso l’utente (input/output chat, ecc.).
     pub io_layer: IOLayer,
 
@@ -141,26 +159,72 @@ pub struct NeuroNode {
 
     /// Contatore di tick eseguiti.
     pub tick_counter: u64,
-    /// Istant di avvio del nodo (per uptime).
+    /// Istant di avvio del nodo (per uptime), secondo il suo orologio.
     pub start_time: Instant,
+    /// Orologio del nodo, condiviso con l'[`AdaptiveThrottle`].
+    clock: SharedClock,
+
+    /// Delta calcolato ma non ancora inviato (tra `DeltaComputation` e
+    /// `DeltaSubmission`, anche su tick diversi).
+    pending_delta: Option<DeltaMessage>,
+    /// File in cui persistere lo stato dell'`AdaptiveThrottle`.
+    throttle_state_path: PathBuf,
+    /// Ultimo salvataggio dello stato del throttle.
+    throttle_saved_at: Instant,
 }
 
 impl NeuroNode {
     /// Bootstrappa un [`NeuroNode`] Heavy/Core a partire da:
     ///
     /// - una directory dati (persistenza locale),
-    /// - un percorso a modello ONNX globale (teacher o student heavy),
+    /// - un percorso al modello globale (teacher o student heavy): `.onnx`
+    ///   per ONNX Runtime (non incluso in questa build: il bootstrap
+    ///   fallisce), `.safetensors` per il backend CPU di riferimento,
     /// - un profilo opzionale (se `None`, viene auto-rilevato).
     ///
     /// Questa funzione:
     /// 1. carica o genera il `NodeId`,
     /// 2. determina il [`NodeProfile`],
-    /// 3. carica il modello ONNX,
-    /// 4. inizializza tutti i sottosistemi.
+    /// 3. carica il modello con il backend adatto (e il `tokenizer.json`
+    ///    accanto ad esso) e ne esegue il warmup,
+    /// 4. inizializza tutti i sottosistemi, ripristinando lo stato
+    ///    dell'[`AdaptiveThrottle`] salvato dall'esecuzione precedente se
+    ///    recente (vedi [`adaptive_throttle::MAX_STATE_AGE`]) e collegandolo
+    ///    a `/proc` per i guardrail di sistema, se disponibile.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il `NodeId`, il modello o uno dei sottosistemi
+    /// non possono essere caricati.
     pub async fn bootstrap(
         data_dir: PathBuf,
         model_path: PathBuf,
         profile_override: Option<NodeProfile>,
+    ) -> Result<Self> {
+        // In una versione futura, la scelta potrà dipendere anche dal
+        // NodeProfile (es. GPU vs CPU).
+        let backend = neural_engine::load_backend(&model_path).with_context(|| {
+            format!("Unable to load global model from {}", model_path.display())
+        })?;
+        let tokenizer = Tokenizer::load_for_model(&model_path)?;
+
+        Self::bootstrap_with_backend(data_dir, backend, tokenizer, profile_override).await
+    }
+
+    /// Come [`NeuroNode::bootstrap`], ma con backend di inferenza e
+    /// tokenizer già costruiti dal chiamante (es. runtime alternativi o
+    /// backend di test).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il `NodeId` o uno dei sottosistemi non possono
+    /// essere caricati, o se il vocabolario del tokenizer è più grande
+    /// dell'output del modello.
+    pub async fn bootstrap_with_backend(
+        data_dir: PathBuf,
+        backend: DynBackend,
+        tokenizer: Tokenizer,
+        profile_override: Option<NodeProfile>,
     ) -> Result<Self> {
         let id = Self::load_or_create_node_id(&data_dir).await?;
         let profile = profile_override.unwrap_or_else(NodeProfileDetector::detect);
@@ -171,21 +235,45 @@ impl NeuroNode {
             profile
         );
 
-        // In una versione futura, qui si potrà scegliere backend diverso
-        // in base a NodeProfile (es. GPU vs CPU).
-        let backend = OnnxBackend::load(&model_path)
-            .with_context(|| format!("Unable to load global ONNX model from {:?}", model_path))?;
+        let model_vocab = backend
+            .output_schema()
+            .first()
+            .and_then(|spec| spec.shape.last().copied().flatten());
+        // Un id oltre il vocabolario del modello farebbe fallire ogni prompt
+        // che lo contiene: meglio rifiutare la coppia all'avvio.
+        if let Some(model_vocab) = model_vocab {
+            ensure!(
+                tokenizer.vocab_size() <= model_vocab,
+                "Tokenizer vocabulary ({}) is larger than the model output ({model_vocab})",
+                tokenizer.vocab_size()
+            );
+        }
+
+        let tokenizer = Arc::new(tokenizer);
+        let neural_engine = NeuralEngine::from_boxed(backend, Arc::clone(&tokenizer));
+        neural_engine
+            .warmup()
+            .context("Inference backend warmup failed")?;
+
+        let policy_core = PolicyCore::load_or_default(&data_dir).await?;
+        let mut meta_observer = MetaObserver::new();
+        meta_observer.observe_policy(policy_core.version());
+
+        let throttle_state_path = data_dir.join(THROTTLE_STATE_FILE);
+        let adaptive_throttle = Self::restore_throttle(&throttle_state_path).await;
+        let clock = SystemClock::shared();
+        let now = clock.now();
 
         Ok(Self {
             id,
             profile,
 
-            policy_core: PolicyCore::load_or_default(&data_dir).await?,
+            policy_core,
             meta_brain: MetaBrain::new(),
-            neural_engine: NeuralEngine::new(backend),
-            io_layer: IOLayer::new(data_dir.join("io")).await?,
+            neural_engine,
+            io_layer: IOLayer::new(data_dir.join("io"), tokenizer).await?,
 
-            adaptive_throttle: AdaptiveThrottle::new(),
+            adaptive_throttle,
             scheduler: PriorityScheduler::new(),
 
             federated: Arc::new(RwLock::new(
@@ -194,14 +282,89 @@ impl NeuroNode {
             net_client: NetClient::new(id),
 
             snapshot_store: SnapshotStore::open(data_dir.join("snapshots")).await?,
-            meta_observer: MetaObserver::new(),
+            meta_observer,
             update_agent: UpdateAgent::new(data_dir.join("updates")),
 
             tick_counter: 0,
-            start_time: Instant::now(),
+            start_time: now,
+            clock,
+
+            pending_delta: None,
+            throttle_state_path,
+            throttle_saved_at: now,
         })
     }
 
+    /// Sostituisce l'orologio del nodo e del suo [`AdaptiveThrottle`] (es.
+    /// con un [`ManualClock`](clock::ManualClock) per simulazioni e test).
+    ///
+    /// Uptime e intervallo di salvataggio dello stato ripartono dal nuovo
+    /// orologio.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.adaptive_throttle.set_clock(Arc::clone(&clock));
+        self.start_time = clock.now();
+        self.throttle_saved_at = self.start_time;
+        self.clock = clock;
+    }
+
+    /// Orologio del nodo.
+    #[must_use]
+    pub const fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Tempo trascorso dall'avvio del nodo, secondo il suo orologio.
+    #[must_use]
+    pub fn uptime(&self) -> Duration {
+        self.clock.elapsed(self.start_time)
+    }
+
+    /// Crea l'[`AdaptiveThrottle`] ripristinando lo stato salvato in `path`,
+    /// se presente e recente. Uno stato illeggibile viene ignorato.
+    async fn restore_throttle(path: &Path) -> AdaptiveThrottle {
+        let mut throttle = AdaptiveThrottle::new();
+        let sampler = ProcSampler::new();
+        if sampler.is_available() {
+            throttle.set_sampler(sampler);
+        }
+        match ThrottleState::load(path).await {
+            Ok(Some(state)) if throttle.restore(&state, MAX_STATE_AGE) => {
+                info!(
+                    "Restored throttle state ({}, saved {:.0?} ago)",
+                    state.level,
+                    state.age()
+                );
+            }
+            Ok(Some(state)) => {
+                info!(
+                    "Ignoring stale throttle state (saved {:.0?} ago)",
+                    state.age()
+                );
+            }
+            Ok(None) => {}
+            Err(err) => warn!("Ignoring throttle state: {err:#}"),
+        }
+        throttle
+    }
+
+    /// Salva lo stato dell'[`AdaptiveThrottle`] sotto il `data_dir`, per
+    /// ripristinarlo al prossimo bootstrap.
+    ///
+    /// Viene chiamato periodicamente da [`NeuroNode::tick`] (ogni
+    /// [`adaptive_throttle::STATE_SAVE_INTERVAL`]) e allo stop del loop del
+    /// nodo.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non può essere scritto.
+    pub async fn save_throttle_state(&mut self) -> Result<()> {
+        self.throttle_saved_at = self.clock.now();
+        self.adaptive_throttle
+            .state()
+            .save(&self.throttle_state_path)
+            .await
+    }
+
     /// Carica un `NodeId` persistito, oppure ne crea uno nuovo se assente.
     ///
     /// Il file è salvato in `data_dir/node_id.bin` ed è un array di 32 byte.
@@ -211,7 +374,7 @@ impl NeuroNode {
         if id_path.exists() {
             let bytes = tokio::fs::read(&id_path)
                 .await
-                .with_context(|| format!("Unable to read node id from {:?}", id_path))?;
+                .with_context(|| format!("Unable to read node id from {}", id_path.display()))?;
 
             bytes
                 .try_into()
@@ -226,10 +389,10 @@ impl NeuroNode {
 
             tokio::fs::create_dir_all(data_dir)
                 .await
-                .with_context(|| format!("Unable to create data dir {:?}", data_dir))?;
+                .with_context(|| format!("Unable to create data dir {}", data_dir.display()))?;
             tokio::fs::write(&id_path, &array)
                 .await
-                .with_context(|| format!("Unable to persist node id to {:?}", id_path))?;
+                .with_context(|| format!("Unable to persist node id to {}", id_path.display()))?;
 
             Ok(array)
         }
@@ -240,81 +403,114 @@ impl NeuroNode {
     /// Questo metodo è pensato per essere invocato in un loop (vedi
     /// [`crate::node::run_node`]) e implementa:
     ///
-    /// - **corsia critical**: gestione input utente + inferenza + policy;
-    /// - **corsia background**: training federato DP, meta-observer,
-    ///   snapshot, aggiornamenti binari;
-    /// - aggiornamento di `AdaptiveThrottle` e metriche.
+    /// - hot reload del file di policy, se cambiato
+    ///   ([`PolicyCore::poll_reload`]);
+    /// - richiesta del piano al [`PriorityScheduler`], in base a livello e
+    ///   intensità correnti dell'[`AdaptiveThrottle`];
+    /// - esecuzione dei [`TaskKind`] del piano, in ordine di lane:
+    ///   - **corsia critical**: gestione input utente + inferenza + policy;
+    ///     finché restano input in coda il flusso viene ripetuto, entro
+    ///     [`scheduler::SchedulerConfig::max_budget_per_tick`] (non scalato
+    ///     dall'intensità: il throttle sacrifica le altre corsie, non
+    ///     l'utente);
+    ///   - **corsie normal/background**: training federato DP, delta,
+    ///     meta-observer, snapshot, aggiornamenti binari;
+    ///
+    ///   ogni task eseguito scala il proprio [`TaskKind::cost`] dal
+    ///   [`ScheduledWork::budget`]; i task non critici che non entrano nel
+    ///   budget residuo vengono saltati e rimandati al prossimo tick
+    ///   ([`PriorityScheduler::defer`]), senza bloccare quelli più leggeri
+    ///   che li seguono nel piano;
+    /// - aggiornamento di `AdaptiveThrottle` e metriche: la profondità della
+    ///   coda di input ([`AdaptiveThrottle::record_queue_depth`]) e la durata
+    ///   del tick (totale e per corsia) vengono passate a
+    ///   [`AdaptiveThrottle::record_tick_timing`]; lo stato del throttle
+    ///   viene salvato periodicamente ([`NeuroNode::save_throttle_state`]).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore solo per failure considerate fatali (policy,
+    /// snapshot); gli errori di rete e di update vengono solo loggati, e
+    /// una tokenizzazione o un'inferenza fallite vengono consegnate alla
+    /// sola sessione interessata ([`PolicyDecision::inference_failed`]).
     pub async fn tick(&mut self) -> TickResult {
-        // Aggiorna il throttle in base al profilo (in futuro: anche system load).
+        let tick_start = self.clock.now();
+
+        // Aggiorna il throttle in base al profilo e al carico di sistema.
         self.adaptive_throttle.update(&self.profile);
 
-        // ────────────────────────────────────────────────────────────────
-        // CORSIA CRITICAL — I/O utente + inferenza + policy
-        // ────────────────────────────────────────────────────────────────
-        if let Some(user_input) = self.io_layer.try_recv_user_input() {
-            // Prepara gli input per il modello neurale.
-            let model_inputs = self.io_layer.prepare_model_inputs(user_input)?;
-            // Inferenzia con il motore neurale (bloccante per la corsia critical).
-            let raw_output = self.neural_engine.infer(&model_inputs).await?;
-            // Applica le policy di sicurezza / governance.
-            let decision = self.policy_core.evaluate(&raw_output)?;
-            // Consegna la risposta all’utente.
-            self.io_layer.deliver_to_user(decision).await?;
+        // Hot reload delle policy (polling dell'mtime, con cadenza propria).
+        if let Some(outcome) = self.policy_core.poll_reload() {
+            self.meta_observer.record_policy_reload(&outcome);
         }
 
-        // ────────────────────────────────────────────────────────────────
-        // CORSIA BACKGROUND — training, meta, snapshot, update
-        // ────────────────────────────────────────────────────────────────
-        if self.adaptive_throttle.allow_background() {
-            let intensity = self.adaptive_throttle.current_intensity();
-
-            // Scheduling a livello concettuale: qui potremmo chiedere al
-            // PriorityScheduler quali "neuroni / moduli" eseguire.
-            let _scheduled = self.scheduler.schedule_tick(self.tick_counter);
-
-            // 1) Federated training locale (solo nodi Heavy / Desktop forti)
-            if self.profile.is_heavy() {
-                let mut fed = self.federated.write().await;
-                if fed.is_training_enabled().await? {
-                    fed.run_local_epoch(intensity).await?;
-                    if fed.should_submit_delta() {
-                        let delta: DeltaMessage = fed.compute_and_package_delta().await?;
-                        // La failure di rete non è fatale per il tick.
-                        if let Err(err) = self.net_client.submit_delta(delta).await {
-                            warn!("NetClient.submit_delta failed: {err:?}");
-                        }
-                    }
-                }
-            }
+        let plan = self.scheduler.schedule_tick(
+            self.tick_counter,
+            self.adaptive_throttle.current_level(),
+            self.adaptive_throttle.current_intensity(),
+        );
 
-            // 2) Meta-observer (metriche di inferenza / training)
-            if self.profile.is_heavy() {
-                self.meta_observer.sample(&self.neural_engine).await;
+        let mut timing = TickTiming::default();
+        let mut remaining_budget = plan.budget;
+        let mut critical_budget = self.scheduler.config().max_budget_per_tick;
+        let mut flow = CriticalFlow::default();
+        let mut deferred = Vec::new();
+
+        for &task in &plan.tasks {
+            if !ScheduledWork::fits(task, remaining_budget) {
+                // Il budget è stato consumato dai task critici: il task
+                // torna in coda per il prossimo tick, quelli più leggeri
+                // che seguono possono ancora entrare.
+                deferred.push(task);
+                continue;
             }
 
-            // 3) Snapshot periodici del modello
-            if self.tick_counter % 10_000 == 0 {
-                self.snapshot_store.create_snapshot(&self.neural_engine).await?;
+            if self.run_timed_task(task, &mut flow, &mut timing).await? {
+                remaining_budget -= task.cost();
+                if task.lane() == Lane::Critical {
+                    critical_budget -= task.cost();
+                }
             }
 
-            // 4) Controllo aggiornamenti binari
-            if self.tick_counter % 50_000 == 0 {
-                if let Err(e) = self.update_agent.check_for_updates().await {
-                    warn!("UpdateAgent error: {e:?}");
+            // Con altri input in coda il flusso critico riparte, finché il
+            // budget critico del tick copre un giro completo.
+            if task == TaskKind::UserDelivery {
+                let round_cost: f64 = CRITICAL_ROUND.iter().map(TaskKind::cost).sum();
+                while self.io_layer.queue_len() > 0 && critical_budget >= round_cost {
+                    for task in CRITICAL_ROUND {
+                        if self.run_timed_task(task, &mut flow, &mut timing).await? {
+                            remaining_budget -= task.cost();
+                            critical_budget -= task.cost();
+                        }
+                    }
                 }
             }
         }
 
+        self.scheduler.defer(&deferred);
+
         // ────────────────────────────────────────────────────────────────
         // Accounting interno e logging di stato
         // ────────────────────────────────────────────────────────────────
         self.tick_counter = self.tick_counter.wrapping_add(1);
 
-        if self.tick_counter % 5_000 == 0 {
+        timing.total = self.clock.elapsed(tick_start);
+        self.adaptive_throttle
+            .record_queue_depth(self.io_layer.queue_len(), self.io_layer.queue_capacity());
+        self.adaptive_throttle.record_tick_timing(&timing);
+
+        // Un salvataggio fallito non è fatale: si riprova al prossimo intervallo.
+        if self.clock.elapsed(self.throttle_saved_at) >= STATE_SAVE_INTERVAL {
+            if let Err(err) = self.save_throttle_state().await {
+                warn!("Unable to save throttle state: {err:#}");
+            }
+        }
+
+        if self.tick_counter.is_multiple_of(5_000) {
             info!(
                 "Tick {:>10} │ uptime {:>8.0?} │ {:?} │ throttle {:?}",
                 self.tick_counter,
-                self.start_time.elapsed(),
+                self.uptime(),
                 self.profile,
                 self.adaptive_throttle.current_level()
             );
@@ -322,4 +518,232 @@ impl NeuroNode {
 
         Ok(())
     }
+
+    /// Esegue `task` con [`Self::run_task`], sommandone la durata alla
+    /// corsia corrispondente di `timing`.
+    async fn run_timed_task(
+        &mut self,
+        task: TaskKind,
+        flow: &mut CriticalFlow,
+        timing: &mut TickTiming,
+    ) -> Result<bool> {
+        let task_start = self.clock.now();
+        let executed = self.run_task(task, flow).await?;
+        let elapsed = self.clock.elapsed(task_start);
+
+        if task.lane() == Lane::Critical {
+            timing.critical += elapsed;
+        } else {
+            timing.background += elapsed;
+        }
+        Ok(executed)
+    }
+
+    /// Esegue un singolo task del piano schedulato.
+    ///
+    /// Ritorna `true` se il task ha effettivamente svolto lavoro (e va quindi
+    /// scalato dal budget), `false` se non c'era nulla da fare (es. nessun
+    /// input utente in coda, training disabilitato).
+    async fn run_task(&mut self, task: TaskKind, flow: &mut CriticalFlow) -> Result<bool> {
+        match task {
+            // ────────────────────────────────────────────────────────────
+            // CORSIA CRITICAL — I/O utente + inferenza + policy
+            // ────────────────────────────────────────────────────────────
+            TaskKind::UserInference => {
+                let Some(user_input) = self.io_layer.try_recv_user_input() else {
+                    return Ok(false);
+                };
+                // Policy sul prompt: un blocco salta l'inferenza e passa
+                // direttamente alla consegna.
+                let input_decision = self.policy_core.evaluate_input(&user_input.text)?;
+                if input_decision.is_blocked() {
+                    flow.session_id = Some(user_input.session_id);
+                    flow.decision = Some(input_decision);
+                    return Ok(true);
+                }
+                // Un fallimento del modello riguarda solo questa richiesta:
+                // la sessione riceve un errore e il tick prosegue.
+                let session_id = user_input.session_id.clone();
+                flow.session_id = Some(session_id.clone());
+                let input = UserInput {
+                    text: input_decision.text,
+                    ..user_input
+                };
+                if let Err(err) = self.generate_reply(input, flow).await {
+                    warn!("Inference failed for session {session_id}: {err:#}");
+                    flow.output = None;
+                    flow.decision = Some(PolicyDecision::inference_failed());
+                }
+                Ok(true)
+            }
+            TaskKind::PolicyEvaluation => {
+                let Some(raw_output) = flow.output.take() else {
+                    return Ok(false);
+                };
+                // Applica le policy di sicurezza / governance.
+                flow.decision = Some(self.policy_core.evaluate(&raw_output)?);
+                Ok(true)
+            }
+            TaskKind::UserDelivery => {
+                let Some(decision) = flow.decision.take() else {
+                    return Ok(false);
+                };
+                let session_id = flow.session_id.take().unwrap_or_default();
+                // Consegna la risposta all’utente.
+                self.io_layer
+                    .deliver_to_user(&session_id, Delivery::Final(decision))
+                    .await;
+                Ok(true)
+            }
+
+            // ────────────────────────────────────────────────────────────
+            // CORSIA NORMAL — training federato e delta
+            // ────────────────────────────────────────────────────────────
+            TaskKind::LocalTraining => {
+                // Training locale solo sui nodi Heavy.
+                if !self.profile.is_heavy() {
+                    return Ok(false);
+                }
+                let intensity = self.adaptive_throttle.current_intensity();
+                let mut fed = self.federated.write().await;
+                if !fed.is_training_enabled().await? {
+                    return Ok(false);
+                }
+                fed.run_local_epoch(intensity).await?;
+                drop(fed);
+                Ok(true)
+            }
+            TaskKind::DeltaComputation => {
+                let mut fed = self.federated.write().await;
+                if !fed.should_submit_delta() {
+                    return Ok(false);
+                }
+                let delta = fed.compute_and_package_delta().await?;
+                drop(fed);
+                self.pending_delta = Some(delta);
+                Ok(true)
+            }
+            TaskKind::DeltaSubmission => {
+                let Some(delta) = self.pending_delta.take() else {
+                    return Ok(false);
+                };
+                // La failure di rete non è fatale per il tick.
+                if let Err(err) = self.net_client.submit_delta(delta).await {
+                    warn!("NetClient.submit_delta failed: {err:?}");
+                }
+                Ok(true)
+            }
+
+            // ────────────────────────────────────────────────────────────
+            // CORSIA BACKGROUND — meta, snapshot, update
+            // ────────────────────────────────────────────────────────────
+            TaskKind::MetricsSampling => {
+                if !self.profile.is_heavy() {
+                    return Ok(false);
+                }
+                self.meta_observer.sample(&self.neural_engine).await;
+                Ok(true)
+            }
+            TaskKind::SnapshotCreation => {
+                self.snapshot_store
+                    .create_snapshot(&self.neural_engine)
+                    .await?;
+                Ok(true)
+            }
+            TaskKind::UpdateCheck => {
+                if let Err(e) = self.update_agent.check_for_updates().await {
+                    warn!("UpdateAgent error: {e:?}");
+                }
+                Ok(true)
+            }
+            // Il MetaBrain non produce ancora ADR da applicare.
+            TaskKind::AdrApplication => Ok(false),
+        }
+    }
+
+    /// Prepara gli input (eventualmente redatti) con il contesto della
+    /// sessione ed esegue l'inferenza in streaming.
+    async fn generate_reply(&mut self, input: UserInput, flow: &mut CriticalFlow) -> Result<()> {
+        let model_inputs = self.io_layer.prepare_model_inputs(input).await?;
+        self.stream_inference(&model_inputs, flow).await
+    }
+
+    /// Genera la risposta in streaming, consegnando all'utente ogni
+    /// frammento che il [`PolicyCore`] approva.
+    ///
+    /// Viene consegnato solo il prefisso già stabile
+    /// ([`PolicyCore::settled_len`]): la coda che potrebbe ancora diventare
+    /// un dato personale o una keyword resta trattenuta e arriva con la
+    /// decisione finale.
+    ///
+    /// Se il `PolicyCore` blocca il testo parziale, lo stream viene
+    /// interrotto e la decisione passa direttamente alla consegna, saltando
+    /// `PolicyEvaluation`. Se il testo va redatto, la generazione prosegue
+    /// ma i frammenti successivi vengono trattenuti: l'utente riceve solo
+    /// il testo redatto della decisione finale.
+    async fn stream_inference(
+        &mut self,
+        input: &ModelInput,
+        flow: &mut CriticalFlow,
+    ) -> Result<()> {
+        let cancel = CancellationToken::new();
+        let mut stream = self.neural_engine.infer_stream(input, cancel.clone());
+        let mut output = ModelOutput::default();
+        let mut withhold = false;
+        let mut delivered = 0;
+
+        while let Some(event) = stream.next().await {
+            let (delta, finish) = match event? {
+                StreamEvent::Token { id, text } => {
+                    output.token_ids.push(id);
+                    (text, None)
+                }
+                StreamEvent::Finished { text, reason } => (text, Some(reason)),
+            };
+
+            if !delta.is_empty() {
+                let candidate = format!("{}{delta}", output.text);
+                let decision = self.policy_core.evaluate_partial(&candidate)?;
+                if decision.is_blocked() {
+                    cancel.cancel();
+                    flow.decision = Some(decision);
+                    return Ok(());
+                }
+                withhold |= !decision.is_allowed();
+                output.text = candidate;
+                let settled = self.policy_core.settled_len(&output.text);
+                if !withhold && settled > delivered {
+                    let fragment = output.text[delivered..settled].to_owned();
+                    delivered = settled;
+                    self.io_layer
+                        .deliver_to_user(&input.session_id, Delivery::Partial(fragment))
+                        .await;
+                }
+            }
+
+            if let Some(reason) = finish {
+                output.finish_reason = reason;
+                break;
+            }
+        }
+
+        flow.output = Some(output);
+        Ok(())
+    }
+}
+
+/// Un giro completo della pipeline critical per un input utente.
+const CRITICAL_ROUND: [TaskKind; 3] = [
+    TaskKind::UserInference,
+    TaskKind::PolicyEvaluation,
+    TaskKind::UserDelivery,
+];
+
+/// Stato intermedio della pipeline critical all'interno di un singolo tick
+/// (inferenza → policy → consegna).
+#[derive(Default)]
+struct CriticalFlow {
+    session_id: Option<SessionId>,
+    output: Option<ModelOutput>,
+    decision: Option<PolicyDecision>,
 }
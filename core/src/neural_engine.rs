@@ -1,29 +1,1023 @@
-//! Neural engine with ONNX backend.
+//! Neural engine with pluggable inference backends.
+//!
+//! Il [`NeuralEngine`] non conosce il runtime che esegue il modello: fa
+//! dispatch su un [`InferenceBackend`], che espone:
+//!
+//! - caricamento del modello da disco ([`InferenceBackend::load`]),
+//! - schema di input/output ([`InferenceBackend::input_schema`],
+//!   [`InferenceBackend::output_schema`]),
+//! - inferenza su tensori ([`InferenceBackend::infer`]),
+//! - warmup opzionale ([`InferenceBackend::warmup`]).
+//!
+//! Backend disponibili:
+//!
+//! - [`OnnxBackend`]: modelli `.onnx` tramite ONNX Runtime (Heavy/Core);
+//! - [`cpu::CpuMlpBackend`]: backend di riferimento in puro Rust che legge
+//!   un piccolo MLP da un file `.safetensors`, per nodi solo-CPU senza
+//!   ONNX Runtime.
+//!
+//! # Convenzione degli I/O
+//!
+//! Il motore genera testo in modo autoregressivo: ad ogni passo passa il
+//! contesto corrente come primo input dello schema (tensore `I64` di shape
+//! `[1, seq_len]`), l'eventuale maschera nell'input `attention_mask`, e
+//! legge i logit del prossimo token dal primo output (tensore `F32`, di cui
+//! vengono usati gli ultimi `vocab_size` valori). I token generati sono
+//! decodificati con il [`Tokenizer`] del modello.
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use futures::stream::{self, BoxStream, StreamExt};
 use std::path::Path;
+use std::sync::Arc;
+use tokio::runtime::RuntimeFlavor;
+use tokio_util::sync::CancellationToken;
 
-pub struct NeuralEngine<B> {
-    _backend: B,
+use crate::io_layer::{GenerationParams, ModelInput};
+use crate::tokenizer::Tokenizer;
+use safetensors::SafeTensors;
+
+/// Backend di riferimento in puro Rust (MLP da safetensors).
+pub mod cpu;
+/// Lettura e scrittura di tensori in formato safetensors.
+pub mod safetensors;
+
+/// Numero massimo di token generati per richiesta.
+pub const DEFAULT_MAX_NEW_TOKENS: usize = 64;
+
+/// Nome convenzionale dell'input con la maschera di attenzione.
+pub const ATTENTION_MASK: &str = "attention_mask";
+
+/// Tipo degli elementi di un tensore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DType {
+    /// Float a 32 bit (logit, attivazioni).
+    F32,
+    /// Intero a 64 bit (token id, maschere).
+    I64,
+}
+
+/// Descrizione di un input o output del modello.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorSpec {
+    /// Nome del tensore (es. `input_ids`, `logits`).
+    pub name: String,
+    /// Tipo degli elementi.
+    pub dtype: DType,
+    /// Shape attesa; `None` indica una dimensione dinamica.
+    pub shape: Vec<Option<usize>>,
 }
 
-impl<B> NeuralEngine<B> {
-    pub fn new(backend: B) -> Self {
-        Self { _backend: backend }
+impl TensorSpec {
+    /// Crea una nuova specifica di tensore.
+    #[must_use]
+    pub fn new(name: impl Into<String>, dtype: DType, shape: Vec<Option<usize>>) -> Self {
+        Self {
+            name: name.into(),
+            dtype,
+            shape,
+        }
     }
 
-    pub async fn infer(&self, _input: &crate::io_layer::ModelInput) -> Result<ModelOutput> {
-        Ok(ModelOutput {})
+    /// Restituisce `true` se `tensor` rispetta tipo e shape della specifica.
+    #[must_use]
+    pub fn accepts(&self, tensor: &Tensor) -> bool {
+        tensor.dtype() == self.dtype
+            && tensor.shape.len() == self.shape.len()
+            && self
+                .shape
+                .iter()
+                .zip(&tensor.shape)
+                .all(|(expected, actual)| expected.is_none_or(|dim| dim == *actual))
     }
 }
 
-pub struct OnnxBackend {}
+/// Dati di un tensore, in ordine row-major.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TensorData {
+    /// Elementi `f32`.
+    F32(Vec<f32>),
+    /// Elementi `i64`.
+    I64(Vec<i64>),
+}
+
+/// Tensore denso passato da/verso un [`InferenceBackend`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    /// Nome del tensore, corrispondente a una [`TensorSpec`].
+    pub name: String,
+    /// Shape effettiva.
+    pub shape: Vec<usize>,
+    /// Elementi.
+    pub data: TensorData,
+}
 
-impl OnnxBackend {
-    pub fn load(_path: &Path) -> Result<Self> {
-        Ok(Self {})
+impl Tensor {
+    /// Crea un tensore `f32`.
+    #[must_use]
+    pub fn f32(name: impl Into<String>, shape: Vec<usize>, data: Vec<f32>) -> Self {
+        Self {
+            name: name.into(),
+            shape,
+            data: TensorData::F32(data),
+        }
+    }
+
+    /// Crea un tensore `i64`.
+    #[must_use]
+    pub fn i64(name: impl Into<String>, shape: Vec<usize>, data: Vec<i64>) -> Self {
+        Self {
+            name: name.into(),
+            shape,
+            data: TensorData::I64(data),
+        }
+    }
+
+    /// Crea un tensore di zeri conforme a `spec` (dimensioni dinamiche = 1).
+    #[must_use]
+    pub fn zeros(spec: &TensorSpec) -> Self {
+        let shape: Vec<usize> = spec.shape.iter().map(|dim| dim.unwrap_or(1)).collect();
+        let len = shape.iter().product();
+        let data = match spec.dtype {
+            DType::F32 => TensorData::F32(vec![0.0; len]),
+            DType::I64 => TensorData::I64(vec![0; len]),
+        };
+        Self {
+            name: spec.name.clone(),
+            shape,
+            data,
+        }
+    }
+
+    /// Restituisce il tipo degli elementi.
+    #[must_use]
+    pub const fn dtype(&self) -> DType {
+        match self.data {
+            TensorData::F32(_) => DType::F32,
+            TensorData::I64(_) => DType::I64,
+        }
+    }
+
+    /// Restituisce gli elementi se il tensore è `f32`.
+    #[must_use]
+    pub fn as_f32(&self) -> Option<&[f32]> {
+        match &self.data {
+            TensorData::F32(values) => Some(values),
+            TensorData::I64(_) => None,
+        }
+    }
+
+    /// Restituisce gli elementi se il tensore è `i64`.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<&[i64]> {
+        match &self.data {
+            TensorData::I64(values) => Some(values),
+            TensorData::F32(_) => None,
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct ModelOutput {}
+/// Backend di inferenza su cui il [`NeuralEngine`] fa dispatch.
+///
+/// Permette di sostituire ONNX Runtime con altri runtime (o con backend
+/// finti nei test) senza toccare il resto del nodo.
+pub trait InferenceBackend: Send + Sync {
+    /// Carica il modello da `path`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non esiste o non è un modello valido per
+    /// questo backend.
+    fn load(path: &Path) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Descrive gli input attesi dal modello, in ordine.
+    fn input_schema(&self) -> &[TensorSpec];
+
+    /// Descrive gli output prodotti dal modello, in ordine.
+    fn output_schema(&self) -> &[TensorSpec];
+
+    /// Esegue l'inferenza sincrona su `inputs` (uno per voce dello schema).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se gli input non rispettano lo schema o se il runtime
+    /// sottostante fallisce.
+    fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>>;
+
+    /// Esegue un'inferenza a vuoto per scaldare cache e allocazioni.
+    ///
+    /// L'implementazione di default passa un tensore di zeri per ogni input
+    /// dello schema e scarta il risultato.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se l'inferenza di warmup fallisce.
+    fn warmup(&self) -> Result<()> {
+        let schema = self.input_schema();
+        if schema.is_empty() {
+            return Ok(());
+        }
+        let inputs: Vec<Tensor> = schema.iter().map(Tensor::zeros).collect();
+        self.infer(&inputs).map(drop)
+    }
+
+    /// Pesi correnti del modello, salvati negli snapshot per il rollback.
+    ///
+    /// `None` (default) se il backend non li espone: i suoi snapshot non
+    /// possono essere ripristinati.
+    fn export_weights(&self) -> Option<SafeTensors> {
+        None
+    }
+
+    /// Sostituisce i pesi del modello con quelli di uno snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il backend non supporta il ripristino dei pesi
+    /// (default) o se i pesi non sono compatibili con il modello corrente.
+    fn import_weights(&mut self, _weights: SafeTensors) -> Result<()> {
+        bail!("Restoring model weights is not supported by this backend")
+    }
+}
+
+/// Backend scelto a runtime (es. in base al formato del modello).
+pub type DynBackend = Box<dyn InferenceBackend>;
+
+/// Carica il backend adatto al modello in `path`, in base all'estensione:
+///
+/// - `.safetensors` → [`cpu::CpuMlpBackend`],
+/// - qualunque altra → [`OnnxBackend`].
+///
+/// # Errors
+///
+/// Ritorna errore se il backend selezionato non riesce a caricare il modello.
+pub fn load_backend(path: &Path) -> Result<DynBackend> {
+    let is_safetensors = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("safetensors"));
+
+    if is_safetensors {
+        Ok(Box::new(cpu::CpuMlpBackend::load(path)?))
+    } else {
+        Ok(Box::new(OnnxBackend::load(path)?))
+    }
+}
+
+/// Motore neurale generico sul backend di inferenza `B`.
+pub struct NeuralEngine<B: ?Sized = dyn InferenceBackend> {
+    tokenizer: Arc<Tokenizer>,
+    max_new_tokens: usize,
+    backend: Box<B>,
+}
+
+impl<B: InferenceBackend> NeuralEngine<B> {
+    /// Crea un motore neurale sopra il backend indicato.
+    #[must_use]
+    pub fn new(backend: B, tokenizer: Arc<Tokenizer>) -> Self {
+        Self::from_boxed(Box::new(backend), tokenizer)
+    }
+}
+
+impl<B: InferenceBackend + ?Sized> NeuralEngine<B> {
+    /// Crea un motore neurale sopra un backend già allocato (anche `dyn`).
+    #[must_use]
+    pub const fn from_boxed(backend: Box<B>, tokenizer: Arc<Tokenizer>) -> Self {
+        Self {
+            tokenizer,
+            max_new_tokens: DEFAULT_MAX_NEW_TOKENS,
+            backend,
+        }
+    }
+
+    /// Imposta il limite massimo di token generati per richiesta, che
+    /// prevale su [`GenerationParams::max_new_tokens`].
+    pub const fn set_max_new_tokens(&mut self, max_new_tokens: usize) {
+        self.max_new_tokens = max_new_tokens;
+    }
+
+    /// Limite massimo di token generati per richiesta.
+    #[must_use]
+    pub const fn max_new_tokens(&self) -> usize {
+        self.max_new_tokens
+    }
+
+    /// Restituisce il backend sottostante.
+    #[must_use]
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Restituisce il backend sottostante in modo mutabile.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Restituisce il tokenizer usato per decodificare gli output.
+    #[must_use]
+    pub const fn tokenizer(&self) -> &Arc<Tokenizer> {
+        &self.tokenizer
+    }
+
+    /// Esegue il warmup del backend.
+    ///
+    /// # Errors
+    ///
+    /// Vedi [`InferenceBackend::warmup`].
+    pub fn warmup(&self) -> Result<()> {
+        self.backend.warmup()
+    }
+
+    /// Esegue l'inferenza sugli input preparati dall'[`crate::io_layer::IOLayer`].
+    ///
+    /// Raccoglie l'intero [`NeuralEngine::infer_stream`] in un unico
+    /// [`ModelOutput`].
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il backend fallisce o produce output malformati.
+    pub async fn infer(&self, input: &ModelInput) -> Result<ModelOutput> {
+        let mut stream = self.infer_stream(input, CancellationToken::new());
+        let mut output = ModelOutput::default();
+
+        while let Some(event) = stream.next().await {
+            match event? {
+                StreamEvent::Token { id, text } => {
+                    output.token_ids.push(id);
+                    output.text.push_str(&text);
+                }
+                StreamEvent::Finished { text, reason } => {
+                    output.text.push_str(&text);
+                    output.finish_reason = reason;
+                    return Ok(output);
+                }
+            }
+        }
+
+        bail!("Generation stream ended without a finish event")
+    }
+
+    /// Genera token in streaming a partire da quelli di `input`.
+    ///
+    /// Lo stream emette un [`StreamEvent::Token`] per ogni token generato,
+    /// con il testo reso disponibile da quel token, e termina sempre con un
+    /// [`StreamEvent::Finished`]. La generazione si ferma:
+    ///
+    /// - al token di fine sequenza del tokenizer,
+    /// - al limite di token (il minore tra richiesta e motore),
+    /// - alla prima stop sequence (esclusa dal testo),
+    /// - quando `cancel` viene cancellato (il testo trattenuto è scartato).
+    ///
+    /// Il testo che potrebbe essere l'inizio di una stop sequence, o di un
+    /// carattere UTF-8 incompleto, viene trattenuto finché non è
+    /// disambiguato. Dopo un errore lo stream termina.
+    #[must_use]
+    pub fn infer_stream<'a>(
+        &'a self,
+        input: &ModelInput,
+        cancel: CancellationToken,
+    ) -> BoxStream<'a, Result<StreamEvent>>
+    where
+        B: 'a,
+    {
+        let generation = Generation {
+            engine: self,
+            sampler: Sampler::new(&input.params),
+            context: input.token_ids.clone(),
+            mask: input.attention_mask.iter().map(|&m| i64::from(m)).collect(),
+            generated: Vec::new(),
+            limit: input.params.max_new_tokens.min(self.max_new_tokens),
+            stop_sequences: input
+                .params
+                .stop_sequences
+                .iter()
+                .filter(|stop| !stop.is_empty())
+                .cloned()
+                .collect(),
+            text: String::new(),
+            emitted: 0,
+            cancel,
+            done: false,
+        };
+
+        stream::unfold(generation, |mut generation| async move {
+            if generation.done {
+                return None;
+            }
+            // Lascia spazio agli altri task (e alla cancellazione) tra un
+            // token e l'altro.
+            tokio::task::yield_now().await;
+            let event = run_blocking(|| generation.step());
+            if !matches!(event, Ok(StreamEvent::Token { .. })) {
+                generation.done = true;
+            }
+            Some((event, generation))
+        })
+        .boxed()
+    }
+
+    /// Calcola i logit del prossimo token dato il contesto.
+    ///
+    /// Il primo input dello schema riceve i token; un eventuale input
+    /// `attention_mask` riceve la maschera.
+    fn next_logits(&self, context: &[u32], mask: &[i64]) -> Result<Vec<f32>> {
+        let shape = vec![1, context.len()];
+        let inputs = self
+            .backend
+            .input_schema()
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| match (i, spec.name.as_str()) {
+                (_, ATTENTION_MASK) => {
+                    Ok(Tensor::i64(spec.name.clone(), shape.clone(), mask.to_vec()))
+                }
+                (0, _) => {
+                    let ids = context.iter().map(|&id| i64::from(id)).collect();
+                    Ok(Tensor::i64(spec.name.clone(), shape.clone(), ids))
+                }
+                (_, name) => Err(anyhow!("Unsupported model input {name}")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(!inputs.is_empty(), "Inference backend declares no inputs");
+
+        let outputs = self.backend.infer(&inputs)?;
+        let logits = outputs
+            .first()
+            .and_then(Tensor::as_f32)
+            .ok_or_else(|| anyhow!("Inference backend returned no f32 logits"))?;
+        let vocab_size = *outputs[0]
+            .shape
+            .last()
+            .ok_or_else(|| anyhow!("Logits tensor has no dimensions"))?;
+        ensure!(
+            vocab_size > 0 && logits.len() >= vocab_size,
+            "Logits tensor is smaller than its vocabulary dimension"
+        );
+
+        Ok(logits[logits.len() - vocab_size..].to_vec())
+    }
+}
+
+/// Esegue `f`, che blocca il thread per la durata dell'inferenza.
+///
+/// Sul runtime multi-thread il worker viene ceduto con
+/// [`tokio::task::block_in_place`], così un modello lento non ferma il tick
+/// loop né i task HTTP e di controllo; sul runtime current-thread non c'è
+/// un altro worker a cui cederlo e `f` viene eseguita direttamente.
+fn run_blocking<T>(f: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// Evento prodotto da [`NeuralEngine::infer_stream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// Nuovo token generato.
+    Token {
+        /// Id del token.
+        id: u32,
+        /// Testo reso disponibile da questo token (vuoto se trattenuto).
+        text: String,
+    },
+    /// Fine della generazione.
+    Finished {
+        /// Testo trattenuto e non ancora emesso.
+        text: String,
+        /// Motivo di fine generazione.
+        reason: FinishReason,
+    },
+}
+
+/// Stato di una generazione in streaming.
+struct Generation<'a, B: ?Sized> {
+    engine: &'a NeuralEngine<B>,
+    sampler: Sampler,
+    context: Vec<u32>,
+    mask: Vec<i64>,
+    generated: Vec<u32>,
+    limit: usize,
+    stop_sequences: Vec<String>,
+    /// Testo decodificato di tutti i token generati.
+    text: String,
+    /// Byte di `text` già emessi.
+    emitted: usize,
+    cancel: CancellationToken,
+    done: bool,
+}
+
+impl<B: InferenceBackend + ?Sized> Generation<'_, B> {
+    /// Genera un token, oppure chiude la generazione.
+    fn step(&mut self) -> Result<StreamEvent> {
+        if self.cancel.is_cancelled() {
+            return Ok(self.finish(FinishReason::Cancelled, self.emitted));
+        }
+        if self.generated.len() >= self.limit {
+            return Ok(self.finish(FinishReason::Length, self.text.len()));
+        }
+
+        let logits = self.engine.next_logits(&self.context, &self.mask)?;
+        let next = self.sampler.pick(&logits)?;
+        if Some(next) == self.engine.tokenizer.eos_token_id() {
+            return Ok(self.finish(FinishReason::EndOfSequence, self.text.len()));
+        }
+
+        self.generated.push(next);
+        self.context.push(next);
+        self.mask.push(1);
+        self.text = self.engine.tokenizer.decode(&self.generated, true)?;
+
+        let stop = self
+            .stop_sequences
+            .iter()
+            .filter_map(|stop| self.text.find(stop.as_str()))
+            .min();
+        if let Some(position) = stop {
+            return Ok(self.finish(FinishReason::StopSequence, position));
+        }
+
+        let safe = self.safe_len().max(self.emitted);
+        let text = self
+            .text
+            .get(self.emitted..safe)
+            .unwrap_or_default()
+            .to_owned();
+        self.emitted = safe;
+        Ok(StreamEvent::Token { id: next, text })
+    }
+
+    /// Chiude la generazione emettendo il testo fino a `end`.
+    fn finish(&self, reason: FinishReason, end: usize) -> StreamEvent {
+        let text = self
+            .text
+            .get(self.emitted..end.max(self.emitted))
+            .unwrap_or_default()
+            .to_owned();
+        StreamEvent::Finished { text, reason }
+    }
+
+    /// Lunghezza del prefisso di `text` che può essere emesso senza
+    /// rischiare di spezzare un carattere o una stop sequence.
+    fn safe_len(&self) -> usize {
+        let mut safe = self.text.len();
+        if self.text.ends_with(char::REPLACEMENT_CHARACTER) {
+            safe -= char::REPLACEMENT_CHARACTER.len_utf8();
+        }
+        let candidate = &self.text[..safe];
+        let held_back = self
+            .stop_sequences
+            .iter()
+            .flat_map(|stop| {
+                stop.char_indices()
+                    .skip(1)
+                    .map(|(end, _)| &stop[..end])
+                    .filter(|prefix| candidate.ends_with(prefix))
+                    .map(str::len)
+            })
+            .max()
+            .unwrap_or(0);
+        safe - held_back
+    }
+}
+
+/// Sceglie il prossimo token dai logit secondo i [`GenerationParams`].
+struct Sampler {
+    temperature: f32,
+    rng: XorShift64,
+}
+
+impl Sampler {
+    fn new(params: &GenerationParams) -> Self {
+        let seed = params
+            .seed
+            .unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0);
+        Self {
+            temperature: params.temperature,
+            rng: XorShift64::new(seed),
+        }
+    }
+
+    fn pick(&mut self, logits: &[f32]) -> Result<u32> {
+        let (best, &max) = logits
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .ok_or_else(|| anyhow!("Empty logits"))?;
+
+        let index = if self.temperature > 0.0 {
+            // Softmax con temperatura, poi estrazione dalla cumulata.
+            let weights: Vec<f32> = logits
+                .iter()
+                .map(|&logit| ((logit - max) / self.temperature).exp())
+                .collect();
+            let mut target = self.rng.next_f32() * weights.iter().sum::<f32>();
+            weights
+                .iter()
+                .position(|&weight| {
+                    target -= weight;
+                    target <= 0.0
+                })
+                .unwrap_or(best)
+        } else {
+            best
+        };
+
+        u32::try_from(index).context("Token id does not fit in u32")
+    }
+}
+
+/// Generatore pseudo-casuale xorshift64*, deterministico dato il seed.
+pub(crate) struct XorShift64(u64);
+
+impl XorShift64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    pub(crate) const fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Valore uniforme in `[0, 1)`.
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        #[allow(clippy::cast_precision_loss)]
+        let unit = (self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32;
+        unit
+    }
+}
+
+/// Backend di inferenza basato su ONNX Runtime.
+///
+/// Questa build non include ONNX Runtime: il caricamento verifica il modello
+/// su disco e poi fallisce, così un nodo configurato con un `.onnx` si
+/// rifiuta di partire invece di rispondere `inference_failed` a ogni
+/// richiesta. Per nodi solo-CPU usare un modello `.safetensors` con
+/// [`cpu::CpuMlpBackend`].
+pub struct OnnxBackend {
+    inputs: Vec<TensorSpec>,
+    outputs: Vec<TensorSpec>,
+}
+
+/// Errore riportato da [`OnnxBackend`] in questa build.
+const ONNX_UNAVAILABLE: &str =
+    "ONNX Runtime is not available in this build; use a .safetensors model";
+
+impl InferenceBackend for OnnxBackend {
+    fn load(path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("ONNX model not found at {}", path.display()))?;
+        ensure!(metadata.is_file(), "{} is not a file", path.display());
+
+        bail!("Cannot load {}: {ONNX_UNAVAILABLE}", path.display())
+    }
+
+    fn input_schema(&self) -> &[TensorSpec] {
+        &self.inputs
+    }
+
+    fn output_schema(&self) -> &[TensorSpec] {
+        &self.outputs
+    }
+
+    fn infer(&self, _inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+        bail!(ONNX_UNAVAILABLE)
+    }
+}
+
+/// Motivo per cui la generazione si è fermata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FinishReason {
+    /// Raggiunto il limite di token.
+    #[default]
+    Length,
+    /// Il modello ha prodotto il token di fine sequenza.
+    EndOfSequence,
+    /// Il testo generato contiene una stop sequence.
+    StopSequence,
+    /// La generazione è stata interrotta dal chiamante.
+    Cancelled,
+}
+
+/// Output grezzo del modello, prima della valutazione delle policy.
+#[derive(Debug, Clone, Default)]
+pub struct ModelOutput {
+    /// Token generati dal modello (escluso il prompt e l'EOS).
+    pub token_ids: Vec<u32>,
+    /// Testo decodificato da `token_ids`.
+    pub text: String,
+    /// Motivo di fine generazione.
+    pub finish_reason: FinishReason,
+}
+
+impl ModelOutput {
+    /// Costruisce l'output decodificando `token_ids` con `tokenizer`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se un token non appartiene al vocabolario.
+    pub fn from_tokens(
+        token_ids: Vec<u32>,
+        finish_reason: FinishReason,
+        tokenizer: &Tokenizer,
+    ) -> Result<Self> {
+        let text = tokenizer.decode(&token_ids, true)?;
+        Ok(Self {
+            token_ids,
+            text,
+            finish_reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io_layer::SessionId;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Backend finto: il prossimo token è sempre `(ultimo + 1) % 3`.
+    struct NextTokenBackend {
+        inputs: Vec<TensorSpec>,
+    }
+
+    impl InferenceBackend for NextTokenBackend {
+        fn load(_path: &Path) -> Result<Self> {
+            Ok(Self {
+                inputs: vec![TensorSpec::new(
+                    "input_ids",
+                    DType::I64,
+                    vec![Some(1), None],
+                )],
+            })
+        }
+
+        fn input_schema(&self) -> &[TensorSpec] {
+            &self.inputs
+        }
+
+        fn output_schema(&self) -> &[TensorSpec] {
+            &[]
+        }
+
+        fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+            let last = inputs[0].as_i64().unwrap().last().copied().unwrap_or(2);
+            let mut logits = vec![0.0; 3];
+            logits[usize::try_from((last + 1) % 3).unwrap()] = 1.0;
+            Ok(vec![Tensor::f32("logits", vec![1, 3], logits)])
+        }
+    }
+
+    fn engine(tokenizer: Tokenizer) -> NeuralEngine<NextTokenBackend> {
+        NeuralEngine::new(
+            NextTokenBackend::load(Path::new("")).unwrap(),
+            Arc::new(tokenizer),
+        )
+    }
+
+    fn input(token_ids: Vec<u32>, max_new_tokens: usize) -> ModelInput {
+        let params = GenerationParams {
+            max_new_tokens,
+            ..GenerationParams::default()
+        };
+        ModelInput::new(SessionId::default(), token_ids, params)
+    }
+
+    #[tokio::test]
+    async fn infer_generates_greedily_up_to_max_new_tokens() {
+        let mut engine = engine(Tokenizer::byte_level());
+
+        let output = engine.infer(&input(vec![1], 5)).await.unwrap();
+        assert_eq!(output.token_ids, vec![2, 0, 1, 2, 0]);
+        assert_eq!(output.text.as_bytes(), [2, 0, 1, 2, 0]);
+        assert_eq!(output.finish_reason, FinishReason::Length);
+
+        // Il limite del motore prevale su quello della richiesta.
+        engine.set_max_new_tokens(2);
+        let output = engine.infer(&input(vec![1], 5)).await.unwrap();
+        assert_eq!(output.token_ids, vec![2, 0]);
+    }
+
+    /// Backend che blocca il thread per `delay` e registra se nel frattempo
+    /// un altro task è riuscito a girare.
+    struct BlockingBackend {
+        inputs: Vec<TensorSpec>,
+        delay: std::time::Duration,
+        ticked: Arc<AtomicBool>,
+        ran_alongside: Arc<AtomicBool>,
+    }
+
+    impl InferenceBackend for BlockingBackend {
+        fn load(_path: &Path) -> Result<Self> {
+            bail!("constructed directly in tests")
+        }
+
+        fn input_schema(&self) -> &[TensorSpec] {
+            &self.inputs
+        }
+
+        fn output_schema(&self) -> &[TensorSpec] {
+            &[]
+        }
+
+        fn infer(&self, _inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+            std::thread::sleep(self.delay);
+            if self.ticked.load(Ordering::SeqCst) {
+                self.ran_alongside.store(true, Ordering::SeqCst);
+            }
+            Ok(vec![Tensor::f32("logits", vec![1, 3], vec![1.0, 0.0, 0.0])])
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn slow_inference_does_not_stall_other_tasks() {
+        let ticked = Arc::new(AtomicBool::new(false));
+        let ran_alongside = Arc::new(AtomicBool::new(false));
+        let engine = NeuralEngine::new(
+            BlockingBackend {
+                inputs: vec![TensorSpec::new(
+                    "input_ids",
+                    DType::I64,
+                    vec![Some(1), None],
+                )],
+                delay: std::time::Duration::from_millis(200),
+                ticked: Arc::clone(&ticked),
+                ran_alongside: Arc::clone(&ran_alongside),
+            },
+            Arc::new(Tokenizer::byte_level()),
+        );
+
+        // Entrambi i task girano sull'unico worker del runtime.
+        let background = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            ticked.store(true, Ordering::SeqCst);
+        });
+        let inference = tokio::spawn(async move { engine.infer(&input(vec![1], 1)).await });
+        inference.await.unwrap().unwrap();
+        background.await.unwrap();
+
+        assert!(ran_alongside.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn infer_stops_at_end_of_sequence() {
+        let raw = serde_json::json!({
+            "added_tokens": [{ "id": 0, "content": "</s>", "special": true }],
+            "model": { "type": "BPE", "vocab": { "</s>": 0, "a": 1, "b": 2 } }
+        });
+        let engine = engine(Tokenizer::from_json(&raw.to_string()).unwrap());
+
+        let output = engine.infer(&input(vec![1], 10)).await.unwrap();
+        assert_eq!(output.token_ids, vec![2]);
+        assert_eq!(output.text, "b");
+        assert_eq!(output.finish_reason, FinishReason::EndOfSequence);
+    }
+
+    /// Tokenizer con vocabolario `a`, `b`, `c` (id 0, 1, 2).
+    fn letters() -> Tokenizer {
+        let raw = serde_json::json!({
+            "model": { "type": "BPE", "vocab": { "a": 0, "b": 1, "c": 2 } }
+        });
+        Tokenizer::from_json(&raw.to_string()).unwrap()
+    }
+
+    async fn collect(stream: BoxStream<'_, Result<StreamEvent>>) -> Vec<StreamEvent> {
+        stream.map(Result::unwrap).collect().await
+    }
+
+    #[tokio::test]
+    async fn infer_stream_emits_tokens_then_finishes() {
+        let engine = engine(letters());
+        let events =
+            collect(engine.infer_stream(&input(vec![0], 3), CancellationToken::new())).await;
+
+        let token = |id, text: &str| StreamEvent::Token {
+            id,
+            text: text.to_owned(),
+        };
+        assert_eq!(
+            events,
+            vec![
+                token(1, "b"),
+                token(2, "c"),
+                token(0, "a"),
+                StreamEvent::Finished {
+                    text: String::new(),
+                    reason: FinishReason::Length,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn infer_stream_holds_back_and_strips_stop_sequences() {
+        let engine = engine(letters());
+        let mut request = input(vec![0], 10);
+        request.params.stop_sequences = vec!["ab".to_owned()];
+
+        let events = collect(engine.infer_stream(&request, CancellationToken::new())).await;
+        let texts: Vec<&str> = events
+            .iter()
+            .map(|event| match event {
+                StreamEvent::Token { text, .. } | StreamEvent::Finished { text, .. } => {
+                    text.as_str()
+                }
+            })
+            .collect();
+        // "a" resta trattenuta finché non si capisce che inizia "ab".
+        assert_eq!(texts, vec!["b", "c", "", ""]);
+        assert!(matches!(
+            events.last(),
+            Some(StreamEvent::Finished {
+                reason: FinishReason::StopSequence,
+                ..
+            })
+        ));
+
+        let output = engine.infer(&request).await.unwrap();
+        assert_eq!(output.text, "bc");
+        assert_eq!(output.finish_reason, FinishReason::StopSequence);
+    }
+
+    #[tokio::test]
+    async fn infer_stream_stops_when_cancelled() {
+        let engine = engine(letters());
+        let cancel = CancellationToken::new();
+        let mut stream = engine.infer_stream(&input(vec![0], 10), cancel.clone());
+
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(StreamEvent::Token { id: 1, .. }))
+        ));
+        cancel.cancel();
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            StreamEvent::Finished {
+                text: String::new(),
+                reason: FinishReason::Cancelled,
+            }
+        );
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn sampler_is_greedy_at_zero_temperature_and_seeded_otherwise() {
+        let logits = [0.1, 3.0, 0.2, 2.9];
+        let mut greedy = Sampler::new(&GenerationParams::default());
+        assert_eq!(greedy.pick(&logits).unwrap(), 1);
+
+        let params = GenerationParams {
+            temperature: 1.0,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let draws = |params: &GenerationParams| {
+            let mut sampler = Sampler::new(params);
+            (0..32)
+                .map(|_| sampler.pick(&logits).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let first = draws(&params);
+        assert_eq!(first, draws(&params));
+        assert!(first.iter().all(|&id| id < 4));
+        assert!(first.contains(&1) && first.contains(&3));
+    }
+
+    #[test]
+    fn tensor_spec_accepts_dynamic_dimensions() {
+        let spec = TensorSpec::new("input_ids", DType::I64, vec![Some(1), None]);
+
+        assert!(spec.accepts(&Tensor::i64("input_ids", vec![1, 7], vec![0; 7])));
+        assert!(!spec.accepts(&Tensor::i64("input_ids", vec![2, 7], vec![0; 14])));
+        assert!(!spec.accepts(&Tensor::f32("input_ids", vec![1, 1], vec![0.0])));
+        assert_eq!(Tensor::zeros(&spec).shape, vec![1, 1]);
+    }
+
+    #[test]
+    fn load_backend_selects_by_extension() {
+        let dir = std::env::temp_dir().join(format!("samaritan-engine-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mlp = dir.join("model.safetensors");
+        cpu::MlpWeights::seeded(8, 4, 4, 1)
+            .to_safetensors()
+            .write(&mlp)
+            .unwrap();
+        let backend = load_backend(&mlp).unwrap();
+        assert_eq!(backend.output_schema()[0].shape, vec![Some(1), Some(8)]);
+
+        let onnx = dir.join("model.onnx");
+        std::fs::write(&onnx, b"onnx").unwrap();
+        let err = load_backend(&onnx).err().unwrap();
+        assert!(err.to_string().contains("ONNX Runtime is not available"));
+
+        assert!(load_backend(&dir.join("missing.onnx")).is_err());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,422 @@
+//! Pure-Rust CPU reference backend.
+//!
+//! [`CpuMlpBackend`] esegue un piccolo language model MLP interamente in
+//! Rust, senza dipendenze native. È pensato come backend di riferimento per
+//! nodi solo-CPU e per i test end-to-end del nodo.
+//!
+//! # Modello
+//!
+//! Dato il contesto di token, il modello:
+//!
+//! 1. fa la media degli embedding degli ultimi `context_window` token,
+//! 2. applica uno strato nascosto con ReLU,
+//! 3. proietta sul vocabolario producendo i logit del prossimo token.
+//!
+//! ```text
+//! x      = mean(embed[t] for t in context[-window..])     // [dim]
+//! h      = relu(hidden.weight · x + hidden.bias)          // [hidden]
+//! logits = output.weight · h + output.bias                // [vocab]
+//! ```
+//!
+//! # Formato safetensors
+//!
+//! | tensore         | shape            |
+//! |-----------------|------------------|
+//! | `embed.weight`  | `[vocab, dim]`   |
+//! | `hidden.weight` | `[hidden, dim]`  |
+//! | `hidden.bias`   | `[hidden]`       |
+//! | `output.weight` | `[vocab, hidden]`|
+//! | `output.bias`   | `[vocab]`        |
+//!
+//! Il metadato opzionale `context_window` (default: 8) limita quanti token
+//! del contesto vengono considerati.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use std::path::Path;
+
+use super::safetensors::{F32Tensor, SafeTensors};
+use super::{DType, InferenceBackend, Tensor, TensorSpec, XorShift64};
+
+/// Finestra di contesto di default, se non specificata nei metadati.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8;
+
+/// Nome dell'input atteso dal backend.
+pub const INPUT_IDS: &str = "input_ids";
+/// Nome dell'output prodotto dal backend.
+pub const LOGITS: &str = "logits";
+
+/// Pesi del modello MLP, già validati.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MlpWeights {
+    /// Embedding dei token, `[vocab, dim]`.
+    pub embed: F32Tensor,
+    /// Pesi dello strato nascosto, `[hidden, dim]`.
+    pub hidden_weight: F32Tensor,
+    /// Bias dello strato nascosto, `[hidden]`.
+    pub hidden_bias: F32Tensor,
+    /// Pesi della proiezione sul vocabolario, `[vocab, hidden]`.
+    pub output_weight: F32Tensor,
+    /// Bias della proiezione sul vocabolario, `[vocab]`.
+    pub output_bias: F32Tensor,
+    /// Numero massimo di token di contesto considerati.
+    pub context_window: usize,
+}
+
+impl MlpWeights {
+    /// Estrae e valida i pesi da un file safetensors già letto.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se mancano tensori o le shape sono incoerenti.
+    pub fn from_safetensors(mut st: SafeTensors) -> Result<Self> {
+        let context_window = match st.metadata.get("context_window") {
+            Some(raw) => raw
+                .parse()
+                .with_context(|| format!("Invalid context_window metadata {raw:?}"))?,
+            None => DEFAULT_CONTEXT_WINDOW,
+        };
+
+        let weights = Self {
+            embed: st.take("embed.weight", 2)?,
+            hidden_weight: st.take("hidden.weight", 2)?,
+            hidden_bias: st.take("hidden.bias", 1)?,
+            output_weight: st.take("output.weight", 2)?,
+            output_bias: st.take("output.bias", 1)?,
+            context_window,
+        };
+        weights.validate()?;
+        Ok(weights)
+    }
+
+    /// Converte i pesi in formato safetensors.
+    #[must_use]
+    pub fn to_safetensors(&self) -> SafeTensors {
+        let mut st = SafeTensors::default();
+        for (name, tensor) in [
+            ("embed.weight", &self.embed),
+            ("hidden.weight", &self.hidden_weight),
+            ("hidden.bias", &self.hidden_bias),
+            ("output.weight", &self.output_weight),
+            ("output.bias", &self.output_bias),
+        ] {
+            st.tensors.insert(name.to_owned(), tensor.clone());
+        }
+        st.metadata
+            .insert("context_window".to_owned(), self.context_window.to_string());
+        st
+    }
+
+    /// Genera pesi pseudo-casuali deterministici (utile per test e demo).
+    ///
+    /// Gli stessi parametri producono sempre gli stessi pesi.
+    #[must_use]
+    pub fn seeded(vocab_size: usize, dim: usize, hidden: usize, seed: u64) -> Self {
+        let mut rng = XorShift64::new(seed);
+        let mut next = move || rng.next_f32() - 0.5;
+        let mut tensor = |shape: Vec<usize>| {
+            let data = (0..shape.iter().product()).map(|_| next()).collect();
+            F32Tensor { shape, data }
+        };
+
+        Self {
+            embed: tensor(vec![vocab_size, dim]),
+            hidden_weight: tensor(vec![hidden, dim]),
+            hidden_bias: tensor(vec![hidden]),
+            output_weight: tensor(vec![vocab_size, hidden]),
+            output_bias: tensor(vec![vocab_size]),
+            context_window: DEFAULT_CONTEXT_WINDOW,
+        }
+    }
+
+    /// Dimensione del vocabolario.
+    #[must_use]
+    pub fn vocab_size(&self) -> usize {
+        self.embed.shape[0]
+    }
+
+    fn dim(&self) -> usize {
+        self.embed.shape[1]
+    }
+
+    fn hidden(&self) -> usize {
+        self.hidden_weight.shape[0]
+    }
+
+    fn validate(&self) -> Result<()> {
+        let (vocab, dim, hidden) = (self.vocab_size(), self.dim(), self.hidden());
+        ensure!(
+            vocab > 0 && dim > 0 && hidden > 0,
+            "Model dimensions must be non-zero"
+        );
+        ensure!(
+            self.hidden_weight.shape == [hidden, dim],
+            "hidden.weight must be [{hidden}, {dim}], found {:?}",
+            self.hidden_weight.shape
+        );
+        ensure!(
+            self.hidden_bias.shape == [hidden],
+            "hidden.bias must be [{hidden}], found {:?}",
+            self.hidden_bias.shape
+        );
+        ensure!(
+            self.output_weight.shape == [vocab, hidden],
+            "output.weight must be [{vocab}, {hidden}], found {:?}",
+            self.output_weight.shape
+        );
+        ensure!(
+            self.output_bias.shape == [vocab],
+            "output.bias must be [{vocab}], found {:?}",
+            self.output_bias.shape
+        );
+        ensure!(self.context_window > 0, "context_window must be non-zero");
+        Ok(())
+    }
+}
+
+/// Backend di riferimento che esegue un [`MlpWeights`] su CPU.
+#[derive(Debug, Clone)]
+pub struct CpuMlpBackend {
+    weights: MlpWeights,
+    inputs: Vec<TensorSpec>,
+    outputs: Vec<TensorSpec>,
+}
+
+impl CpuMlpBackend {
+    /// Crea il backend da pesi già in memoria.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se le shape dei pesi sono incoerenti.
+    pub fn from_weights(weights: MlpWeights) -> Result<Self> {
+        weights.validate()?;
+        let vocab = weights.vocab_size();
+        Ok(Self {
+            weights,
+            inputs: vec![TensorSpec::new(INPUT_IDS, DType::I64, vec![Some(1), None])],
+            outputs: vec![TensorSpec::new(
+                LOGITS,
+                DType::F32,
+                vec![Some(1), Some(vocab)],
+            )],
+        })
+    }
+
+    /// Restituisce i pesi del modello.
+    #[must_use]
+    pub const fn weights(&self) -> &MlpWeights {
+        &self.weights
+    }
+
+    /// Calcola i logit del prossimo token per il contesto dato.
+    fn forward(&self, token_ids: &[i64]) -> Result<Vec<f32>> {
+        let w = &self.weights;
+        let (vocab, dim, hidden) = (w.vocab_size(), w.dim(), w.hidden());
+
+        // 1) Media degli embedding sulla finestra di contesto.
+        let window = &token_ids[token_ids.len().saturating_sub(w.context_window)..];
+        let mut x = vec![0.0_f32; dim];
+        for &id in window {
+            let row = usize::try_from(id)
+                .ok()
+                .filter(|&row| row < vocab)
+                .ok_or_else(|| anyhow!("Token id {id} out of vocabulary (size {vocab})"))?;
+            for (acc, value) in x.iter_mut().zip(&w.embed.data[row * dim..(row + 1) * dim]) {
+                *acc += value;
+            }
+        }
+        if !window.is_empty() {
+            #[allow(clippy::cast_precision_loss)]
+            let scale = 1.0 / window.len() as f32;
+            for value in &mut x {
+                *value *= scale;
+            }
+        }
+
+        // 2) Strato nascosto + ReLU.
+        let h = affine(&w.hidden_weight.data, &w.hidden_bias.data, &x, hidden)
+            .into_iter()
+            .map(|value| value.max(0.0))
+            .collect::<Vec<_>>();
+
+        // 3) Proiezione sul vocabolario.
+        Ok(affine(
+            &w.output_weight.data,
+            &w.output_bias.data,
+            &h,
+            vocab,
+        ))
+    }
+}
+
+/// Calcola `weight · x + bias` con `weight` di shape `[rows, x.len()]`.
+fn affine(weight: &[f32], bias: &[f32], x: &[f32], rows: usize) -> Vec<f32> {
+    let cols = x.len();
+    (0..rows)
+        .map(|r| {
+            weight[r * cols..(r + 1) * cols]
+                .iter()
+                .zip(x)
+                .fold(bias[r], |acc, (w, v)| w.mul_add(*v, acc))
+        })
+        .collect()
+}
+
+impl InferenceBackend for CpuMlpBackend {
+    fn load(path: &Path) -> Result<Self> {
+        let st = SafeTensors::read(path)?;
+        let weights = MlpWeights::from_safetensors(st)
+            .with_context(|| format!("Invalid MLP model {}", path.display()))?;
+        Self::from_weights(weights)
+    }
+
+    fn input_schema(&self) -> &[TensorSpec] {
+        &self.inputs
+    }
+
+    fn output_schema(&self) -> &[TensorSpec] {
+        &self.outputs
+    }
+
+    fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+        let input = inputs
+            .iter()
+            .find(|tensor| tensor.name == INPUT_IDS)
+            .ok_or_else(|| anyhow!("Missing input tensor {INPUT_IDS}"))?;
+        ensure!(
+            self.inputs[0].accepts(input),
+            "Input {INPUT_IDS} must be i64 [1, seq_len], found {:?} {:?}",
+            input.dtype(),
+            input.shape
+        );
+        let token_ids = input
+            .as_i64()
+            .ok_or_else(|| anyhow!("Input {INPUT_IDS} must be i64"))?;
+
+        let logits = self.forward(token_ids)?;
+        let vocab = logits.len();
+        Ok(vec![Tensor::f32(LOGITS, vec![1, vocab], logits)])
+    }
+
+    fn export_weights(&self) -> Option<SafeTensors> {
+        Some(self.weights.to_safetensors())
+    }
+
+    fn import_weights(&mut self, weights: SafeTensors) -> Result<()> {
+        let weights = MlpWeights::from_safetensors(weights)?;
+        ensure!(
+            weights.vocab_size() == self.weights.vocab_size(),
+            "Snapshot vocabulary ({}) does not match the model ({})",
+            weights.vocab_size(),
+            self.weights.vocab_size()
+        );
+        *self = Self::from_weights(weights)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(shape: Vec<usize>, data: Vec<f32>) -> F32Tensor {
+        F32Tensor { shape, data }
+    }
+
+    /// Modello giocattolo con vocabolario di 3 token in cui ogni token
+    /// "predice" il successivo: 0 → 1 → 2 → 0.
+    fn cyclic_weights() -> MlpWeights {
+        MlpWeights {
+            // embedding one-hot
+            embed: tensor(
+                vec![3, 3],
+                vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            ),
+            hidden_weight: tensor(
+                vec![3, 3],
+                vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            ),
+            hidden_bias: tensor(vec![3], vec![0.0; 3]),
+            // riga i = logit del token i: il token i segue il token i-1
+            output_weight: tensor(
+                vec![3, 3],
+                vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            ),
+            output_bias: tensor(vec![3], vec![0.0; 3]),
+            context_window: 1,
+        }
+    }
+
+    fn argmax(values: &[f32]) -> usize {
+        values
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap()
+            .0
+    }
+
+    fn ids(values: &[i64]) -> Tensor {
+        Tensor::i64(INPUT_IDS, vec![1, values.len()], values.to_vec())
+    }
+
+    #[test]
+    fn forward_follows_cyclic_model() {
+        let backend = CpuMlpBackend::from_weights(cyclic_weights()).unwrap();
+
+        for (token, expected) in [(0, 1), (1, 2), (2, 0)] {
+            let out = backend.infer(&[ids(&[token])]).unwrap();
+            assert_eq!(out[0].shape, vec![1, 3]);
+            assert_eq!(argmax(out[0].as_f32().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn context_window_limits_history() {
+        let backend = CpuMlpBackend::from_weights(cyclic_weights()).unwrap();
+        // Con finestra 1 conta solo l'ultimo token.
+        let out = backend.infer(&[ids(&[2, 2, 0])]).unwrap();
+        assert_eq!(argmax(out[0].as_f32().unwrap()), 1);
+    }
+
+    #[test]
+    fn rejects_out_of_vocabulary_tokens() {
+        let backend = CpuMlpBackend::from_weights(cyclic_weights()).unwrap();
+        assert!(backend.infer(&[ids(&[7])]).is_err());
+        assert!(backend.infer(&[ids(&[-1])]).is_err());
+    }
+
+    #[test]
+    fn rejects_inconsistent_shapes() {
+        let mut weights = cyclic_weights();
+        weights.output_bias = tensor(vec![2], vec![0.0; 2]);
+        assert!(CpuMlpBackend::from_weights(weights).is_err());
+    }
+
+    #[test]
+    fn seeded_weights_are_deterministic_and_valid() {
+        let a = MlpWeights::seeded(16, 4, 8, 42);
+        let b = MlpWeights::seeded(16, 4, 8, 42);
+        let c = MlpWeights::seeded(16, 4, 8, 43);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.embed.data.iter().all(|v| v.abs() <= 0.5));
+        assert!(CpuMlpBackend::from_weights(a).is_ok());
+    }
+
+    #[test]
+    fn load_reads_safetensors_from_disk() {
+        let path = std::env::temp_dir().join(format!(
+            "samaritan-mlp-{}.safetensors",
+            uuid::Uuid::new_v4()
+        ));
+        cyclic_weights().to_safetensors().write(&path).unwrap();
+
+        let backend = CpuMlpBackend::load(&path).unwrap();
+        assert_eq!(backend.weights(), &cyclic_weights());
+        assert_eq!(backend.output_schema()[0].shape, vec![Some(1), Some(3)]);
+        backend.warmup().unwrap();
+
+        std::fs::remove_file(path).ok();
+    }
+}
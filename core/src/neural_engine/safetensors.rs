@@ -0,0 +1,279 @@
+//! Minimal reader/writer for the safetensors format.
+//!
+//! Layout del file:
+//!
+//! ```text
+//! ┌──────────────┬──────────────────────────┬──────────────────────┐
+//! │ u64 LE: N    │ header JSON (N byte)     │ buffer dati          │
+//! └──────────────┴──────────────────────────┴──────────────────────┘
+//! ```
+//!
+//! L'header mappa ogni nome di tensore a `dtype`, `shape` e `data_offsets`
+//! (relativi all'inizio del buffer dati); la chiave opzionale
+//! `__metadata__` contiene coppie stringa → stringa.
+//!
+//! Sono supportati solo tensori `F32` little-endian, sufficienti per il
+//! backend di riferimento [`super::cpu::CpuMlpBackend`].
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const METADATA_KEY: &str = "__metadata__";
+
+/// Limite di sicurezza sulla dimensione dell'header JSON (100 MB).
+const MAX_HEADER_LEN: u64 = 100 * 1024 * 1024;
+
+/// Tensore `f32` letto da (o da scrivere in) un file safetensors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct F32Tensor {
+    /// Shape del tensore.
+    pub shape: Vec<usize>,
+    /// Elementi in ordine row-major.
+    pub data: Vec<f32>,
+}
+
+/// Contenuto di un file safetensors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SafeTensors {
+    /// Tensori indicizzati per nome.
+    pub tensors: HashMap<String, F32Tensor>,
+    /// Metadati liberi (`__metadata__`).
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TensorHeader {
+    dtype: String,
+    shape: Vec<usize>,
+    data_offsets: [usize; 2],
+}
+
+impl SafeTensors {
+    /// Legge un file safetensors da disco.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o non è valido.
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Unable to read safetensors file {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Invalid safetensors file {}", path.display()))
+    }
+
+    /// Decodifica un file safetensors già in memoria.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se header, offset o dtype non sono validi.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let (len_bytes, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or_else(|| anyhow!("File too short for safetensors header"))?;
+        let header_len = u64::from_le_bytes(*len_bytes);
+        ensure!(
+            header_len <= MAX_HEADER_LEN,
+            "Header too large ({header_len} bytes)"
+        );
+        let header_len = usize::try_from(header_len)?;
+        ensure!(rest.len() >= header_len, "Truncated safetensors header");
+
+        let (header_bytes, buffer) = rest.split_at(header_len);
+        let raw: BTreeMap<String, serde_json::Value> = serde_json::from_slice(header_bytes)?;
+
+        let mut parsed = Self::default();
+        for (name, value) in raw {
+            if name == METADATA_KEY {
+                parsed.metadata = serde_json::from_value(value)?;
+                continue;
+            }
+
+            let header: TensorHeader = serde_json::from_value(value)
+                .with_context(|| format!("Invalid header for tensor {name}"))?;
+            if header.dtype != "F32" {
+                bail!("Tensor {name}: unsupported dtype {}", header.dtype);
+            }
+
+            let [start, end] = header.data_offsets;
+            ensure!(
+                start <= end && end <= buffer.len(),
+                "Tensor {name}: data offsets out of bounds"
+            );
+            let bytes = header
+                .shape
+                .iter()
+                .try_fold(4_usize, |bytes, &dim| bytes.checked_mul(dim))
+                .ok_or_else(|| anyhow!("Tensor {name}: shape {:?} overflows", header.shape))?;
+            ensure!(
+                end - start == bytes,
+                "Tensor {name}: shape {:?} does not match {} data bytes",
+                header.shape,
+                end - start
+            );
+
+            let data = buffer[start..end]
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect();
+
+            parsed.tensors.insert(
+                name,
+                F32Tensor {
+                    shape: header.shape,
+                    data,
+                },
+            );
+        }
+
+        Ok(parsed)
+    }
+
+    /// Serializza in formato safetensors (tensori in ordine alfabetico).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se un tensore ha dati incoerenti con la shape.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut names: Vec<&String> = self.tensors.keys().collect();
+        names.sort();
+
+        let mut header = serde_json::Map::new();
+        if !self.metadata.is_empty() {
+            header.insert(
+                METADATA_KEY.to_owned(),
+                serde_json::to_value(&self.metadata)?,
+            );
+        }
+
+        let mut buffer = Vec::new();
+        for name in names {
+            let tensor = &self.tensors[name];
+            ensure!(
+                tensor.shape.iter().product::<usize>() == tensor.data.len(),
+                "Tensor {name}: shape {:?} does not match {} elements",
+                tensor.shape,
+                tensor.data.len()
+            );
+
+            let start = buffer.len();
+            for value in &tensor.data {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            let entry = TensorHeader {
+                dtype: "F32".to_owned(),
+                shape: tensor.shape.clone(),
+                data_offsets: [start, buffer.len()],
+            };
+            header.insert(name.clone(), serde_json::to_value(entry)?);
+        }
+
+        let header_bytes = serde_json::to_vec(&header)?;
+        let mut bytes = Vec::with_capacity(8 + header_bytes.len() + buffer.len());
+        bytes.extend_from_slice(&u64::try_from(header_bytes.len())?.to_le_bytes());
+        bytes.extend_from_slice(&header_bytes);
+        bytes.extend_from_slice(&buffer);
+        Ok(bytes)
+    }
+
+    /// Scrive il file safetensors su disco.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la serializzazione o la scrittura falliscono.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("Unable to write safetensors file {}", path.display()))
+    }
+
+    /// Rimuove e restituisce il tensore `name`, verificandone la shape.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il tensore manca o ha un numero di dimensioni diverso
+    /// da `rank`.
+    pub fn take(&mut self, name: &str, rank: usize) -> Result<F32Tensor> {
+        let tensor = self
+            .tensors
+            .remove(name)
+            .ok_or_else(|| anyhow!("Missing tensor {name}"))?;
+        ensure!(
+            tensor.shape.len() == rank,
+            "Tensor {name}: expected rank {rank}, found shape {:?}",
+            tensor.shape
+        );
+        Ok(tensor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SafeTensors {
+        let mut st = SafeTensors::default();
+        st.tensors.insert(
+            "a".to_owned(),
+            F32Tensor {
+                shape: vec![2, 2],
+                data: vec![1.0, -2.0, 3.5, 0.25],
+            },
+        );
+        st.tensors.insert(
+            "b".to_owned(),
+            F32Tensor {
+                shape: vec![3],
+                data: vec![0.0, 1.0, 2.0],
+            },
+        );
+        st.metadata
+            .insert("context_window".to_owned(), "4".to_owned());
+        st
+    }
+
+    #[test]
+    fn roundtrip_preserves_tensors_and_metadata() {
+        let st = sample();
+        let parsed = SafeTensors::parse(&st.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, st);
+    }
+
+    #[test]
+    fn rejects_truncated_buffer() {
+        let bytes = sample().to_bytes().unwrap();
+        assert!(SafeTensors::parse(&bytes[..bytes.len() - 4]).is_err());
+        assert!(SafeTensors::parse(&bytes[..4]).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_dtype() {
+        let header = br#"{"x":{"dtype":"F16","shape":[1],"data_offsets":[0,2]}}"#;
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&[0, 0]);
+
+        let err = SafeTensors::parse(&bytes).unwrap_err();
+        assert!(err.to_string().contains("unsupported dtype"));
+    }
+
+    #[test]
+    fn rejects_overflowing_shape() {
+        let header = format!(
+            r#"{{"x":{{"dtype":"F32","shape":[{},2],"data_offsets":[0,0]}}}}"#,
+            usize::MAX
+        );
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+
+        let err = SafeTensors::parse(&bytes).unwrap_err();
+        assert!(err.to_string().contains("overflows"), "{err:#}");
+    }
+
+    #[test]
+    fn take_checks_rank() {
+        let mut st = sample();
+        assert!(st.take("a", 1).is_err());
+        assert!(st.take("b", 1).is_ok());
+        assert!(st.take("b", 1).is_err());
+    }
+}
@@ -1,62 +1,758 @@
 //! Node configuration and runner.
+//!
+//! Questo modulo fa da glue tra la configurazione su disco (`samaritan.yaml`)
+//! e il [`NeuroNode`]:
+//!
+//! - [`NodeConfig`]: configurazione deserializzata da YAML,
+//! - [`build_node`]: bootstrap del nodo applicando la configurazione,
+//! - [`run_node`]: loop a tick con cadenza configurabile e shutdown pulito
+//!   ([`run_node_with_hook`] per eseguire un [`TickHook`] prima di ogni tick).
+//!
+//! # Esempio
+//!
+//! ```no_run
+//! use samaritan_core::node::{build_node, run_node, NodeConfig};
+//! use tokio_util::sync::CancellationToken;
+//!
+//! # async fn demo() -> anyhow::Result<()> {
+//! let config = NodeConfig::load_default()?;
+//! let node = build_node(&config).await?;
+//!
+//! let shutdown = CancellationToken::new();
+//! let node = run_node(node, config.runtime.tick_interval(), shutdown.clone()).await?;
+//! # drop(node);
+//! # Ok(())
+//! # }
+//! ```
 
-use anyhow::Result;
-use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
+use crate::adaptive_throttle::{SystemGuardrails, ThrottleConfig};
+use crate::io_layer::queue::{DEFAULT_QUEUE_CAPACITY, DEFAULT_SESSION_QUEUE_LIMIT};
 use crate::node_profile::NodeProfile;
+use crate::scheduler::SchedulerConfig;
 use crate::NeuroNode;
 
+/// Configurazione completa di un nodo, tipicamente letta da `samaritan.yaml`.
 #[derive(Debug, Clone, Deserialize)]
 pub struct NodeConfig {
+    /// Directory dati del nodo (persistenza locale).
     pub data_dir: PathBuf,
+    /// Percorso del modello globale.
     pub model_path: PathBuf,
+    /// Profilo forzato; se assente viene auto-rilevato.
     #[serde(default)]
     pub profile_override: Option<NodeProfile>,
+    /// Sezione `federated:`.
     #[serde(default)]
     pub federated: FederatedConfig,
+    /// Sezione `policy:`.
     #[serde(default)]
     pub policy: PolicyConfig,
+    /// Sezione `runtime:`.
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    /// Sezione `throttle:`.
+    #[serde(default)]
+    pub throttle: ThrottleOverrides,
+    /// Sezione `scheduler:`.
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+/// Configurazione del Federated Learning.
+#[derive(Debug, Clone, Deserialize)]
 pub struct FederatedConfig {
+    /// Abilita il training locale (default: `true`).
     #[serde(default = "FederatedConfig::default_enabled")]
     pub enabled: bool,
+    /// Endpoint del server federato, se presente.
     #[serde(default)]
     pub endpoint: Option<String>,
 }
 
 impl FederatedConfig {
-    fn default_enabled() -> bool {
+    const fn default_enabled() -> bool {
         true
     }
 }
 
+impl Default for FederatedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            endpoint: None,
+        }
+    }
+}
+
+/// Configurazione del [`crate::policy_core::PolicyCore`].
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct PolicyConfig {
+    /// Avvia il nodo con la modalità strict attiva.
     #[serde(default)]
     pub strict_mode: bool,
 }
 
+/// Configurazione del loop di esecuzione.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuntimeConfig {
+    /// Intervallo tra due tick consecutivi, in millisecondi (default: 10).
+    #[serde(default = "RuntimeConfig::default_tick_interval_ms")]
+    pub tick_interval_ms: u64,
+    /// Input utente in coda oltre i quali i nuovi vengono scartati
+    /// (default: 64).
+    #[serde(default = "RuntimeConfig::default_input_queue_capacity")]
+    pub input_queue_capacity: usize,
+    /// Input in coda ammessi per singola sessione (default: 8).
+    #[serde(default = "RuntimeConfig::default_input_queue_per_session")]
+    pub input_queue_per_session: usize,
+}
+
+impl RuntimeConfig {
+    const fn default_tick_interval_ms() -> u64 {
+        10
+    }
+
+    const fn default_input_queue_capacity() -> usize {
+        DEFAULT_QUEUE_CAPACITY
+    }
+
+    const fn default_input_queue_per_session() -> usize {
+        DEFAULT_SESSION_QUEUE_LIMIT
+    }
+
+    /// Restituisce la cadenza dei tick come [`Duration`].
+    #[must_use]
+    pub const fn tick_interval(&self) -> Duration {
+        Duration::from_millis(self.tick_interval_ms)
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            tick_interval_ms: Self::default_tick_interval_ms(),
+            input_queue_capacity: Self::default_input_queue_capacity(),
+            input_queue_per_session: Self::default_input_queue_per_session(),
+        }
+    }
+}
+
+/// Override del preset di [`ThrottleConfig`] del profilo.
+///
+/// Il preset dipende dal profilo, noto solo al bootstrap: per questo i campi
+/// sono opzionali e vengono applicati sopra il preset da [`build_node`].
+///
+/// ```yaml
+/// throttle:
+///   target_latency_ms: 30
+///   kp: 0.04
+///   throttled_min_dwell_ms: 500
+///   guardrails:
+///     cpu_throttled_ratio: 0.8
+///   online_tuning: true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThrottleOverrides {
+    /// Latenza target per tick, in millisecondi.
+    pub target_latency_ms: Option<f64>,
+    /// Coefficiente proporzionale del PID.
+    pub kp: Option<f64>,
+    /// Coefficiente integrale del PID.
+    pub ki: Option<f64>,
+    /// Coefficiente derivativo del PID.
+    pub kd: Option<f64>,
+    /// Latenza di ingresso in Survival mode, in ms.
+    pub survival_threshold_ms: Option<f64>,
+    /// Latenza di uscita da Survival mode, in ms.
+    pub survival_exit_ms: Option<f64>,
+    /// Intensità di ingresso in Throttled mode.
+    pub throttled_threshold: Option<f64>,
+    /// Intensità di uscita da Throttled mode.
+    pub throttled_exit_threshold: Option<f64>,
+    /// Permanenza minima in Throttled mode, in ms.
+    pub throttled_min_dwell_ms: Option<u64>,
+    /// Permanenza minima in Survival mode, in ms.
+    pub survival_min_dwell_ms: Option<u64>,
+    /// Riempimento della coda di input da cui si va in Throttled mode.
+    pub queue_throttled_ratio: Option<f64>,
+    /// Riempimento della coda di input da cui si va in Survival mode.
+    pub queue_survival_ratio: Option<f64>,
+    /// Sottosezione `guardrails:`.
+    #[serde(default)]
+    pub guardrails: GuardrailOverrides,
+    /// Attiva il tuning online dei guadagni del PID (default: `false`).
+    #[serde(default)]
+    pub online_tuning: bool,
+}
+
+/// Override dei [`SystemGuardrails`] del profilo.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuardrailOverrides {
+    /// Uso della CPU da cui si va in Throttled mode.
+    pub cpu_throttled_ratio: Option<f64>,
+    /// Uso della CPU da cui si va in Survival mode.
+    pub cpu_survival_ratio: Option<f64>,
+    /// RAM disponibile sotto la quale si va in Throttled mode.
+    pub mem_available_throttled_ratio: Option<f64>,
+    /// RAM disponibile sotto la quale si va in Survival mode.
+    pub mem_available_survival_ratio: Option<f64>,
+    /// PSI memoria o I/O da cui si va in Throttled mode.
+    pub pressure_throttled_pct: Option<f64>,
+    /// PSI memoria o I/O da cui si va in Survival mode.
+    pub pressure_survival_pct: Option<f64>,
+    /// PSI CPU da cui si va in Throttled mode.
+    pub cpu_pressure_throttled_pct: Option<f64>,
+    /// PSI CPU da cui si va in Survival mode.
+    pub cpu_pressure_survival_pct: Option<f64>,
+}
+
+/// Sostituisce `field` con `value`, se presente.
+fn apply<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+impl ThrottleOverrides {
+    /// Applica gli override a `preset` e valida il risultato.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna un errore che nomina il primo campo non valido (es.
+    /// `survival_exit_ms`).
+    pub fn apply_to(&self, mut preset: ThrottleConfig) -> Result<ThrottleConfig> {
+        apply(&mut preset.target_latency_ms, self.target_latency_ms);
+        apply(&mut preset.kp, self.kp);
+        apply(&mut preset.ki, self.ki);
+        apply(&mut preset.kd, self.kd);
+        apply(
+            &mut preset.survival_threshold_ms,
+            self.survival_threshold_ms,
+        );
+        apply(&mut preset.survival_exit_ms, self.survival_exit_ms);
+        apply(&mut preset.throttled_threshold, self.throttled_threshold);
+        apply(
+            &mut preset.throttled_exit_threshold,
+            self.throttled_exit_threshold,
+        );
+        apply(
+            &mut preset.throttled_min_dwell,
+            self.throttled_min_dwell_ms.map(Duration::from_millis),
+        );
+        apply(
+            &mut preset.survival_min_dwell,
+            self.survival_min_dwell_ms.map(Duration::from_millis),
+        );
+        apply(
+            &mut preset.queue_throttled_ratio,
+            self.queue_throttled_ratio,
+        );
+        apply(&mut preset.queue_survival_ratio, self.queue_survival_ratio);
+        self.guardrails.apply_to(&mut preset.guardrails);
+
+        preset.validate().context("Invalid throttle section")?;
+        Ok(preset)
+    }
+}
+
+impl GuardrailOverrides {
+    fn apply_to(&self, guardrails: &mut SystemGuardrails) {
+        apply(
+            &mut guardrails.cpu_throttled_ratio,
+            self.cpu_throttled_ratio,
+        );
+        apply(&mut guardrails.cpu_survival_ratio, self.cpu_survival_ratio);
+        apply(
+            &mut guardrails.mem_available_throttled_ratio,
+            self.mem_available_throttled_ratio,
+        );
+        apply(
+            &mut guardrails.mem_available_survival_ratio,
+            self.mem_available_survival_ratio,
+        );
+        apply(
+            &mut guardrails.pressure_throttled_pct,
+            self.pressure_throttled_pct,
+        );
+        apply(
+            &mut guardrails.pressure_survival_pct,
+            self.pressure_survival_pct,
+        );
+        apply(
+            &mut guardrails.cpu_pressure_throttled_pct,
+            self.cpu_pressure_throttled_pct,
+        );
+        apply(
+            &mut guardrails.cpu_pressure_survival_pct,
+            self.cpu_pressure_survival_pct,
+        );
+    }
+}
+
 impl NodeConfig {
+    /// Legge la configurazione da un file YAML.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o non è YAML valido.
     pub fn from_yaml(path: &Path) -> Result<Self> {
-        let raw = std::fs::read_to_string(path)?;
-        let cfg: NodeConfig = serde_yaml::from_str(&raw)?;
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read node config {}", path.display()))?;
+        let cfg: Self = serde_yaml::from_str(&raw)
+            .with_context(|| format!("Invalid node config {}", path.display()))?;
         Ok(cfg)
     }
 
+    /// Legge `samaritan.yaml` dalla directory corrente.
+    ///
+    /// # Errors
+    ///
+    /// Vedi [`NodeConfig::from_yaml`].
     pub fn load_default() -> Result<Self> {
         Self::from_yaml(Path::new("samaritan.yaml"))
     }
 }
 
-pub async fn build_node(_config: &NodeConfig) -> Result<NeuroNode> {
-    unimplemented!("build_node")
+/// Costruisce un [`NeuroNode`] a partire dalla configurazione.
+///
+/// Il nodo viene bootstrappato con [`NeuroNode::bootstrap`], poi:
+///
+/// - `federated.endpoint` viene passato a [`crate::net::NetClient::set_endpoint`],
+/// - `federated.enabled = false` disabilita il training locale,
+/// - `policy.strict_mode = true` attiva [`crate::policy_core::PolicyCore::enable_strict_mode`],
+/// - `runtime.input_queue_*` limitano la coda di input
+///   ([`crate::io_layer::IOLayer::set_input_queue_limits`]),
+/// - `throttle:` sovrascrive il preset [`ThrottleConfig`] del profilo
+///   ([`ThrottleOverrides::apply_to`]) e `throttle.online_tuning` attiva il
+///   tuning online dei guadagni,
+/// - `scheduler:` sostituisce la [`SchedulerConfig`] di default.
+///
+/// # Errors
+///
+/// Ritorna errore se le sezioni `throttle:` o `scheduler:` non sono valide
+/// (l'errore nomina il campo) o se il bootstrap del nodo fallisce.
+pub async fn build_node(config: &NodeConfig) -> Result<NeuroNode> {
+    config
+        .scheduler
+        .validate()
+        .context("Invalid scheduler section")?;
+
+    let mut node = NeuroNode::bootstrap(
+        config.data_dir.clone(),
+        config.model_path.clone(),
+        config.profile_override,
+    )
+    .await?;
+
+    if let Some(endpoint) = &config.federated.endpoint {
+        node.net_client.set_endpoint(endpoint.clone());
+    }
+
+    if !config.federated.enabled {
+        node.federated.write().await.disable_training();
+        info!("Federated training disabled by config");
+    }
+
+    if config.policy.strict_mode {
+        node.policy_core.enable_strict_mode("config");
+    }
+
+    node.io_layer.set_input_queue_limits(
+        config.runtime.input_queue_capacity,
+        config.runtime.input_queue_per_session,
+    );
+
+    let throttle = config
+        .throttle
+        .apply_to(ThrottleConfig::for_profile(&node.profile))?;
+    node.adaptive_throttle.set_config(throttle);
+    node.adaptive_throttle
+        .set_online_tuning(config.throttle.online_tuning);
+    node.scheduler.set_config(config.scheduler.clone());
+
+    Ok(node)
+}
+
+/// Esegue il loop a tick del nodo finché `shutdown` non viene cancellato.
+///
+/// Ogni `tick_interval` viene chiamato [`NeuroNode::tick`]; se un tick dura
+/// più dell'intervallo, il successivo parte subito dopo senza recuperare i
+/// tick persi. Alla cancellazione il tick in corso viene completato, lo
+/// stato del throttle salvato ([`NeuroNode::save_throttle_state`]) e il nodo
+/// restituito al chiamante (es. per persistere altro stato).
+///
+/// # Errors
+///
+/// Ritorna il primo errore fatale prodotto da [`NeuroNode::tick`].
+pub async fn run_node(
+    node: NeuroNode,
+    tick_interval: Duration,
+    shutdown: CancellationToken,
+) -> Result<NeuroNode> {
+    run_node_with_hook(node, tick_interval, shutdown, &mut ()).await
+}
+
+/// Lavoro eseguito dal loop del nodo prima di ogni tick.
+///
+/// Serve ai componenti che non possiedono il [`NeuroNode`] (server HTTP,
+/// control plane) per applicargli le richieste ricevute nel frattempo.
+pub trait TickHook: Send {
+    /// Applica a `node` le richieste in attesa.
+    ///
+    /// # Errors
+    ///
+    /// Un errore è fatale e ferma il loop del nodo.
+    fn before_tick(&mut self, node: &mut NeuroNode) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl TickHook for () {
+    async fn before_tick(&mut self, _node: &mut NeuroNode) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<H: TickHook> TickHook for Option<H> {
+    async fn before_tick(&mut self, node: &mut NeuroNode) -> Result<()> {
+        match self {
+            Some(hook) => hook.before_tick(node).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: TickHook, B: TickHook> TickHook for (A, B) {
+    async fn before_tick(&mut self, node: &mut NeuroNode) -> Result<()> {
+        self.0.before_tick(node).await?;
+        self.1.before_tick(node).await
+    }
 }
 
-pub async fn run_node(_node: NeuroNode) -> ! {
+/// Come [`run_node`], eseguendo `hook` prima di ogni tick.
+///
+/// # Errors
+///
+/// Ritorna il primo errore fatale prodotto da `hook` o da
+/// [`NeuroNode::tick`].
+pub async fn run_node_with_hook(
+    mut node: NeuroNode,
+    tick_interval: Duration,
+    shutdown: CancellationToken,
+    hook: &mut impl TickHook,
+) -> Result<NeuroNode> {
+    // `tokio::time::interval` non accetta periodi nulli.
+    let mut interval = tokio::time::interval(tick_interval.max(Duration::from_millis(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    info!("NeuroNode loop started (tick every {tick_interval:?})");
+
     loop {
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        tokio::select! {
+            biased;
+            () = shutdown.cancelled() => break,
+            _ = interval.tick() => {
+                hook.before_tick(&mut node).await?;
+                node.tick().await?;
+            }
+        }
+    }
+
+    if let Err(err) = node.save_throttle_state().await {
+        warn!("Unable to save throttle state: {err:#}");
+    }
+    info!(
+        "NeuroNode loop stopped after {} ticks (uptime {:.0?})",
+        node.tick_counter,
+        node.uptime()
+    );
+
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptive_throttle::ThrottleLevel;
+    use crate::neural_engine::cpu::MlpWeights;
+
+    fn temp_data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("samaritan-node-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    /// Config di test con un piccolo modello CPU scritto in `data_dir`.
+    fn test_config(data_dir: PathBuf) -> NodeConfig {
+        let model_path = data_dir.join("model.safetensors");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        MlpWeights::seeded(256, 4, 8, 7)
+            .to_safetensors()
+            .write(&model_path)
+            .unwrap();
+
+        NodeConfig {
+            model_path,
+            data_dir,
+            profile_override: Some(NodeProfile::Desktop),
+            federated: FederatedConfig::default(),
+            policy: PolicyConfig::default(),
+            runtime: RuntimeConfig::default(),
+            throttle: ThrottleOverrides::default(),
+            scheduler: SchedulerConfig::default(),
+        }
+    }
+
+    #[test]
+    fn yaml_defaults_enable_federated_training() {
+        let cfg: NodeConfig = serde_yaml::from_str(
+            "data_dir: /tmp/samaritan\nmodel_path: /tmp/samaritan/model.onnx\n",
+        )
+        .unwrap();
+
+        assert!(cfg.federated.enabled);
+        assert!(cfg.federated.endpoint.is_none());
+        assert!(!cfg.policy.strict_mode);
+        assert_eq!(cfg.runtime.tick_interval(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn yaml_sections_are_parsed() {
+        let cfg: NodeConfig = serde_yaml::from_str(
+            "data_dir: /tmp/samaritan\n\
+             model_path: /tmp/samaritan/model.onnx\n\
+             profile_override: Mobile\n\
+             federated:\n  enabled: false\n  endpoint: https://fed.example.org\n\
+             policy:\n  strict_mode: true\n\
+             runtime:\n  tick_interval_ms: 250\n  input_queue_capacity: 4\n",
+        )
+        .unwrap();
+
+        assert_eq!(cfg.profile_override, Some(NodeProfile::Mobile));
+        assert!(!cfg.federated.enabled);
+        assert_eq!(
+            cfg.federated.endpoint.as_deref(),
+            Some("https://fed.example.org")
+        );
+        assert!(cfg.policy.strict_mode);
+        assert_eq!(cfg.runtime.tick_interval(), Duration::from_millis(250));
+        assert_eq!(cfg.runtime.input_queue_capacity, 4);
+        assert_eq!(
+            cfg.runtime.input_queue_per_session,
+            DEFAULT_SESSION_QUEUE_LIMIT
+        );
+    }
+
+    #[test]
+    fn yaml_errors_name_the_bad_field() {
+        let parse = |sections: &str| {
+            serde_yaml::from_str::<NodeConfig>(&format!(
+                "data_dir: /tmp/samaritan\nmodel_path: /tmp/samaritan/model.onnx\n{sections}"
+            ))
+            .map(drop)
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert!(parse("throttle:\n  kp: fast\n").starts_with("throttle.kp: invalid type"));
+        assert!(parse("throttle:\n  kpp: 0.1\n").contains("unknown field `kpp`"));
+        assert!(
+            parse("throttle:\n  guardrails:\n    cpu_survival_ratio: high\n")
+                .starts_with("throttle.guardrails.cpu_survival_ratio:")
+        );
+        assert!(parse("scheduler:\n  normal_weight: -1\n").starts_with("scheduler.normal_weight:"));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::float_cmp)]
+    async fn build_node_applies_throttle_and_scheduler_sections() {
+        let data_dir = temp_data_dir();
+        let mut cfg = test_config(data_dir.clone());
+        let sections: NodeConfig = serde_yaml::from_str(
+            "data_dir: /tmp/samaritan\n\
+             model_path: /tmp/samaritan/model.onnx\n\
+             throttle:\n  target_latency_ms: 30\n  survival_min_dwell_ms: 1500\n  \
+             guardrails:\n    cpu_throttled_ratio: 0.8\n    cpu_pressure_survival_pct: 70\n  \
+             online_tuning: true\n\
+             scheduler:\n  background_weight: 2\n",
+        )
+        .unwrap();
+        cfg.throttle = sections.throttle;
+        cfg.scheduler = sections.scheduler;
+
+        let mut node = build_node(&cfg).await.unwrap();
+        let throttle = node.adaptive_throttle.config();
+        assert_eq!(throttle.target_latency_ms, 30.0);
+        assert_eq!(throttle.survival_min_dwell, Duration::from_millis(1500));
+        assert_eq!(throttle.guardrails.cpu_throttled_ratio, 0.8);
+        assert_eq!(throttle.guardrails.cpu_pressure_survival_pct, 70.0);
+        // I campi non sovrascritti restano quelli del preset Desktop.
+        assert_eq!(throttle.kp, ThrottleConfig::desktop().kp);
+        assert_eq!(
+            throttle.guardrails.cpu_survival_ratio,
+            SystemGuardrails::desktop().cpu_survival_ratio
+        );
+        assert!(node.adaptive_throttle.online_tuner().is_some());
+        assert_eq!(node.scheduler.config().background_weight, 2);
+        assert_eq!(node.scheduler.config().critical_weight, 10);
+
+        // Il profilo non riporta la configurazione al preset.
+        node.tick().await.unwrap();
+        assert_eq!(node.adaptive_throttle.config().target_latency_ms, 30.0);
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    async fn build_error(cfg: &NodeConfig) -> String {
+        let Err(err) = build_node(cfg).await else {
+            panic!("invalid config accepted");
+        };
+        format!("{err:#}")
+    }
+
+    #[tokio::test]
+    async fn build_node_rejects_invalid_sections() {
+        let data_dir = temp_data_dir();
+        let mut cfg = test_config(data_dir.clone());
+        // Desktop: survival_threshold_ms = 200.
+        cfg.throttle.survival_exit_ms = Some(250.0);
+        let err = build_error(&cfg).await;
+        assert!(err.contains("survival_exit_ms must be between"), "{err}");
+
+        cfg.throttle = ThrottleOverrides::default();
+        cfg.throttle.guardrails.pressure_survival_pct = Some(5.0);
+        let err = build_error(&cfg).await;
+        assert!(err.contains("guardrails.pressure_survival_pct"), "{err}");
+
+        cfg.throttle = ThrottleOverrides::default();
+        cfg.scheduler.max_budget_per_tick = 1.5;
+        let err = build_error(&cfg).await;
+        assert!(err.contains("max_budget_per_tick"), "{err}");
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn build_node_applies_config() {
+        let data_dir = temp_data_dir();
+        let mut cfg = test_config(data_dir.clone());
+        cfg.federated.enabled = false;
+        cfg.federated.endpoint = Some("https://fed.example.org".to_owned());
+        cfg.policy.strict_mode = true;
+        cfg.runtime.input_queue_capacity = 2;
+
+        let node = build_node(&cfg).await.unwrap();
+
+        assert_eq!(node.profile, NodeProfile::Desktop);
+        assert_eq!(node.net_client.endpoint(), Some("https://fed.example.org"));
+        assert!(!node
+            .federated
+            .read()
+            .await
+            .is_training_enabled()
+            .await
+            .unwrap());
+        assert!(node.policy_core.is_strict_mode());
+        assert_eq!(node.io_layer.queue_capacity(), 2);
+        assert_eq!(
+            node.meta_observer.policy_version(),
+            Some(node.policy_core.version())
+        );
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn build_node_rejects_missing_model() {
+        let data_dir = temp_data_dir();
+        let mut cfg = test_config(data_dir.clone());
+        cfg.model_path = data_dir.join("missing.onnx");
+
+        assert!(build_node(&cfg).await.is_err());
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn build_node_keeps_defaults() {
+        let data_dir = temp_data_dir();
+        let node = build_node(&test_config(data_dir.clone())).await.unwrap();
+
+        assert_eq!(node.net_client.endpoint(), None);
+        assert!(node
+            .federated
+            .read()
+            .await
+            .is_training_enabled()
+            .await
+            .unwrap());
+        assert!(!node.policy_core.is_strict_mode());
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn run_node_ticks_until_cancelled() {
+        let data_dir = temp_data_dir();
+        let node = build_node(&test_config(data_dir.clone())).await.unwrap();
+
+        let shutdown = CancellationToken::new();
+        let handle = tokio::spawn(run_node(node, Duration::from_millis(1), shutdown.clone()));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.cancel();
+
+        let node = handle.await.unwrap().unwrap();
+        assert!(node.tick_counter > 0);
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn throttle_state_is_restored_after_restart() {
+        let data_dir = temp_data_dir();
+        let config = test_config(data_dir.clone());
+        let mut node = build_node(&config).await.unwrap();
+        node.adaptive_throttle
+            .record_tick_latency(Duration::from_secs(1));
+
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        drop(
+            run_node(node, Duration::from_secs(3600), shutdown)
+                .await
+                .unwrap(),
+        );
+
+        let node = build_node(&config).await.unwrap();
+        assert_eq!(
+            node.adaptive_throttle.current_level(),
+            ThrottleLevel::Survival
+        );
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn run_node_returns_immediately_if_already_cancelled() {
+        let data_dir = temp_data_dir();
+        let node = build_node(&test_config(data_dir.clone())).await.unwrap();
+
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        let node = run_node(node, Duration::from_secs(3600), shutdown)
+            .await
+            .unwrap();
+        assert_eq!(node.tick_counter, 0);
+
+        std::fs::remove_dir_all(data_dir).ok();
     }
 }
@@ -32,7 +32,7 @@ use tracing::warn;
 ///
 /// Determina quali task il nodo può eseguire, con quali priorità,
 /// e come viene schedulato il training federato.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub enum NodeProfile {
     /// Server o workstation con GPU dedicata potente.
     ///
@@ -62,6 +62,7 @@ pub enum NodeProfile {
     /// - GPU integrata o entry-level
     ///
     /// Ruolo: inferenza locale, training leggero, partecipazione federata parziale.
+    #[default]
     Desktop,
 
     /// Laptop, tablet, o dispositivo mobile/embedded.
@@ -136,12 +137,6 @@ impl NodeProfile {
     }
 }
 
-impl Default for NodeProfile {
-    fn default() -> Self {
-        Self::Desktop
-    }
-}
-
 impl std::fmt::Display for NodeProfile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.as_str())
@@ -175,7 +170,7 @@ impl HardwareCapabilities {
     /// - 8GB RAM
     /// - nessuna GPU dedicata
     #[must_use]
-    pub fn fallback() -> Self {
+    pub const fn fallback() -> Self {
         Self {
             cpu_cores: 4,
             ram_mb: 8_192,
@@ -211,7 +206,7 @@ impl HardwareCapabilities {
     /// 3. Se CPU <= 4 core o RAM <= 8GB → `Mobile`
     /// 4. Altrimenti → `Desktop`
     #[must_use]
-    pub fn classify(&self) -> NodeProfile {
+    pub const fn classify(&self) -> NodeProfile {
         // HeavyGpu: GPU potente + sistema potente
         if self.has_dedicated_gpu
             && self.gpu_vram_mb >= 8_000
@@ -239,12 +234,13 @@ impl HardwareCapabilities {
     ///
     /// Usa `std::thread::available_parallelism()` (stable da Rust 1.59).
     fn detect_cpu_cores() -> usize {
-        std::thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or_else(|_| {
+        std::thread::available_parallelism().map_or_else(
+            |_| {
                 warn!("Failed to detect CPU cores, using fallback: 4");
                 4
-            })
+            },
+            std::num::NonZero::get,
+        )
     }
 
     /// Rileva la RAM totale di sistema, in MB.
@@ -1,19 +1,1073 @@
 //! Policy core for safety and governance.
+//!
+//! Il [`PolicyCore`] applica un insieme di regole con nome ([`rules::RuleSet`])
+//! caricato da `data_dir/policy.yaml` (o la policy di default inclusa nel
+//! crate). Ogni regola ha una categoria, una severità e un'azione; se più
+//! regole corrispondono vince l'azione più restrittiva
+//! (`refuse` > `escalate` > `redact` > `allow`).
+//!
+//! Oltre alle regole, il rilevatore [`pii::PiiDetector`] individua dati
+//! personali (carte, IBAN, email, telefoni, codici fiscali, IP); ogni
+//! redazione applicata è riportata in [`PolicyDecision::redactions`] con
+//! il suo intervallo nel testo originale.
+//!
+//! In modalità strict ([`PolicyCore::set_strict_mode`]) il core:
+//!
+//! - rifiuta a partire dalla soglia `thresholds.strict` invece di
+//!   `thresholds.normal`,
+//! - rifiuta invece di redigere i dati personali,
+//! - aggiunge [`STRICT_DISCLAIMER`] alle risposte consentite che hanno
+//!   comunque attivato qualche regola.
+//!
+//! Il file di policy viene ricontrollato periodicamente
+//! ([`PolicyCore::poll_reload`], polling dell'mtime): un nuovo file viene
+//! attivato solo se è valido e supera i suoi `self_tests`, altrimenti le
+//! regole correnti restano in vigore. Versione e hash delle regole attive
+//! sono riportati in ogni [`PolicyDecision`].
+//!
+//! Le decisioni definitive (input, output completo, blocchi durante lo
+//! streaming) sono registrate nel log di audit a catena di hash
+//! ([`audit::AuditLog`]) sotto `data_dir/policy/audit/`.
+//!
+//! Il core valuta sia l'output completo del modello sia il testo parziale
+//! durante lo streaming, così da poter interrompere una risposta a metà
+//! generazione.
 
-use anyhow::Result;
-use std::path::Path;
+pub mod audit;
+pub mod pii;
+pub mod regression;
+pub mod rules;
 
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+use crate::neural_engine::ModelOutput;
+use audit::{AuditLog, AUDIT_DIR};
+use rules::{Action, Category, PolicyVersion, RuleSet, Severity};
+
+/// Nome del file di policy, nella radice di `data_dir`.
+pub const POLICY_FILE: &str = "policy.yaml";
+
+/// Intervallo di default tra due controlli dell'mtime del file di policy.
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Messaggio consegnato all'utente al posto di una risposta rifiutata.
+pub const REFUSAL_MESSAGE: &str = "I can't help with that request.";
+
+/// Messaggio consegnato all'utente quando la risposta è inoltrata a
+/// revisione umana.
+pub const ESCALATION_MESSAGE: &str =
+    "This request needs human review. If you are in danger, please contact local emergency services.";
+
+/// Messaggio consegnato all'utente quando il modello non riesce a
+/// rispondere (tokenizzazione o inferenza fallite).
+pub const INFERENCE_FAILURE_MESSAGE: &str =
+    "The model could not answer this request. Please try again later.";
+
+/// Avvertenza aggiunta in modalità strict alle risposte borderline.
+pub const STRICT_DISCLAIMER: &str =
+    "Note: this answer touches on sensitive topics and is provided for general information only.";
+
+/// Esito della valutazione di policy, in ordine crescente di restrittività.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// Il testo può essere consegnato così com'è.
+    Allow,
+    /// Il testo può essere consegnato dopo la redazione.
+    Redact,
+    /// Il testo non viene consegnato e va inoltrato a revisione umana.
+    Escalate,
+    /// Il testo non può essere consegnato.
+    Refuse,
+}
+
+impl From<Action> for Verdict {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Allow => Self::Allow,
+            Action::Redact => Self::Redact,
+            Action::Escalate => Self::Escalate,
+            Action::Refuse => Self::Refuse,
+        }
+    }
+}
+
+/// Punto della pipeline in cui viene valutato un testo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Prompt dell'utente, prima dell'inferenza.
+    Input,
+    /// Risposta del modello (parziale o completa).
+    Output,
+}
+
+/// Codice del motivo di un blocco, consegnato all'utente insieme alla
+/// decisione.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReasonCode {
+    /// Prompt rifiutato: l'inferenza non è stata eseguita.
+    InputRefused,
+    /// Prompt inoltrato a revisione umana: l'inferenza non è stata eseguita.
+    InputEscalated,
+    /// Risposta del modello rifiutata.
+    OutputRefused,
+    /// Risposta del modello inoltrata a revisione umana.
+    OutputEscalated,
+    /// Il modello non è riuscito a produrre una risposta.
+    InferenceFailed,
+}
+
+impl ReasonCode {
+    /// Codice per una decisione bloccante, se `verdict` lo è.
+    #[must_use]
+    pub const fn for_verdict(stage: Stage, verdict: Verdict) -> Option<Self> {
+        match (stage, verdict) {
+            (Stage::Input, Verdict::Refuse) => Some(Self::InputRefused),
+            (Stage::Input, Verdict::Escalate) => Some(Self::InputEscalated),
+            (Stage::Output, Verdict::Refuse) => Some(Self::OutputRefused),
+            (Stage::Output, Verdict::Escalate) => Some(Self::OutputEscalated),
+            (_, Verdict::Allow | Verdict::Redact) => None,
+        }
+    }
+
+    /// Codice stabile, adatto a client e log.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InputRefused => "input_refused",
+            Self::InputEscalated => "input_escalated",
+            Self::OutputRefused => "output_refused",
+            Self::OutputEscalated => "output_escalated",
+            Self::InferenceFailed => "inference_failed",
+        }
+    }
+}
+
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Decisione del [`PolicyCore`] su un testo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDecision {
+    /// Esito della valutazione.
+    pub verdict: Verdict,
+    /// Id delle regole corrispondenti, in ordine di valutazione.
+    pub matched_rules: Vec<String>,
+    /// Categorie delle regole corrispondenti (senza duplicati).
+    pub categories: Vec<Category>,
+    /// Severità massima tra le regole corrispondenti.
+    pub severity: Option<Severity>,
+    /// Redazioni applicate a `text` (solo per [`Verdict::Redact`]).
+    pub redactions: Vec<Redaction>,
+    /// Motivo del blocco (solo per decisioni bloccanti).
+    pub reason: Option<ReasonCode>,
+    /// Versione e hash delle regole che hanno prodotto la decisione
+    /// (`None` per decisioni costruite a mano).
+    pub policy: Option<PolicyVersion>,
+    /// Testo da consegnare all'utente (eventualmente riscritto).
+    pub text: String,
+}
+
+impl PolicyDecision {
+    /// Decisione che consegna `text` così com'è.
+    #[must_use]
+    pub fn allow(text: impl Into<String>) -> Self {
+        Self {
+            verdict: Verdict::Allow,
+            matched_rules: Vec::new(),
+            categories: Vec::new(),
+            severity: None,
+            redactions: Vec::new(),
+            reason: None,
+            policy: None,
+            text: text.into(),
+        }
+    }
+
+    /// Decisione di rifiuto in `stage`, con il messaggio standard.
+    #[must_use]
+    pub fn refuse(stage: Stage) -> Self {
+        Self {
+            verdict: Verdict::Refuse,
+            reason: ReasonCode::for_verdict(stage, Verdict::Refuse),
+            text: REFUSAL_MESSAGE.to_owned(),
+            ..Self::allow("")
+        }
+    }
+
+    /// Decisione consegnata al posto di una risposta che il modello non è
+    /// riuscito a produrre.
+    #[must_use]
+    pub fn inference_failed() -> Self {
+        Self {
+            verdict: Verdict::Refuse,
+            reason: Some(ReasonCode::InferenceFailed),
+            text: INFERENCE_FAILURE_MESSAGE.to_owned(),
+            ..Self::allow("")
+        }
+    }
+
+    /// Restituisce `true` se il testo può essere consegnato senza modifiche.
+    #[must_use]
+    pub fn is_allowed(&self) -> bool {
+        self.verdict == Verdict::Allow
+    }
+
+    /// Restituisce `true` se il testo originale non può essere consegnato
+    /// affatto (rifiuto o revisione umana).
+    #[must_use]
+    pub fn is_blocked(&self) -> bool {
+        self.verdict >= Verdict::Escalate
+    }
+}
+
+/// Parte di testo rimossa o sostituita da una redazione.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    /// Id della regola (o del tipo di PII) che ha causato la redazione.
+    pub rule: String,
+    /// Intervallo (in byte) nel testo valutato.
+    pub span: Range<usize>,
+    /// Testo inserito al posto dell'intervallo.
+    pub replacement: String,
+}
+
+/// Esito di un tentativo di ricaricare il file di policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadOutcome {
+    /// Nuove regole attivate.
+    Reloaded(PolicyVersion),
+    /// File non valido o self-test falliti: restano le regole correnti.
+    Rejected {
+        /// Descrizione dell'errore.
+        error: String,
+    },
+}
+
+/// Core delle policy di sicurezza, privacy e governance.
+#[derive(Debug)]
+pub struct PolicyCore {
+    strict_mode: bool,
+    rules: RuleSet,
+    audit: Option<Mutex<AuditLog>>,
+    source: Option<PolicySource>,
+}
+
+/// File di policy osservato per il hot reload.
 #[derive(Debug)]
-pub struct PolicyCore {}
+struct PolicySource {
+    path: PathBuf,
+    mtime: Option<SystemTime>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
 
 impl PolicyCore {
-    pub async fn load_or_default(_data_dir: &Path) -> Result<Self> {
-        Ok(Self {})
+    /// Carica le policy da `data_dir/policy.yaml`, oppure usa quelle di
+    /// default se il file non esiste, e apre il log di audit in
+    /// `data_dir/policy/audit/`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file di policy esiste ma non è leggibile o non
+    /// è valido, o se il log di audit non può essere aperto.
+    pub async fn load_or_default(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(POLICY_FILE);
+        let mtime = modified(&path);
+        let rules = match tokio::fs::read_to_string(&path).await {
+            Ok(raw) => RuleSet::from_yaml(&raw)
+                .with_context(|| format!("Invalid policy file {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => RuleSet::default_rules(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read policy file {}", path.display()))
+            }
+        };
+        run_self_tests(&rules)
+            .with_context(|| format!("Policy file {} failed its self-tests", path.display()))?;
+        info!(
+            "PolicyCore loaded {} rules (version {})",
+            rules.rules().len(),
+            rules.policy_version()
+        );
+
+        let audit = AuditLog::open(&data_dir.join(AUDIT_DIR))?;
+        let mut core = Self::with_rules(rules);
+        core.attach_audit_log(audit);
+        core.source = Some(PolicySource {
+            path,
+            mtime,
+            interval: DEFAULT_RELOAD_INTERVAL,
+            last_poll: None,
+        });
+        Ok(core)
+    }
+
+    /// Crea il core con un insieme di regole già compilato, senza log di
+    /// audit.
+    #[must_use]
+    pub const fn with_rules(rules: RuleSet) -> Self {
+        Self {
+            strict_mode: false,
+            rules,
+            audit: None,
+            source: None,
+        }
+    }
+
+    /// Registra da ora in poi le decisioni definitive in `audit`.
+    pub fn attach_audit_log(&mut self, audit: AuditLog) {
+        self.audit = Some(Mutex::new(audit));
+    }
+
+    /// Regole attive.
+    #[must_use]
+    pub const fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// Versione e hash delle regole attive.
+    #[must_use]
+    pub const fn version(&self) -> &PolicyVersion {
+        self.rules.policy_version()
+    }
+
+    /// Imposta l'intervallo minimo tra due controlli del file di policy.
+    pub const fn set_reload_interval(&mut self, interval: Duration) {
+        if let Some(source) = &mut self.source {
+            source.interval = interval;
+        }
+    }
+
+    /// Ricarica il file di policy se la sua mtime è cambiata.
+    ///
+    /// Il controllo avviene al più una volta per intervallo di reload (vedi
+    /// [`PolicyCore::set_reload_interval`]). Ritorna `None` se non c'era
+    /// nulla da fare; un file rifiutato non viene ritentato finché non
+    /// cambia di nuovo.
+    pub fn poll_reload(&mut self) -> Option<ReloadOutcome> {
+        let source = self.source.as_mut()?;
+        let now = Instant::now();
+        if source
+            .last_poll
+            .is_some_and(|last| now.duration_since(last) < source.interval)
+        {
+            return None;
+        }
+        source.last_poll = Some(now);
+
+        let mtime = modified(&source.path);
+        if mtime.is_none() || mtime == source.mtime {
+            return None;
+        }
+        source.mtime = mtime;
+        Some(self.reload_now())
+    }
+
+    /// Rilegge subito il file di policy e, se valido e i suoi self-test
+    /// passano, sostituisce le regole attive; altrimenti le mantiene.
+    pub fn reload_now(&mut self) -> ReloadOutcome {
+        let Some(source) = &self.source else {
+            return ReloadOutcome::Rejected {
+                error: "PolicyCore has no policy file to reload".to_owned(),
+            };
+        };
+        let path = source.path.clone();
+        let candidate = RuleSet::load(&path).and_then(|rules| {
+            run_self_tests(&rules).context("Policy self-tests failed")?;
+            Ok(rules)
+        });
+
+        match candidate {
+            Ok(rules) => {
+                let version = rules.policy_version().clone();
+                info!(
+                    "PolicyCore reloaded {} ({} rules, was {})",
+                    version,
+                    rules.rules().len(),
+                    self.rules.policy_version()
+                );
+                self.rules = rules;
+                ReloadOutcome::Reloaded(version)
+            }
+            Err(err) => {
+                warn!(
+                    "PolicyCore kept {} after rejecting {}: {err:#}",
+                    self.rules.policy_version(),
+                    path.display()
+                );
+                ReloadOutcome::Rejected {
+                    error: format!("{err:#}"),
+                }
+            }
+        }
+    }
+
+    /// Valuta l'output del modello e produce una decisione di policy.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la valutazione non può essere completata o la
+    /// decisione non può essere registrata nel log di audit.
+    pub fn evaluate(&self, output: &ModelOutput) -> Result<PolicyDecision> {
+        let decision = self.evaluate_text(Stage::Output, &output.text)?;
+        self.record(Stage::Output, &output.text, &decision)?;
+        Ok(decision)
+    }
+
+    /// Valuta il prompt dell'utente prima dell'inferenza.
+    ///
+    /// Se la decisione è bloccante l'inferenza va saltata e la decisione
+    /// consegnata direttamente all'utente (con [`PolicyDecision::reason`]);
+    /// altrimenti il prompt da inviare al modello è `decision.text`
+    /// (eventualmente redatto).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la valutazione non può essere completata o la
+    /// decisione non può essere registrata nel log di audit.
+    pub fn evaluate_input(&self, prompt: &str) -> Result<PolicyDecision> {
+        let decision = self.evaluate_text(Stage::Input, prompt)?;
+        self.record(Stage::Input, prompt, &decision)?;
+        Ok(decision)
+    }
+
+    /// Valuta il testo parziale di una risposta in streaming.
+    ///
+    /// Se la decisione è bloccante ([`PolicyDecision::is_blocked`]) lo
+    /// stream va interrotto e la decisione consegnata al posto del resto
+    /// della risposta; se è [`Verdict::Redact`] i frammenti successivi
+    /// vanno trattenuti fino alla decisione finale.
+    ///
+    /// Solo le decisioni bloccanti vengono registrate nel log di audit (le
+    /// altre saranno superate dalla valutazione dell'output completo).
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la valutazione non può essere completata.
+    pub fn evaluate_partial(&self, text: &str) -> Result<PolicyDecision> {
+        let decision = self.evaluate_text(Stage::Output, text)?;
+        if decision.is_blocked() {
+            self.record(Stage::Output, text, &decision)?;
+        }
+        Ok(decision)
+    }
+
+    /// Lunghezza (in byte) del prefisso di `text` che nessun testo
+    /// successivo può più far rientrare in una corrispondenza.
+    ///
+    /// Durante lo streaming va consegnato solo questo prefisso. Restano
+    /// trattenute le ultime [`RuleSet::keyword_words`] parole (l'ultima può
+    /// essere ancora incompleta) e, se il rilevatore PII è attivo, la serie
+    /// di parole con cifre che le precede: carte, IBAN e numeri di telefono
+    /// vengono generati a gruppi e sono riconoscibili solo quando sono
+    /// completi.
+    #[must_use]
+    pub fn settled_len(&self, text: &str) -> usize {
+        let words = word_spans(text);
+        let mut held = words.len().saturating_sub(self.rules.keyword_words());
+        if self.rules.pii().spec().enabled {
+            while held > 0 && text[words[held - 1].clone()].contains(|c: char| c.is_ascii_digit()) {
+                held -= 1;
+            }
+        }
+        words.get(held).map_or(text.len(), |word| word.start)
+    }
+
+    /// Registra una decisione nel log di audit, se presente.
+    fn record(&self, stage: Stage, text: &str, decision: &PolicyDecision) -> Result<()> {
+        let Some(audit) = &self.audit else {
+            return Ok(());
+        };
+        let mut audit = audit
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit log lock poisoned"))?;
+        audit.append(
+            stage,
+            text,
+            decision,
+            self.rules.policy_version(),
+            self.strict_mode,
+        )?;
+        drop(audit);
+        Ok(())
+    }
+
+    /// Applica le regole a un testo valutato in `stage`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la valutazione non può essere completata.
+    pub fn evaluate_text(&self, stage: Stage, text: &str) -> Result<PolicyDecision> {
+        let matches = self.rules.matches(text);
+        let pii = self.rules.pii();
+        let pii_matches = pii.detect(text);
+
+        let mut hits: Vec<(&str, Category, Severity, Action)> = matches
+            .iter()
+            .map(|m| {
+                let action = self.effective_action(m.rule.category, m.rule.severity, m.rule.action);
+                (m.rule.id.as_str(), m.rule.category, m.rule.severity, action)
+            })
+            .collect();
+        let mut candidates: Vec<Redaction> = matches
+            .iter()
+            .filter(|m| m.rule.action == Action::Redact)
+            .flat_map(|m| {
+                m.spans.iter().map(|span| Redaction {
+                    rule: m.rule.id.clone(),
+                    span: span.clone(),
+                    replacement: m.rule.replacement.clone(),
+                })
+            })
+            .collect();
+        let spec = pii.spec();
+        let pii_action = self.effective_action(Category::Pii, spec.severity, spec.action);
+        for m in &pii_matches {
+            let id = m.kind.rule_id();
+            if !hits.iter().any(|(hit, ..)| *hit == id) {
+                hits.push((id, Category::Pii, spec.severity, pii_action));
+            }
+            if pii_action == Action::Redact {
+                candidates.push(Redaction {
+                    rule: id.to_owned(),
+                    span: m.span.clone(),
+                    replacement: pii.replacement(m.kind, &text[m.span.clone()]),
+                });
+            }
+        }
+
+        let verdict = hits
+            .iter()
+            .map(|&(.., action)| Verdict::from(action))
+            .max()
+            .unwrap_or(Verdict::Allow);
+        let mut categories: Vec<Category> =
+            hits.iter().map(|&(_, category, ..)| category).collect();
+        categories.sort_unstable();
+        categories.dedup();
+
+        let (mut text, redactions) = match verdict {
+            Verdict::Allow => (text.to_owned(), Vec::new()),
+            Verdict::Redact => redact(text, candidates),
+            Verdict::Escalate => (ESCALATION_MESSAGE.to_owned(), Vec::new()),
+            Verdict::Refuse => (REFUSAL_MESSAGE.to_owned(), Vec::new()),
+        };
+        // Risposta borderline: consentita ma con almeno una corrispondenza.
+        if self.strict_mode
+            && stage == Stage::Output
+            && !hits.is_empty()
+            && verdict <= Verdict::Redact
+        {
+            text = format!("{text}\n\n{STRICT_DISCLAIMER}");
+        }
+
+        Ok(PolicyDecision {
+            verdict,
+            matched_rules: hits.iter().map(|&(id, ..)| id.to_owned()).collect(),
+            categories,
+            severity: hits.iter().map(|&(_, _, severity, _)| severity).max(),
+            redactions,
+            reason: ReasonCode::for_verdict(stage, verdict),
+            policy: Some(self.rules.policy_version().clone()),
+            text,
+        })
+    }
+
+    /// Azione effettiva di una corrispondenza, date le soglie di severità e
+    /// la modalità corrente.
+    fn effective_action(&self, category: Category, severity: Severity, action: Action) -> Action {
+        let thresholds = self.rules.thresholds();
+        let refuse_at = if self.strict_mode {
+            thresholds.strict
+        } else {
+            thresholds.normal
+        };
+        match action {
+            Action::Escalate | Action::Refuse => action,
+            _ if severity >= refuse_at => Action::Refuse,
+            Action::Redact if self.strict_mode && category == Category::Pii => Action::Refuse,
+            _ => action,
+        }
+    }
+
+    /// Attiva o disattiva la modalità strict a runtime.
+    ///
+    /// Ogni chiamata viene registrata (via `tracing`) insieme a
+    /// `triggered_by`, che identifica chi ha richiesto il cambio (es.
+    /// `"config"`, `"control-plane"`, `"repl"`).
+    pub fn set_strict_mode(&mut self, enabled: bool, triggered_by: &str) {
+        let previous = std::mem::replace(&mut self.strict_mode, enabled);
+        info!(
+            "PolicyCore strict mode {} by {triggered_by} (was {})",
+            if enabled { "enabled" } else { "disabled" },
+            if previous { "on" } else { "off" }
+        );
+    }
+
+    /// Attiva la modalità strict (policy più conservative).
+    ///
+    /// Vedi [`PolicyCore::set_strict_mode`].
+    pub fn enable_strict_mode(&mut self, triggered_by: &str) {
+        self.set_strict_mode(true, triggered_by);
+    }
+
+    /// Restituisce `true` se la modalità strict è attiva.
+    #[must_use]
+    pub const fn is_strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+}
+
+/// Intervalli (in byte) delle parole di `text`, separate da spazi.
+///
+/// Se `text` termina con uno spazio l'ultima parola è vuota: quella
+/// precedente è già completa.
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(index),
+            (true, Some(begin)) => {
+                spans.push(begin..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans.push(start.unwrap_or(text.len())..text.len());
+    spans
+}
+
+/// Mtime di `path`, se il file esiste.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Esegue i `self_tests` dichiarati da `rules`.
+///
+/// # Errors
+///
+/// Ritorna errore al primo caso con esito diverso da quello atteso.
+fn run_self_tests(rules: &RuleSet) -> Result<()> {
+    let mut core = PolicyCore::with_rules(rules.clone());
+    for (index, test) in rules.self_tests().iter().enumerate() {
+        core.strict_mode = test.strict;
+        let decision = core.evaluate_text(test.stage, &test.text)?;
+        anyhow::ensure!(
+            decision.verdict == test.expect,
+            "self-test #{} ({:?}): expected {:?}, got {:?}",
+            index + 1,
+            test.text,
+            test.expect,
+            decision.verdict
+        );
+    }
+    Ok(())
+}
+
+/// Applica le redazioni candidate a `text`.
+///
+/// Le redazioni sovrapposte vengono unite: l'intervallo unito usa il testo
+/// sostitutivo di quella che inizia prima (a parità, la più lunga). Ritorna il testo redatto e le
+/// redazioni effettivamente applicate, in ordine.
+fn redact(text: &str, mut candidates: Vec<Redaction>) -> (String, Vec<Redaction>) {
+    candidates.sort_by_key(|r| (r.span.start, std::cmp::Reverse(r.span.end)));
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut applied: Vec<Redaction> = Vec::new();
+    let mut cursor = 0;
+    for redaction in candidates {
+        if redaction.span.start < cursor {
+            // Sovrapposizione parziale: estende la redazione precedente.
+            if let Some(last) = applied.last_mut().filter(|_| redaction.span.end > cursor) {
+                last.span.end = redaction.span.end;
+                cursor = redaction.span.end;
+            }
+            continue;
+        }
+        redacted.push_str(&text[cursor..redaction.span.start]);
+        redacted.push_str(&redaction.replacement);
+        cursor = redaction.span.end;
+        applied.push(redaction);
+    }
+    redacted.push_str(&text[cursor..]);
+    (redacted, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r"
+version: test-2
+rules:
+  - id: hacking
+    category: crime-hacking
+    severity: high
+    action: refuse
+    keywords: [ransomware]
+  - id: distress
+    category: self-harm
+    severity: high
+    action: escalate
+    keywords: [hopeless]
+  - id: email
+    category: pii
+    severity: medium
+    action: redact
+    replacement: '[EMAIL]'
+    regex: ['[a-z]+@[a-z]+\.[a-z]+']
+  - id: digits
+    category: pii
+    severity: low
+    action: redact
+    regex: ['\d+']
+  - id: greeting
+    category: pii
+    severity: low
+    action: allow
+    keywords: [ciao]
+pii:
+  enabled: false
+";
+
+    fn core() -> PolicyCore {
+        PolicyCore::with_rules(RuleSet::from_yaml(POLICY).unwrap())
+    }
+
+    #[test]
+    fn unmatched_and_allow_rules_keep_the_text() {
+        let policy = core();
+
+        assert_eq!(
+            policy.evaluate_partial("all good").unwrap(),
+            PolicyDecision {
+                policy: Some(policy.version().clone()),
+                ..PolicyDecision::allow("all good")
+            }
+        );
+
+        let decision = policy.evaluate_partial("ciao a tutti").unwrap();
+        assert!(decision.is_allowed());
+        assert_eq!(decision.matched_rules, vec!["greeting"]);
+        assert_eq!(decision.text, "ciao a tutti");
+    }
+
+    #[test]
+    fn redaction_rewrites_matched_spans() {
+        let decision = core()
+            .evaluate_partial("ciao, write to ann@mail.com or call 555")
+            .unwrap();
+
+        assert_eq!(decision.verdict, Verdict::Redact);
+        assert_eq!(decision.matched_rules, vec!["email", "digits", "greeting"]);
+        assert_eq!(decision.categories, vec![Category::Pii]);
+        assert_eq!(decision.severity, Some(Severity::Medium));
+        assert_eq!(decision.text, "ciao, write to [EMAIL] or call [REDACTED]");
+        assert_eq!(
+            decision.redactions,
+            vec![
+                Redaction {
+                    rule: "email".to_owned(),
+                    span: 15..27,
+                    replacement: "[EMAIL]".to_owned(),
+                },
+                Redaction {
+                    rule: "digits".to_owned(),
+                    span: 36..39,
+                    replacement: "[REDACTED]".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn settled_prefix_holds_back_fragments_that_can_still_match() {
+        let without_pii = core();
+        assert_eq!(without_pii.settled_len(""), 0);
+        assert_eq!(without_pii.settled_len("call me"), 5);
+        assert_eq!(without_pii.settled_len("call me "), 8);
+        assert_eq!(without_pii.settled_len("call 4111 1111"), 10);
+
+        let rules = RuleSet::from_yaml(
+            "rules:\n  - {id: kw, category: self-harm, severity: high, action: refuse, keywords: ['end my life']}\n",
+        )
+        .unwrap();
+        let policy = PolicyCore::with_rules(rules);
+        assert_eq!(policy.rules().keyword_words(), 3);
+        assert_eq!(policy.settled_len("I want to end my"), 7);
+        assert_eq!(policy.settled_len("card 4111 1111 "), 5);
+        assert_eq!(policy.settled_len("IBAN IT60 X054 2811 ok"), 5);
+        assert_eq!(policy.settled_len("call +39 347 1234567 ok now then"), 5);
+        assert_eq!(
+            policy.settled_len("call +39 347 1234567 ok now then so"),
+            24
+        );
     }
 
-    pub fn evaluate(&self, _output: &crate::neural_engine::ModelOutput) -> Result<crate::io_layer::PolicyDecision> {
-        Ok(crate::io_layer::PolicyDecision {})
+    #[test]
+    fn pii_detector_reports_redaction_spans() {
+        let rules = RuleSet::from_yaml(
+            "rules:\n  - {id: word, category: pii, severity: low, action: redact, regex: ['card 4']}\npii:\n  mode: drop\n",
+        )
+        .unwrap();
+        let policy = PolicyCore::with_rules(rules);
+        let text = "card 4111 1111 1111 1111 or ip 10.0.0.1!";
+
+        let decision = policy.evaluate_input(text).unwrap();
+
+        assert_eq!(decision.verdict, Verdict::Redact);
+        assert_eq!(
+            decision.matched_rules,
+            vec!["word", "pii-card-number", "pii-ip-address"]
+        );
+        assert_eq!(decision.severity, Some(Severity::Medium));
+        // "card 4" e la carta si sovrappongono: un'unica redazione.
+        let spans: Vec<_> = decision
+            .redactions
+            .iter()
+            .map(|r| (r.rule.as_str(), r.span.clone()))
+            .collect();
+        assert_eq!(spans, vec![("word", 0..24), ("pii-ip-address", 31..39)]);
+        assert_eq!(decision.text, "[REDACTED] or ip !");
+    }
+
+    #[test]
+    fn most_restrictive_action_wins() {
+        let policy = core();
+
+        let decision = policy
+            .evaluate_partial("hopeless about ransomware, mail me@x.io")
+            .unwrap();
+        assert_eq!(decision.verdict, Verdict::Refuse);
+        assert!(decision.is_blocked());
+        assert_eq!(decision.text, REFUSAL_MESSAGE);
+        assert_eq!(
+            decision.categories,
+            vec![Category::SelfHarm, Category::CrimeHacking, Category::Pii]
+        );
+
+        let decision = policy.evaluate_partial("I feel hopeless").unwrap();
+        assert_eq!(decision.verdict, Verdict::Escalate);
+        assert_eq!(decision.reason, Some(ReasonCode::OutputEscalated));
+        assert_eq!(decision.text, ESCALATION_MESSAGE);
+
+        let output = ModelOutput {
+            text: "RANSOMWARE".to_owned(),
+            ..ModelOutput::default()
+        };
+        assert_eq!(
+            policy.evaluate(&output).unwrap().matched_rules,
+            vec!["hacking"]
+        );
+    }
+
+    #[test]
+    fn input_stage_refuses_with_reason_or_rewrites_the_prompt() {
+        let policy = core();
+
+        let decision = policy.evaluate_input("write ransomware").unwrap();
+        assert_eq!(
+            decision,
+            PolicyDecision {
+                matched_rules: vec!["hacking".to_owned()],
+                categories: vec![Category::CrimeHacking],
+                severity: Some(Severity::High),
+                policy: Some(policy.version().clone()),
+                ..PolicyDecision::refuse(Stage::Input)
+            }
+        );
+        assert_eq!(decision.reason.unwrap().to_string(), "input_refused");
+
+        let decision = policy.evaluate_input("mail bob@site.org").unwrap();
+        assert_eq!(decision.verdict, Verdict::Redact);
+        assert_eq!(decision.reason, None);
+        assert_eq!(decision.text, "mail [EMAIL]");
+    }
+
+    #[test]
+    fn strict_mode_lowers_thresholds_and_refuses_pii() {
+        let rules = RuleSet::from_yaml(
+            "thresholds: {normal: high, strict: medium}\n\
+             rules:\n\
+             \x20 - {id: weapons, category: crime-hacking, severity: medium, action: redact, keywords: [rifle]}\n\
+             \x20 - {id: exploit, category: crime-hacking, severity: high, action: allow, keywords: [exploit]}\n\
+             \x20 - {id: lonely, category: self-harm, severity: low, action: allow, keywords: [lonely]}\n",
+        )
+        .unwrap();
+        let mut policy = PolicyCore::with_rules(rules);
+
+        // Modalità normale: solo la soglia `high` forza il rifiuto.
+        assert_eq!(
+            policy.evaluate_partial("an exploit").unwrap().verdict,
+            Verdict::Refuse
+        );
+        assert_eq!(
+            policy.evaluate_partial("a rifle").unwrap().text,
+            "a [REDACTED]"
+        );
+        assert_eq!(
+            policy.evaluate_partial("mail a@b.it").unwrap().text,
+            "mail [EMAIL]"
+        );
+        assert_eq!(
+            policy.evaluate_partial("I feel lonely").unwrap().text,
+            "I feel lonely"
+        );
+
+        policy.set_strict_mode(true, "test");
+        assert!(policy.is_strict_mode());
+        assert_eq!(
+            policy.evaluate_partial("a rifle").unwrap().verdict,
+            Verdict::Refuse
+        );
+        let decision = policy.evaluate_input("mail a@b.it").unwrap();
+        assert_eq!(decision.verdict, Verdict::Refuse);
+        assert_eq!(decision.reason, Some(ReasonCode::InputRefused));
+
+        policy.set_strict_mode(false, "test");
+        assert_eq!(
+            policy.evaluate_partial("a rifle").unwrap().verdict,
+            Verdict::Redact
+        );
+    }
+
+    #[test]
+    fn strict_mode_adds_disclaimer_to_borderline_answers() {
+        let mut policy = core();
+        policy.enable_strict_mode("test");
+
+        let decision = policy.evaluate_partial("ciao a tutti").unwrap();
+        assert!(decision.is_allowed());
+        assert_eq!(
+            decision.text,
+            format!("ciao a tutti\n\n{STRICT_DISCLAIMER}")
+        );
+
+        assert_eq!(
+            policy.evaluate_partial("all good").unwrap().text,
+            "all good"
+        );
+        assert_eq!(policy.evaluate_input("ciao").unwrap().text, "ciao");
+    }
+
+    #[tokio::test]
+    async fn final_and_blocking_decisions_are_audited() {
+        let data_dir =
+            std::env::temp_dir().join(format!("samaritan-policy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::write(data_dir.join(POLICY_FILE), POLICY).unwrap();
+        let policy = PolicyCore::load_or_default(&data_dir).await.unwrap();
+
+        policy.evaluate_input("ciao").unwrap();
+        policy.evaluate_partial("all good").unwrap();
+        policy.evaluate_partial("ransomware").unwrap();
+        let output = ModelOutput {
+            text: "fine".to_owned(),
+            ..ModelOutput::default()
+        };
+        policy.evaluate(&output).unwrap();
+
+        let audit_dir = data_dir.join(AUDIT_DIR);
+        let report = AuditLog::verify(&audit_dir).unwrap();
+        assert!(report.is_intact());
+        assert_eq!(report.entries, 3);
+        let raw = std::fs::read_to_string(audit_dir.join("audit.log")).unwrap();
+        assert!(raw.contains("\"policy_version\":\"test-2\""));
+        assert!(raw.contains("\"verdict\":\"refuse\""));
+        assert!(!raw.contains("ransomware"));
+
+        std::fs::remove_dir_all(data_dir).ok();
     }
 
-    pub fn enable_strict_mode(&mut self) {}
+    #[tokio::test]
+    async fn hot_reload_swaps_only_valid_policies() {
+        let data_dir =
+            std::env::temp_dir().join(format!("samaritan-policy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let path = data_dir.join(POLICY_FILE);
+        std::fs::write(&path, POLICY).unwrap();
+        let mut policy = PolicyCore::load_or_default(&data_dir).await.unwrap();
+        policy.set_reload_interval(Duration::ZERO);
+        let original = policy.version().clone();
+        assert_eq!(policy.poll_reload(), None);
+
+        // Self-test fallito: restano le regole correnti.
+        let failing = format!("{POLICY}self_tests:\n  - {{text: ransomware, expect: allow}}\n");
+        write_with_mtime(&path, &failing, 1);
+        let Some(ReloadOutcome::Rejected { error }) = policy.poll_reload() else {
+            panic!("expected rejection");
+        };
+        assert!(error.contains("self-test #1"), "{error}");
+        assert_eq!(policy.version(), &original);
+        // Un file rifiutato non viene ritentato finché non cambia.
+        assert_eq!(policy.poll_reload(), None);
+
+        write_with_mtime(&path, "rules: [{id: broken}]", 2);
+        assert!(matches!(
+            policy.poll_reload(),
+            Some(ReloadOutcome::Rejected { .. })
+        ));
+        assert_eq!(policy.version(), &original);
+
+        let updated = POLICY.replace("test-2", "test-3");
+        write_with_mtime(&path, &updated, 3);
+        let Some(ReloadOutcome::Reloaded(version)) = policy.poll_reload() else {
+            panic!("expected reload");
+        };
+        assert_eq!(version.version, "test-3");
+        assert_ne!(version.hash, original.hash);
+        assert_eq!(
+            policy.evaluate_partial("ciao").unwrap().policy,
+            Some(version)
+        );
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
+
+    /// Scrive `path` forzando una mtime distinta (la risoluzione del
+    /// filesystem potrebbe non distinguere scritture ravvicinate).
+    fn write_with_mtime(path: &Path, contents: &str, secs: u64) {
+        std::fs::write(path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_or_default_reads_policy_file_from_data_dir() {
+        let data_dir =
+            std::env::temp_dir().join(format!("samaritan-policy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let policy = PolicyCore::load_or_default(&data_dir).await.unwrap();
+        assert_eq!(policy.rules().version(), RuleSet::default_rules().version());
+
+        std::fs::write(data_dir.join(POLICY_FILE), POLICY).unwrap();
+        let policy = PolicyCore::load_or_default(&data_dir).await.unwrap();
+        assert_eq!(policy.rules().version(), "test-2");
+
+        std::fs::write(data_dir.join(POLICY_FILE), "rules: [{id: x}]").unwrap();
+        assert!(PolicyCore::load_or_default(&data_dir).await.is_err());
+
+        std::fs::remove_dir_all(data_dir).ok();
+    }
 }
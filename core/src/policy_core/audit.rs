@@ -0,0 +1,607 @@
+//! Append-only, hash-chained audit log of policy decisions.
+//!
+//! Ogni decisione registrata diventa una riga JSON di `audit.log` sotto
+//! `data_dir/policy/audit/`. Ogni voce contiene l'hash della precedente e il
+//! proprio (`sha256(prev_hash || corpo)`), così una voce modificata o rimossa
+//! rompe la catena; il file `head.json` tiene numero e hash dell'ultima voce
+//! e permette di rilevare anche il troncamento della coda.
+//!
+//! Un crash durante un append lascia in coda una riga troncata: all'apertura
+//! successiva il log entra in modalità degradata ([`AuditLog::degraded`]),
+//! chiude la riga e fa ripartire la catena dall'ultima voce valida. La
+//! verifica accetta le righe troncate seguite da una voce che si aggancia
+//! correttamente alla catena e le riporta in [`AuditReport::torn_lines`].
+//!
+//! Il log non contiene mai testo utente: solo un digest SHA-256 salato con
+//! un salt casuale del nodo (`salt.bin`), generato alla prima apertura.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::warn;
+
+use super::rules::PolicyVersion;
+use super::{PolicyDecision, Stage, Verdict};
+
+/// Sottodirectory di `data_dir` che contiene il log.
+pub const AUDIT_DIR: &str = "policy/audit";
+
+const LOG_FILE: &str = "audit.log";
+const HEAD_FILE: &str = "head.json";
+const SALT_FILE: &str = "salt.bin";
+
+/// Hash "precedente" della prima voce.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Contenuto di una voce del log, coperto dall'hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Numero progressivo della voce (da 1).
+    pub seq: u64,
+    /// Istante della decisione (millisecondi da Unix epoch).
+    pub timestamp_ms: u64,
+    /// Punto della pipeline in cui è stato valutato il testo.
+    pub stage: Stage,
+    /// Digest salato (hex) del testo valutato.
+    pub input_digest: String,
+    /// Id delle regole corrispondenti.
+    pub matched_rules: Vec<String>,
+    /// Esito della valutazione.
+    pub verdict: Verdict,
+    /// Versione del file di policy in uso.
+    pub policy_version: String,
+    /// Hash delle regole in uso.
+    pub policy_hash: String,
+    /// Modalità strict attiva al momento della decisione.
+    pub strict_mode: bool,
+    /// Hash della voce precedente.
+    pub prev_hash: String,
+}
+
+/// Voce del log: corpo più hash di catena.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Contenuto della voce.
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    /// `sha256(prev_hash || json(record))`, in hex.
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn seal(record: AuditRecord) -> Result<Self> {
+        let hash = chain_hash(&record)?;
+        Ok(Self { record, hash })
+    }
+}
+
+/// Ultima voce scritta, salvata in `head.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Head {
+    seq: u64,
+    hash: String,
+}
+
+/// Problema rilevato da [`AuditLog::verify`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AuditViolation {
+    /// Una riga non è una voce valida (es. scrittura troncata).
+    #[error("line {line} is not a valid audit entry")]
+    Malformed {
+        /// Riga (da 1).
+        line: usize,
+    },
+    /// Numerazione non consecutiva (voci rimosse o riordinate).
+    #[error("expected entry {expected}, found {found}")]
+    SequenceGap {
+        /// Numero atteso.
+        expected: u64,
+        /// Numero trovato.
+        found: u64,
+    },
+    /// `prev_hash` non corrisponde alla voce precedente.
+    #[error("entry {seq} does not chain to the previous entry")]
+    BrokenChain {
+        /// Voce interessata.
+        seq: u64,
+    },
+    /// Il contenuto della voce non corrisponde al suo hash.
+    #[error("entry {seq} was modified")]
+    Modified {
+        /// Voce interessata.
+        seq: u64,
+    },
+    /// Il log termina prima dell'ultima voce registrata in `head.json`.
+    #[error("log truncated: head is entry {expected}, log ends at {found}")]
+    Truncated {
+        /// Ultima voce secondo `head.json`.
+        expected: u64,
+        /// Ultima voce presente nel log.
+        found: u64,
+    },
+    /// L'ultima voce non coincide con quella registrata in `head.json`.
+    #[error("last entry {seq} does not match the recorded head")]
+    HeadMismatch {
+        /// Ultima voce presente nel log.
+        seq: u64,
+    },
+}
+
+/// Esito di [`AuditLog::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Voci verificate con successo (prima dell'eventuale violazione).
+    pub entries: u64,
+    /// Righe troncate da un crash (da 1) dopo le quali la catena riprende
+    /// dall'ultima voce valida; non sono violazioni.
+    pub torn_lines: Vec<usize>,
+    /// Prima violazione trovata, se presente.
+    pub violation: Option<AuditViolation>,
+}
+
+impl AuditReport {
+    /// Restituisce `true` se il log è integro.
+    #[must_use]
+    pub const fn is_intact(&self) -> bool {
+        self.violation.is_none()
+    }
+}
+
+/// Log di audit aperto in append.
+#[derive(Debug)]
+pub struct AuditLog {
+    dir: PathBuf,
+    file: File,
+    salt: Vec<u8>,
+    head: Head,
+    degraded: Option<AuditViolation>,
+}
+
+impl AuditLog {
+    /// Apre (o crea) il log in `dir`, riprendendo la catena dall'ultima voce.
+    ///
+    /// Se l'ultima riga è troncata (crash durante un append) il log viene
+    /// aperto in modalità degradata: la violazione resta disponibile in
+    /// [`Self::degraded`] e la prossima voce si aggancia all'ultima voce
+    /// valida, iniziando un nuovo segmento della catena.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory, il salt o il log non sono accessibili.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Unable to create audit dir {}", dir.display()))?;
+        let salt = load_or_create_salt(&dir.join(SALT_FILE))?;
+
+        let log_path = dir.join(LOG_FILE);
+        let raw = match fs::read_to_string(&log_path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read audit log {}", log_path.display()))
+            }
+        };
+        let (head, degraded) = resume(&raw);
+        if let Some(violation) = &degraded {
+            warn!(
+                "Audit log {}: {violation}; resuming the chain from entry {}",
+                log_path.display(),
+                head.seq
+            );
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .with_context(|| format!("Unable to open audit log {}", log_path.display()))?;
+        // Una riga interrotta viene chiusa: la prossima voce parte a capo.
+        if !raw.is_empty() && !raw.ends_with('\n') {
+            file.write_all(b"\n")
+                .and_then(|()| file.sync_data())
+                .with_context(|| format!("Unable to repair audit log {}", log_path.display()))?;
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            salt,
+            head,
+            degraded,
+        })
+    }
+
+    /// Violazione trovata all'apertura (riga finale troncata), se il log è
+    /// stato aperto in modalità degradata.
+    #[must_use]
+    pub const fn degraded(&self) -> Option<&AuditViolation> {
+        self.degraded.as_ref()
+    }
+
+    /// Digest salato (hex) di `text`.
+    #[must_use]
+    pub fn digest(&self, text: &str) -> String {
+        hex::encode(
+            Sha256::new()
+                .chain_update(&self.salt)
+                .chain_update(text.as_bytes())
+                .finalize(),
+        )
+    }
+
+    /// Registra una decisione sul testo `text`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la voce non può essere scritta.
+    pub fn append(
+        &mut self,
+        stage: Stage,
+        text: &str,
+        decision: &PolicyDecision,
+        policy: &PolicyVersion,
+        strict_mode: bool,
+    ) -> Result<AuditEntry> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+            });
+        let entry = AuditEntry::seal(AuditRecord {
+            seq: self.head.seq + 1,
+            timestamp_ms,
+            stage,
+            input_digest: self.digest(text),
+            matched_rules: decision.matched_rules.clone(),
+            verdict: decision.verdict,
+            policy_version: policy.version.clone(),
+            policy_hash: policy.hash.clone(),
+            strict_mode,
+            prev_hash: self.head.hash.clone(),
+        })?;
+
+        let mut line = serde_json::to_vec(&entry).context("Unable to encode audit entry")?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .and_then(|()| self.file.sync_data())
+            .context("Unable to append audit entry")?;
+
+        self.head = Head {
+            seq: entry.record.seq,
+            hash: entry.hash.clone(),
+        };
+        write_head(&self.dir, &self.head)?;
+        Ok(entry)
+    }
+
+    /// Numero di voci scritte finora.
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.head.seq
+    }
+
+    /// Restituisce `true` se il log non contiene voci.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.head.seq == 0
+    }
+
+    /// Verifica l'integrità del log in `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore solo se i file non sono leggibili; le manomissioni
+    /// sono riportate in [`AuditReport::violation`].
+    pub fn verify(dir: &Path) -> Result<AuditReport> {
+        let log_path = dir.join(LOG_FILE);
+        let raw = match fs::read_to_string(&log_path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read audit log {}", log_path.display()))
+            }
+        };
+        let head = match fs::read(dir.join(HEAD_FILE)) {
+            Ok(bytes) => {
+                Some(serde_json::from_slice::<Head>(&bytes).context("Invalid audit head")?)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err).context("Unable to read audit head"),
+        };
+
+        let mut last = genesis();
+        let mut torn_lines = Vec::new();
+        // Righe non valide accettate solo se la voce successiva riprende la
+        // catena da `last`.
+        let mut pending = Vec::new();
+        let report = |entries, torn_lines, violation| {
+            Ok(AuditReport {
+                entries,
+                torn_lines,
+                violation: Some(violation),
+            })
+        };
+        for (index, line) in raw.lines().enumerate() {
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+                pending.push(index + 1);
+                continue;
+            };
+            let seq = entry.record.seq;
+            if seq != last.seq + 1 {
+                return report(
+                    last.seq,
+                    torn_lines,
+                    AuditViolation::SequenceGap {
+                        expected: last.seq + 1,
+                        found: seq,
+                    },
+                );
+            }
+            if entry.record.prev_hash != last.hash {
+                return report(last.seq, torn_lines, AuditViolation::BrokenChain { seq });
+            }
+            if chain_hash(&entry.record)? != entry.hash {
+                return report(last.seq, torn_lines, AuditViolation::Modified { seq });
+            }
+            torn_lines.append(&mut pending);
+            last = Head {
+                seq,
+                hash: entry.hash,
+            };
+        }
+
+        if let Some(&line) = pending.first() {
+            return report(last.seq, torn_lines, AuditViolation::Malformed { line });
+        }
+        if let Some(head) = head {
+            if last.seq < head.seq {
+                return report(
+                    last.seq,
+                    torn_lines,
+                    AuditViolation::Truncated {
+                        expected: head.seq,
+                        found: last.seq,
+                    },
+                );
+            }
+            if last != head {
+                return report(
+                    last.seq,
+                    torn_lines,
+                    AuditViolation::HeadMismatch { seq: last.seq },
+                );
+            }
+        }
+
+        Ok(AuditReport {
+            entries: last.seq,
+            torn_lines,
+            violation: None,
+        })
+    }
+}
+
+/// Ultima voce valida di `raw` da cui riprendere la catena, con la
+/// violazione trovata se l'ultima riga non è una voce valida.
+fn resume(raw: &str) -> (Head, Option<AuditViolation>) {
+    let mut head = genesis();
+    let mut torn = None;
+    for (index, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<AuditEntry>(line) {
+            Ok(entry) => {
+                head = Head {
+                    seq: entry.record.seq,
+                    hash: entry.hash,
+                };
+                torn = None;
+            }
+            Err(_) => torn = Some(AuditViolation::Malformed { line: index + 1 }),
+        }
+    }
+    (head, torn)
+}
+
+fn genesis() -> Head {
+    Head {
+        seq: 0,
+        hash: GENESIS_HASH.to_owned(),
+    }
+}
+
+fn chain_hash(record: &AuditRecord) -> Result<String> {
+    let body = serde_json::to_vec(record).context("Unable to encode audit record")?;
+    Ok(hex::encode(
+        Sha256::new()
+            .chain_update(record.prev_hash.as_bytes())
+            .chain_update(&body)
+            .finalize(),
+    ))
+}
+
+fn load_or_create_salt(path: &Path) -> Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(salt) if !salt.is_empty() => Ok(salt),
+        Ok(_) => anyhow::bail!("Empty audit salt {}", path.display()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let salt = [
+                *uuid::Uuid::new_v4().as_bytes(),
+                *uuid::Uuid::new_v4().as_bytes(),
+            ]
+            .concat();
+            fs::write(path, &salt)
+                .with_context(|| format!("Unable to write audit salt {}", path.display()))?;
+            Ok(salt)
+        }
+        Err(err) => {
+            Err(err).with_context(|| format!("Unable to read audit salt {}", path.display()))
+        }
+    }
+}
+
+/// Scrive `head.json` in modo atomico (file temporaneo + rename).
+fn write_head(dir: &Path, head: &Head) -> Result<()> {
+    let tmp = dir.join(format!("{HEAD_FILE}.tmp"));
+    fs::write(
+        &tmp,
+        serde_json::to_vec(head).context("Unable to encode audit head")?,
+    )
+    .context("Unable to write audit head")?;
+    fs::rename(&tmp, dir.join(HEAD_FILE)).context("Unable to update audit head")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("samaritan-audit-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn fill(dir: &Path, n: usize) {
+        let mut log = AuditLog::open(dir).unwrap();
+        for i in 0..n {
+            log.append(
+                Stage::Input,
+                &format!("secret prompt {i}"),
+                &PolicyDecision::allow("ok"),
+                &PolicyVersion {
+                    version: "v1".to_owned(),
+                    hash: "0123456789abcdef".to_owned(),
+                },
+                false,
+            )
+            .unwrap();
+        }
+    }
+
+    fn rewrite_lines(dir: &Path, edit: impl FnOnce(&mut Vec<String>)) {
+        let path = dir.join(LOG_FILE);
+        let mut lines: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        edit(&mut lines);
+        let mut raw = lines.join("\n");
+        raw.push('\n');
+        fs::write(path, raw).unwrap();
+    }
+
+    #[test]
+    fn chain_survives_reopen_and_never_stores_raw_text() {
+        let dir = temp_dir();
+        fill(&dir, 2);
+        fill(&dir, 1);
+
+        let report = AuditLog::verify(&dir).unwrap();
+        assert!(report.is_intact(), "{report:?}");
+        assert_eq!(report.entries, 3);
+
+        let raw = fs::read_to_string(dir.join(LOG_FILE)).unwrap();
+        assert!(!raw.contains("secret"));
+        let log = AuditLog::open(&dir).unwrap();
+        assert_eq!(log.len(), 3);
+        assert!(raw.contains(&log.digest("secret prompt 0")));
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn modified_entries_are_detected() {
+        let dir = temp_dir();
+        fill(&dir, 3);
+        rewrite_lines(&dir, |lines| {
+            lines[1] = lines[1].replace("\"allow\"", "\"refuse\"");
+        });
+
+        let report = AuditLog::verify(&dir).unwrap();
+        assert_eq!(report.violation, Some(AuditViolation::Modified { seq: 2 }));
+        assert_eq!(report.entries, 1);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn removed_and_truncated_entries_are_detected() {
+        let dir = temp_dir();
+        fill(&dir, 3);
+
+        rewrite_lines(&dir, |lines| {
+            lines.remove(1);
+        });
+        assert_eq!(
+            AuditLog::verify(&dir).unwrap().violation,
+            Some(AuditViolation::SequenceGap {
+                expected: 2,
+                found: 3
+            })
+        );
+
+        fs::remove_dir_all(&dir).ok();
+        fill(&dir, 3);
+        rewrite_lines(&dir, |lines| {
+            lines.pop();
+        });
+        assert_eq!(
+            AuditLog::verify(&dir).unwrap().violation,
+            Some(AuditViolation::Truncated {
+                expected: 3,
+                found: 2
+            })
+        );
+
+        // Scrittura interrotta a metà riga.
+        let path = dir.join(LOG_FILE);
+        let raw = fs::read_to_string(&path).unwrap();
+        fs::write(&path, &raw[..raw.len() - 10]).unwrap();
+        assert_eq!(
+            AuditLog::verify(&dir).unwrap().violation,
+            Some(AuditViolation::Malformed { line: 2 })
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn torn_last_entry_starts_a_new_segment() {
+        let dir = temp_dir();
+        fill(&dir, 3);
+        // Crash durante l'append della terza voce (head.json non aggiornato).
+        let path = dir.join(LOG_FILE);
+        let raw = fs::read_to_string(&path).unwrap();
+        fs::write(&path, &raw[..raw.len() - 10]).unwrap();
+        let second: AuditEntry = serde_json::from_str(raw.lines().nth(1).unwrap()).unwrap();
+        write_head(
+            &dir,
+            &Head {
+                seq: 2,
+                hash: second.hash,
+            },
+        )
+        .unwrap();
+
+        let log = AuditLog::open(&dir).unwrap();
+        assert_eq!(log.degraded(), Some(&AuditViolation::Malformed { line: 3 }));
+        assert_eq!(log.len(), 2);
+        drop(log);
+        fill(&dir, 2);
+
+        let report = AuditLog::verify(&dir).unwrap();
+        assert!(report.is_intact(), "{report:?}");
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.torn_lines, vec![3]);
+        assert!(AuditLog::open(&dir).unwrap().degraded().is_none());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
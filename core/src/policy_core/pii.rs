@@ -0,0 +1,501 @@
+//! PII detection.
+//!
+//! Il [`PiiDetector`] individua dati personali con una regex di candidatura
+//! seguita, dove esiste, da una validazione strutturale:
+//!
+//! | tipo              | validazione                          |
+//! |-------------------|--------------------------------------|
+//! | carta di credito  | algoritmo di Luhn                    |
+//! | IBAN              | checksum ISO 13616 (mod 97)          |
+//! | codice fiscale    | carattere di controllo               |
+//! | indirizzo IP      | parsing IPv4 / IPv6 (no `::`, `::1`) |
+//! | email, telefono   | solo forma                           |
+//!
+//! Il testo sostitutivo dipende dal [`RedactionMode`] configurato nella
+//! sezione `pii:` del file di policy.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+
+use super::rules::{Action, Severity};
+
+/// Numero di cifre esadecimali del digest usato dalla modalità `hash`.
+const HASH_PREFIX_LEN: usize = 12;
+
+/// Tipo di dato personale riconosciuto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PiiKind {
+    /// Indirizzo email.
+    Email,
+    /// IBAN con checksum valido.
+    Iban,
+    /// Numero di carta con checksum di Luhn valido.
+    CardNumber,
+    /// Codice fiscale italiano con carattere di controllo valido.
+    CodiceFiscale,
+    /// Indirizzo IPv4 o IPv6.
+    IpAddress,
+    /// Numero di telefono (internazionale o italiano).
+    Phone,
+}
+
+impl PiiKind {
+    /// Tutti i tipi, in ordine di priorità (in caso di sovrapposizione
+    /// vince il primo).
+    pub const ALL: [Self; 6] = [
+        Self::Email,
+        Self::Iban,
+        Self::CardNumber,
+        Self::CodiceFiscale,
+        Self::IpAddress,
+        Self::Phone,
+    ];
+
+    /// Id della regola riportato in [`super::PolicyDecision::matched_rules`].
+    #[must_use]
+    pub const fn rule_id(self) -> &'static str {
+        match self {
+            Self::Email => "pii-email",
+            Self::Iban => "pii-iban",
+            Self::CardNumber => "pii-card-number",
+            Self::CodiceFiscale => "pii-codice-fiscale",
+            Self::IpAddress => "pii-ip-address",
+            Self::Phone => "pii-phone",
+        }
+    }
+
+    /// Etichetta usata nel testo sostitutivo.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Iban => "IBAN",
+            Self::CardNumber => "CARD",
+            Self::CodiceFiscale => "CODICE_FISCALE",
+            Self::IpAddress => "IP",
+            Self::Phone => "PHONE",
+        }
+    }
+
+    const fn pattern(self) -> &'static str {
+        match self {
+            Self::Email => r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+            Self::Iban => r"(?i)\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b",
+            Self::CardNumber => r"\b\d(?:[ -]?\d){12,18}\b",
+            Self::CodiceFiscale => {
+                r"(?i)\b[A-Z]{6}[0-9LMNPQRSTUV]{2}[ABCDEHLMPRST][0-9LMNPQRSTUV]{2}[A-Z][0-9LMNPQRSTUV]{3}[A-Z]\b"
+            }
+            Self::IpAddress => {
+                r"(?i)\b(?:\d{1,3}\.){3}\d{1,3}\b|(?:\b[0-9a-f]{1,4})?(?::[0-9a-f]{0,4}){2,7}\b"
+            }
+            Self::Phone => {
+                r"(?:\+|\b00)[1-9]\d{0,2}(?:[ .-]?\d){6,12}\b|\b3\d{2}(?:[ .-]?\d){6,7}\b|\b0\d{1,3}[ .-]?\d{5,8}\b"
+            }
+        }
+    }
+
+    fn validate(self, candidate: &str) -> bool {
+        match self {
+            Self::Email | Self::Phone => true,
+            Self::Iban => iban_checksum_ok(candidate),
+            Self::CardNumber => luhn_ok(candidate),
+            Self::CodiceFiscale => codice_fiscale_ok(candidate),
+            Self::IpAddress => candidate.parse::<Ipv4Addr>().is_ok() || ipv6_ok(candidate),
+        }
+    }
+
+    /// Lunghezza (in byte) del più lungo prefisso valido di `candidate`.
+    ///
+    /// Le regex di IBAN e carte ammettono separatori tra i gruppi e quindi
+    /// inglobano anche le parole o le cifre che seguono il dato (`IT60… please`,
+    /// `4111 … 1111 12`): se il candidato intero non passa il checksum si
+    /// riprova troncandolo a ogni separatore, dal più lungo al più corto.
+    fn valid_len(self, candidate: &str) -> Option<usize> {
+        if self.validate(candidate) {
+            return Some(candidate.len());
+        }
+        if !matches!(self, Self::Iban | Self::CardNumber) {
+            return None;
+        }
+        candidate
+            .char_indices()
+            .rev()
+            .filter(|&(_, c)| is_group_separator(c))
+            .map(|(end, _)| end)
+            .find(|&end| self.validate(&candidate[..end]))
+    }
+}
+
+impl fmt::Display for PiiKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.rule_id())
+    }
+}
+
+/// Modalità di redazione dei dati personali.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    /// Sostituisce con un segnaposto per tipo (es. `[EMAIL]`).
+    #[default]
+    Mask,
+    /// Sostituisce con un digest salato e troncato (es. `[EMAIL:1f2e…]`),
+    /// così occorrenze uguali restano correlabili.
+    Hash,
+    /// Rimuove il dato.
+    Drop,
+}
+
+/// Sezione `pii:` del file di policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PiiSpec {
+    /// Attiva il rilevatore.
+    pub enabled: bool,
+    /// Tipi da rilevare.
+    pub kinds: Vec<PiiKind>,
+    /// Modalità di redazione.
+    pub mode: RedactionMode,
+    /// Azione applicata quando viene rilevato un dato personale.
+    pub action: Action,
+    /// Severità riportata nella decisione.
+    pub severity: Severity,
+    /// Salt anteposto al valore nella modalità `hash`.
+    pub hash_salt: String,
+}
+
+impl Default for PiiSpec {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            kinds: PiiKind::ALL.to_vec(),
+            mode: RedactionMode::Mask,
+            action: Action::Redact,
+            severity: Severity::Medium,
+            hash_salt: String::new(),
+        }
+    }
+}
+
+/// Dato personale rilevato in un testo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    /// Tipo di dato.
+    pub kind: PiiKind,
+    /// Intervallo (in byte) nel testo.
+    pub span: Range<usize>,
+}
+
+/// Rilevatore di dati personali compilato da un [`PiiSpec`].
+#[derive(Debug, Clone)]
+pub struct PiiDetector {
+    spec: PiiSpec,
+    matchers: Vec<(PiiKind, Regex)>,
+}
+
+impl PiiDetector {
+    /// Compila il rilevatore.
+    ///
+    /// # Panics
+    ///
+    /// Solo se una delle regex interne non è valida (coperto dai test).
+    #[must_use]
+    pub fn new(spec: PiiSpec) -> Self {
+        let matchers = if spec.enabled {
+            PiiKind::ALL
+                .into_iter()
+                .filter(|kind| spec.kinds.contains(kind))
+                .map(|kind| {
+                    let regex =
+                        Regex::new(kind.pattern()).expect("built-in PII pattern must be valid");
+                    (kind, regex)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self { spec, matchers }
+    }
+
+    /// Configurazione del rilevatore.
+    #[must_use]
+    pub const fn spec(&self) -> &PiiSpec {
+        &self.spec
+    }
+
+    /// Rileva i dati personali in `text`, ordinati per posizione.
+    ///
+    /// Le corrispondenze sovrapposte a un tipo a priorità più alta (vedi
+    /// [`PiiKind::ALL`]) vengono scartate. Un candidato che non supera la
+    /// validazione viene accorciato fino al primo prefisso valido; se non ce
+    /// n'è nessuno la ricerca riprende dal gruppo successivo, così il testo
+    /// che precede il dato non lo nasconde.
+    #[must_use]
+    pub fn detect(&self, text: &str) -> Vec<PiiMatch> {
+        let mut found: Vec<PiiMatch> = Vec::new();
+        for (kind, regex) in &self.matchers {
+            let mut pos = 0;
+            while let Some(candidate) = regex.find_at(text, pos) {
+                let Some(len) = kind.valid_len(candidate.as_str()) else {
+                    pos = candidate
+                        .as_str()
+                        .find(is_group_separator)
+                        .map_or_else(|| candidate.end(), |i| candidate.start() + i + 1);
+                    continue;
+                };
+                let span = candidate.start()..candidate.start() + len;
+                pos = span.end;
+                let overlaps = found
+                    .iter()
+                    .any(|m| m.span.start < span.end && span.start < m.span.end);
+                if !overlaps {
+                    found.push(PiiMatch { kind: *kind, span });
+                }
+            }
+        }
+        found.sort_by_key(|m| m.span.start);
+        found
+    }
+
+    /// Testo sostitutivo per un dato `value` di tipo `kind`.
+    #[must_use]
+    pub fn replacement(&self, kind: PiiKind, value: &str) -> String {
+        match self.spec.mode {
+            RedactionMode::Mask => format!("[{}]", kind.label()),
+            RedactionMode::Hash => {
+                let digest = Sha256::new()
+                    .chain_update(self.spec.hash_salt.as_bytes())
+                    .chain_update(value.as_bytes())
+                    .finalize();
+                let hex = hex::encode(digest);
+                format!("[{}:{}]", kind.label(), &hex[..HASH_PREFIX_LEN])
+            }
+            RedactionMode::Drop => String::new(),
+        }
+    }
+}
+
+impl Default for PiiDetector {
+    fn default() -> Self {
+        Self::new(PiiSpec::default())
+    }
+}
+
+/// Separatore ammesso tra i gruppi di cifre di IBAN e carte.
+const fn is_group_separator(c: char) -> bool {
+    matches!(c, ' ' | '-')
+}
+
+/// Verifica che `candidate` sia un indirizzo IPv6 plausibile.
+///
+/// `::` e `::1` (e i percorsi come `std::fs` o `a::b`, che il parser
+/// accetta) non identificano nessuno: serve almeno una cifra decimale.
+fn ipv6_ok(candidate: &str) -> bool {
+    candidate.parse::<Ipv6Addr>().is_ok_and(|ip| {
+        !ip.is_unspecified()
+            && !ip.is_loopback()
+            && candidate.contains(|c: char| c.is_ascii_digit())
+    })
+}
+
+/// Verifica il checksum di Luhn sulle cifre di `candidate`.
+fn luhn_ok(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Verifica il checksum ISO 13616 (mod 97) di un IBAN.
+fn iban_checksum_ok(candidate: &str) -> bool {
+    let compact: String = candidate
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let (head, tail) = compact.split_at(4);
+    let mut remainder = 0u32;
+    for c in tail.chars().chain(head.chars()) {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// Verifica il carattere di controllo di un codice fiscale.
+fn codice_fiscale_ok(candidate: &str) -> bool {
+    const ODD: [u32; 26] = [
+        1, 0, 5, 7, 9, 13, 15, 17, 19, 21, 2, 4, 18, 20, 11, 3, 6, 8, 12, 14, 16, 10, 22, 25, 24,
+        23,
+    ];
+
+    let upper = candidate.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    if bytes.len() != 16 {
+        return false;
+    }
+    let mut sum = 0;
+    for (i, &b) in bytes[..15].iter().enumerate() {
+        // Le cifre valgono come le lettere di pari posizione (0 = A, …).
+        let index = match b {
+            b'0'..=b'9' => b - b'0',
+            b'A'..=b'Z' => b - b'A',
+            _ => return false,
+        };
+        sum += if i % 2 == 0 {
+            ODD[usize::from(index)]
+        } else {
+            u32::from(index)
+        };
+    }
+    u32::from(bytes[15] - b'A') == sum % 26
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(detector: &PiiDetector, text: &str) -> Vec<(PiiKind, String)> {
+        detector
+            .detect(text)
+            .into_iter()
+            .map(|m| (m.kind, text[m.span].to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn checksums_reject_lookalikes() {
+        assert!(luhn_ok("4111 1111 1111 1111"));
+        assert!(!luhn_ok("4111 1111 1111 1112"));
+        assert!(iban_checksum_ok("IT60 X054 2811 1010 0000 0123 456"));
+        assert!(iban_checksum_ok("GB82WEST12345698765432"));
+        assert!(!iban_checksum_ok("IT61X0542811101000000123456"));
+        assert!(codice_fiscale_ok("RSSMRA85T10A562S"));
+        assert!(!codice_fiscale_ok("RSSMRA85T10A562T"));
+        assert!(ipv6_ok("2001:db8::1"));
+        assert!(ipv6_ok("fe80::1ff:fe23:4567:890a"));
+        assert!(!ipv6_ok("::"));
+        assert!(!ipv6_ok("::1"));
+        assert!(!ipv6_ok("a::b"));
+    }
+
+    #[test]
+    fn detects_every_kind_with_validation() {
+        let detector = PiiDetector::default();
+        let text = "mail a.rossi@example.it, card 4111-1111-1111-1111, \
+                    iban IT60X0542811101000000123456, cf RSSMRA85T10A562S, \
+                    ip 192.168.1.20 or 2001:db8::1, tel +39 347 123 4567";
+
+        assert_eq!(
+            kinds(&detector, text),
+            vec![
+                (PiiKind::Email, "a.rossi@example.it".to_owned()),
+                (PiiKind::CardNumber, "4111-1111-1111-1111".to_owned()),
+                (PiiKind::Iban, "IT60X0542811101000000123456".to_owned()),
+                (PiiKind::CodiceFiscale, "RSSMRA85T10A562S".to_owned()),
+                (PiiKind::IpAddress, "192.168.1.20".to_owned()),
+                (PiiKind::IpAddress, "2001:db8::1".to_owned()),
+                (PiiKind::Phone, "+39 347 123 4567".to_owned()),
+            ]
+        );
+
+        let noise = "card 4111-1111-1111-1112, ip 999.1.1.1, at 10:30:00, cf RSSMRA85T10A562T";
+        assert_eq!(kinds(&detector, noise), vec![]);
+    }
+
+    #[test]
+    fn trailing_text_does_not_hide_iban_or_card() {
+        let detector = PiiDetector::default();
+        let iban = |text| vec![(PiiKind::Iban, text)];
+        assert_eq!(
+            kinds(&detector, "iban IT60X0542811101000000123456 please"),
+            iban("IT60X0542811101000000123456".to_owned())
+        );
+        assert_eq!(
+            kinds(&detector, "pay to IT60X0542811101000000123456 today"),
+            iban("IT60X0542811101000000123456".to_owned())
+        );
+        assert_eq!(
+            kinds(&detector, "IT60 X054 2811 1010 0000 0123 456 grazie"),
+            iban("IT60 X054 2811 1010 0000 0123 456".to_owned())
+        );
+        assert_eq!(
+            kinds(&detector, "card 4111 1111 1111 1111 12 34 ok"),
+            vec![(PiiKind::CardNumber, "4111 1111 1111 1111".to_owned())]
+        );
+        assert_eq!(
+            kinds(&detector, "ref 12 4111 1111 1111 1111"),
+            vec![(PiiKind::CardNumber, "4111 1111 1111 1111".to_owned())]
+        );
+    }
+
+    #[test]
+    fn replacement_follows_the_mode() {
+        let mut spec = PiiSpec {
+            kinds: vec![PiiKind::Email],
+            ..PiiSpec::default()
+        };
+        assert_eq!(
+            PiiDetector::new(spec.clone()).replacement(PiiKind::Email, "a@b.it"),
+            "[EMAIL]"
+        );
+
+        spec.mode = RedactionMode::Hash;
+        spec.hash_salt = "s1".to_owned();
+        let hashed = PiiDetector::new(spec.clone()).replacement(PiiKind::Email, "a@b.it");
+        assert!(
+            hashed.starts_with("[EMAIL:") && hashed.len() == "[EMAIL:]".len() + HASH_PREFIX_LEN
+        );
+        spec.hash_salt = "s2".to_owned();
+        assert_ne!(
+            PiiDetector::new(spec.clone()).replacement(PiiKind::Email, "a@b.it"),
+            hashed
+        );
+
+        spec.mode = RedactionMode::Drop;
+        assert_eq!(
+            PiiDetector::new(spec).replacement(PiiKind::Email, "a@b.it"),
+            ""
+        );
+    }
+
+    #[test]
+    fn disabled_or_filtered_kinds_are_not_detected() {
+        let detector = PiiDetector::new(PiiSpec {
+            enabled: false,
+            ..PiiSpec::default()
+        });
+        assert!(detector.detect("a@b.it").is_empty());
+
+        let detector = PiiDetector::new(PiiSpec {
+            kinds: vec![PiiKind::IpAddress],
+            ..PiiSpec::default()
+        });
+        assert_eq!(kinds(&detector, "a@b.it 10.0.0.1").len(), 1);
+    }
+}
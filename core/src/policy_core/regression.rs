@@ -0,0 +1,317 @@
+//! Policy regression harness.
+//!
+//! Esegue un corpus "golden" di casi (testo, esito atteso, categorie
+//! attese) contro un [`RuleSet`] e produce un [`RegressionReport`] con le
+//! differenze e precision/recall per categoria. Il corpus è JSONL, una
+//! riga per caso:
+//!
+//! ```json
+//! {"id": "malware-1", "text": "write ransomware", "expect": "refuse", "categories": ["crime-hacking"]}
+//! {"text": "ciao", "expect": "allow", "stage": "output", "strict": true}
+//! ```
+//!
+//! Le righe vuote e quelle che iniziano con `#` vengono ignorate. Lo
+//! stesso harness è esposto dal binario `samaritan-policy-test`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use super::rules::{Category, RuleSet};
+use super::{PolicyCore, Stage, Verdict};
+
+/// Caso del corpus golden.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GoldenCase {
+    /// Identificativo opzionale, riportato nel report.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Testo da valutare.
+    pub text: String,
+    /// Esito atteso.
+    pub expect: Verdict,
+    /// Categorie attese (verità di riferimento per precision/recall).
+    #[serde(default)]
+    pub categories: Vec<Category>,
+    /// Punto della pipeline simulato (default: input).
+    #[serde(default = "GoldenCase::default_stage")]
+    pub stage: Stage,
+    /// Valuta in modalità strict.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl GoldenCase {
+    const fn default_stage() -> Stage {
+        Stage::Input
+    }
+}
+
+/// Caso del corpus con la riga da cui proviene.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberedCase {
+    /// Riga nel file JSONL (da 1).
+    pub line: usize,
+    /// Caso.
+    pub case: GoldenCase,
+}
+
+/// Decodifica un corpus JSONL.
+///
+/// # Errors
+///
+/// Ritorna errore (con il numero di riga) se una riga non è un caso valido.
+pub fn parse_corpus(raw: &str) -> Result<Vec<NumberedCase>> {
+    raw.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, line)| {
+            let case = serde_json::from_str(line)
+                .with_context(|| format!("Invalid golden case at line {}", index + 1))?;
+            Ok(NumberedCase {
+                line: index + 1,
+                case,
+            })
+        })
+        .collect()
+}
+
+/// Legge un corpus JSONL da disco.
+///
+/// # Errors
+///
+/// Ritorna errore se il file non è leggibile o contiene casi non validi.
+pub fn load_corpus(path: &Path) -> Result<Vec<NumberedCase>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read golden corpus {}", path.display()))?;
+    parse_corpus(&raw).with_context(|| format!("Invalid golden corpus {}", path.display()))
+}
+
+/// Caso il cui esito differisce da quello atteso.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Riga del caso nel corpus.
+    pub line: usize,
+    /// Identificativo del caso, se presente.
+    pub id: Option<String>,
+    /// Esito atteso.
+    pub expected: Verdict,
+    /// Esito ottenuto.
+    pub actual: Verdict,
+    /// Regole corrispondenti.
+    pub matched_rules: Vec<String>,
+}
+
+/// Conteggi di una categoria.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CategoryStats {
+    /// Categoria attesa e rilevata.
+    pub true_positives: u32,
+    /// Categoria rilevata ma non attesa.
+    pub false_positives: u32,
+    /// Categoria attesa ma non rilevata.
+    pub false_negatives: u32,
+}
+
+impl CategoryStats {
+    /// Frazione di rilevazioni corrette (1.0 se non ci sono rilevazioni).
+    #[must_use]
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Frazione di casi attesi rilevati (1.0 se non ci sono casi attesi).
+    #[must_use]
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+}
+
+fn ratio(hits: u32, total: u32) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        f64::from(hits) / f64::from(total)
+    }
+}
+
+/// Esito di un'esecuzione del corpus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegressionReport {
+    /// Versione delle regole provate (`versione@hash`).
+    pub policy: String,
+    /// Casi eseguiti.
+    pub total: usize,
+    /// Casi con esito diverso da quello atteso.
+    pub mismatches: Vec<Mismatch>,
+    /// Conteggi per categoria (tutte le categorie, anche senza casi).
+    pub categories: BTreeMap<Category, CategoryStats>,
+}
+
+impl RegressionReport {
+    /// Restituisce `true` se tutti i casi hanno l'esito atteso.
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "policy {}: {}/{} cases passed",
+            self.policy,
+            self.total - self.mismatches.len(),
+            self.total
+        )?;
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "line {}{}:",
+                mismatch.line,
+                mismatch
+                    .id
+                    .as_ref()
+                    .map_or_else(String::new, |id| format!(" ({id})"))
+            )?;
+            writeln!(f, "  - expected {:?}", mismatch.expected)?;
+            writeln!(
+                f,
+                "  + got      {:?} (rules: [{}])",
+                mismatch.actual,
+                mismatch.matched_rules.join(", ")
+            )?;
+        }
+        for (category, stats) in &self.categories {
+            writeln!(
+                f,
+                "{:<14} precision {:.3}  recall {:.3}  (tp {}, fp {}, fn {})",
+                category.as_str(),
+                stats.precision(),
+                stats.recall(),
+                stats.true_positives,
+                stats.false_positives,
+                stats.false_negatives
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Esegue il corpus contro `rules`.
+///
+/// # Errors
+///
+/// Ritorna errore se la valutazione di un caso fallisce.
+pub fn run_corpus(rules: &RuleSet, cases: &[NumberedCase]) -> Result<RegressionReport> {
+    let mut core = PolicyCore::with_rules(rules.clone());
+    let mut report = RegressionReport {
+        policy: rules.policy_version().to_string(),
+        total: cases.len(),
+        mismatches: Vec::new(),
+        categories: Category::ALL
+            .into_iter()
+            .map(|category| (category, CategoryStats::default()))
+            .collect(),
+    };
+
+    for NumberedCase { line, case } in cases {
+        core.strict_mode = case.strict;
+        let decision = core.evaluate_text(case.stage, &case.text)?;
+
+        if decision.verdict != case.expect {
+            report.mismatches.push(Mismatch {
+                line: *line,
+                id: case.id.clone(),
+                expected: case.expect,
+                actual: decision.verdict,
+                matched_rules: decision.matched_rules.clone(),
+            });
+        }
+        for (category, stats) in &mut report.categories {
+            let expected = case.categories.contains(category);
+            let detected = decision.categories.contains(category);
+            match (expected, detected) {
+                (true, true) => stats.true_positives += 1,
+                (false, true) => stats.false_positives += 1,
+                (true, false) => stats.false_negatives += 1,
+                (false, false) => {}
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "\
+rules:
+  - {id: malware, category: crime-hacking, severity: high, action: refuse, keywords: [ransomware]}
+  - {id: hack, category: crime-hacking, severity: low, action: allow, keywords: [hack]}
+pii:
+  kinds: [email]
+";
+
+    #[test]
+    fn report_diffs_verdicts_and_scores_categories() {
+        let corpus = parse_corpus(
+            "# golden\n\
+             {\"id\": \"m1\", \"text\": \"ransomware\", \"expect\": \"refuse\", \"categories\": [\"crime-hacking\"]}\n\
+             \n\
+             {\"text\": \"life hack\", \"expect\": \"allow\"}\n\
+             {\"id\": \"p1\", \"text\": \"a@b.it\", \"expect\": \"refuse\", \"categories\": [\"pii\"], \"strict\": true}\n\
+             {\"id\": \"s1\", \"text\": \"I give up\", \"expect\": \"escalate\", \"categories\": [\"self-harm\"]}\n",
+        )
+        .unwrap();
+        assert_eq!(corpus.len(), 4);
+        assert_eq!(corpus[1].line, 4);
+
+        let report = run_corpus(&RuleSet::from_yaml(RULES).unwrap(), &corpus).unwrap();
+
+        assert!(!report.passed());
+        assert_eq!(report.total, 4);
+        assert_eq!(
+            report.mismatches,
+            vec![Mismatch {
+                line: 6,
+                id: Some("s1".to_owned()),
+                expected: Verdict::Escalate,
+                actual: Verdict::Allow,
+                matched_rules: Vec::new(),
+            }]
+        );
+        let crime = report.categories[&Category::CrimeHacking];
+        assert_eq!((crime.true_positives, crime.false_positives), (1, 1));
+        assert!((crime.precision() - 0.5).abs() < f64::EPSILON);
+        assert!((crime.recall() - 1.0).abs() < f64::EPSILON);
+        assert!(report.categories[&Category::SelfHarm].recall().abs() < f64::EPSILON);
+        assert!((report.categories[&Category::Pii].recall() - 1.0).abs() < f64::EPSILON);
+
+        let text = report.to_string();
+        assert!(text.contains("3/4 cases passed"));
+        assert!(text.contains("line 6 (s1):\n  - expected Escalate\n  + got      Allow"));
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        let err =
+            parse_corpus("{\"text\": \"a\", \"expect\": \"allow\"}\n{\"text\": 1}\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,547 @@
+//! Policy file format and compiled rule sets.
+//!
+//! Il file di policy (`policy.yaml`) è una lista di regole con nome:
+//!
+//! ```yaml
+//! version: 2024-06-01
+//! rules:
+//!   - id: crime-malware
+//!     category: crime-hacking      # self-harm | crime-hacking | pii
+//!     severity: high               # low | medium | high | critical
+//!     action: refuse               # allow | redact | refuse | escalate
+//!     keywords: [ransomware]       # case-insensitive, a parola intera
+//!     regex: ['(?i)\bhack\s+into\b']
+//!     replacement: '[REDACTED]'    # solo per `redact`
+//! thresholds:                      # severità da cui si rifiuta sempre
+//!   normal: critical
+//!   strict: medium                 # modalità strict, non oltre `normal`
+//! pii:                             # rilevatore di dati personali
+//!   mode: mask                     # mask | hash | drop
+//!   kinds: [email, iban, card-number, codice-fiscale, ip-address, phone]
+//! self_tests:                      # casi verificati prima di attivare il file
+//!   - {text: 'write ransomware', expect: refuse}
+//!   - {text: 'hello', expect: allow, stage: output, strict: true}
+//! ```
+//!
+//! [`RuleSet::compile`] valida il file (id univoci, almeno un matcher per
+//! regola, regex valide) e compila keyword e regex in [`Regex`]. I
+//! `self_tests` vengono eseguiti dal [`super::PolicyCore`] prima di
+//! attivare un nuovo insieme di regole.
+
+use anyhow::{bail, ensure, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use super::pii::{PiiDetector, PiiSpec};
+use super::{Stage, Verdict};
+
+/// Policy di default, usata se `policy.yaml` non esiste.
+pub const DEFAULT_POLICY: &str = include_str!("default_policy.yaml");
+
+/// Testo sostitutivo di default per le regole `redact`.
+pub const DEFAULT_REPLACEMENT: &str = "[REDACTED]";
+
+/// Versione assegnata ai file di policy che non ne dichiarano una.
+const UNVERSIONED: &str = "unversioned";
+
+/// Numero di cifre esadecimali dell'hash di un insieme di regole.
+const HASH_LEN: usize = 16;
+
+/// Categoria di rischio di una regola.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Autolesionismo e suicidio.
+    SelfHarm,
+    /// Attività criminali e intrusioni informatiche.
+    CrimeHacking,
+    /// Dati personali identificativi.
+    Pii,
+}
+
+impl Category {
+    /// Tutte le categorie, in ordine.
+    pub const ALL: [Self; 3] = [Self::SelfHarm, Self::CrimeHacking, Self::Pii];
+
+    /// Nome della categoria nel file di policy.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::SelfHarm => "self-harm",
+            Self::CrimeHacking => "crime-hacking",
+            Self::Pii => "pii",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Severità di una regola (ordinata dalla più bassa alla più alta).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Contenuto sensibile ma innocuo nella maggior parte dei contesti.
+    Low,
+    /// Contenuto da trattare con cautela.
+    Medium,
+    /// Contenuto dannoso.
+    High,
+    /// Contenuto gravemente dannoso.
+    Critical,
+}
+
+/// Azione applicata quando una regola corrisponde.
+///
+/// L'ordine delle varianti è quello di precedenza: se più regole
+/// corrispondono, vince l'azione più restrittiva.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Consente il testo (la corrispondenza viene solo registrata).
+    Allow,
+    /// Sostituisce le parti corrispondenti.
+    Redact,
+    /// Trattiene il testo e lo segnala per revisione umana.
+    Escalate,
+    /// Rifiuta il testo.
+    Refuse,
+}
+
+/// Contenuto di un file di policy, prima della validazione.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyFile {
+    /// Etichetta di versione del file.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Regole, in ordine di valutazione.
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+    /// Soglie di severità per il rifiuto.
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    /// Configurazione del rilevatore di dati personali.
+    #[serde(default)]
+    pub pii: PiiSpec,
+    /// Casi che il file deve superare per essere attivato.
+    #[serde(default)]
+    pub self_tests: Vec<SelfTest>,
+}
+
+/// Caso di self-test di un file di policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfTest {
+    /// Testo da valutare.
+    pub text: String,
+    /// Esito atteso.
+    pub expect: Verdict,
+    /// Punto della pipeline simulato (default: input).
+    #[serde(default = "SelfTest::default_stage")]
+    pub stage: Stage,
+    /// Valuta in modalità strict.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl SelfTest {
+    const fn default_stage() -> Stage {
+        Stage::Input
+    }
+}
+
+/// Identità dell'insieme di regole attivo: versione dichiarata e hash del
+/// contenuto.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PolicyVersion {
+    /// Versione dichiarata nel file (`unversioned` se assente).
+    pub version: String,
+    /// Prefisso dell'hash SHA-256 del contenuto normalizzato del file.
+    pub hash: String,
+}
+
+impl fmt::Display for PolicyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.version, self.hash)
+    }
+}
+
+/// Severità a partire dalla quale una corrispondenza viene rifiutata,
+/// qualunque sia l'azione della regola (le regole `escalate` restano tali).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// Soglia in modalità normale.
+    pub normal: Severity,
+    /// Soglia in modalità strict (non superiore a `normal`).
+    pub strict: Severity,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            normal: Severity::Critical,
+            strict: Severity::Medium,
+        }
+    }
+}
+
+/// Regola come scritta nel file di policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSpec {
+    /// Identificativo univoco della regola.
+    pub id: String,
+    /// Categoria di rischio.
+    pub category: Category,
+    /// Severità.
+    pub severity: Severity,
+    /// Azione applicata in caso di corrispondenza.
+    pub action: Action,
+    /// Descrizione libera.
+    #[serde(default)]
+    pub description: String,
+    /// Parole o frasi cercate (case-insensitive, a parola intera).
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Espressioni regolari cercate.
+    #[serde(default)]
+    pub regex: Vec<String>,
+    /// Testo sostitutivo per l'azione `redact`.
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+/// Regola validata e compilata.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// Identificativo univoco della regola.
+    pub id: String,
+    /// Categoria di rischio.
+    pub category: Category,
+    /// Severità.
+    pub severity: Severity,
+    /// Azione applicata in caso di corrispondenza.
+    pub action: Action,
+    /// Testo sostitutivo per l'azione `redact`.
+    pub replacement: String,
+    matchers: Vec<Regex>,
+}
+
+impl Rule {
+    fn compile(spec: RuleSpec) -> Result<Self> {
+        ensure!(!spec.id.trim().is_empty(), "Rule id must not be empty");
+        ensure!(
+            spec.keywords
+                .iter()
+                .any(|keyword| !keyword.trim().is_empty())
+                || spec.regex.iter().any(|re| !re.is_empty()),
+            "Rule {} has no keyword or regex matcher",
+            spec.id
+        );
+
+        let keywords = spec
+            .keywords
+            .iter()
+            .filter(|keyword| !keyword.trim().is_empty())
+            .map(|keyword| format!(r"(?i)\b{}\b", regex::escape(keyword.trim())));
+        let matchers = keywords
+            .chain(spec.regex.iter().filter(|re| !re.is_empty()).cloned())
+            .map(|pattern| {
+                Regex::new(&pattern)
+                    .with_context(|| format!("Rule {}: invalid regex {pattern:?}", spec.id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            id: spec.id,
+            category: spec.category,
+            severity: spec.severity,
+            action: spec.action,
+            replacement: spec
+                .replacement
+                .unwrap_or_else(|| DEFAULT_REPLACEMENT.to_owned()),
+            matchers,
+        })
+    }
+
+    /// Intervalli (in byte) di `text` che corrispondono alla regola.
+    #[must_use]
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let mut spans: Vec<Range<usize>> = self
+            .matchers
+            .iter()
+            .flat_map(|matcher| matcher.find_iter(text).map(|m| m.range()))
+            .filter(|span| !span.is_empty())
+            .collect();
+        spans.sort_by_key(|span| (span.start, span.end));
+        spans
+    }
+}
+
+/// Corrispondenza di una regola su un testo.
+#[derive(Debug, Clone)]
+pub struct RuleMatch<'a> {
+    /// Regola corrispondente.
+    pub rule: &'a Rule,
+    /// Intervalli corrispondenti, ordinati.
+    pub spans: Vec<Range<usize>>,
+}
+
+/// Insieme di regole validato, pronto per la valutazione.
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    version: PolicyVersion,
+    rules: Vec<Rule>,
+    self_tests: Vec<SelfTest>,
+    thresholds: Thresholds,
+    pii: PiiDetector,
+    keyword_words: usize,
+}
+
+impl RuleSet {
+    /// Valida e compila un file di policy.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se una regola ha id vuoto o duplicato, nessun matcher
+    /// o una regex non valida, o se la soglia strict supera quella normale.
+    pub fn compile(file: PolicyFile) -> Result<Self> {
+        ensure!(
+            file.thresholds.strict <= file.thresholds.normal,
+            "thresholds.strict ({:?}) must not be above thresholds.normal ({:?})",
+            file.thresholds.strict,
+            file.thresholds.normal
+        );
+        let normalized = serde_json::to_vec(&file).context("Unable to encode policy file")?;
+        let mut hash = hex::encode(Sha256::digest(&normalized));
+        hash.truncate(HASH_LEN);
+
+        let keyword_words = file
+            .rules
+            .iter()
+            .flat_map(|rule| &rule.keywords)
+            .map(|keyword| keyword.split_whitespace().count())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut seen = HashSet::new();
+        let mut rules = Vec::with_capacity(file.rules.len());
+        for spec in file.rules {
+            if !seen.insert(spec.id.clone()) {
+                bail!("Duplicate rule id {}", spec.id);
+            }
+            rules.push(Rule::compile(spec)?);
+        }
+
+        Ok(Self {
+            version: PolicyVersion {
+                version: file.version.unwrap_or_else(|| UNVERSIONED.to_owned()),
+                hash,
+            },
+            rules,
+            self_tests: file.self_tests,
+            thresholds: file.thresholds,
+            pii: PiiDetector::new(file.pii),
+            keyword_words,
+        })
+    }
+
+    /// Decodifica e compila un file di policy YAML.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il YAML non è valido o non supera la validazione.
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        let file: PolicyFile = serde_yaml::from_str(raw).context("Invalid policy YAML")?;
+        Self::compile(file)
+    }
+
+    /// Carica un file di policy da disco.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o non è valido.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read policy file {}", path.display()))?;
+        Self::from_yaml(&raw).with_context(|| format!("Invalid policy file {}", path.display()))
+    }
+
+    /// Regole di default ([`DEFAULT_POLICY`]).
+    ///
+    /// # Panics
+    ///
+    /// Solo se la policy di default inclusa nel crate non è valida (coperto
+    /// dai test).
+    #[must_use]
+    pub fn default_rules() -> Self {
+        Self::from_yaml(DEFAULT_POLICY).expect("built-in default policy must be valid")
+    }
+
+    /// Etichetta di versione del file di policy.
+    #[must_use]
+    pub fn version(&self) -> &str {
+        &self.version.version
+    }
+
+    /// Versione e hash dell'insieme di regole.
+    #[must_use]
+    pub const fn policy_version(&self) -> &PolicyVersion {
+        &self.version
+    }
+
+    /// Self-test dichiarati nel file di policy.
+    #[must_use]
+    pub fn self_tests(&self) -> &[SelfTest] {
+        &self.self_tests
+    }
+
+    /// Regole, in ordine di valutazione.
+    #[must_use]
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Soglie di severità per il rifiuto.
+    #[must_use]
+    pub const fn thresholds(&self) -> Thresholds {
+        self.thresholds
+    }
+
+    /// Rilevatore di dati personali.
+    #[must_use]
+    pub const fn pii(&self) -> &PiiDetector {
+        &self.pii
+    }
+
+    /// Numero massimo di parole di una keyword (almeno 1).
+    #[must_use]
+    pub const fn keyword_words(&self) -> usize {
+        self.keyword_words
+    }
+
+    /// Restituisce le regole che corrispondono a `text`, in ordine.
+    #[must_use]
+    pub fn matches(&self, text: &str) -> Vec<RuleMatch<'_>> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                let spans = rule.find_spans(text);
+                (!spans.is_empty()).then_some(RuleMatch { rule, spans })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r"
+version: test-1
+rules:
+  - id: kw
+    category: crime-hacking
+    severity: high
+    action: refuse
+    keywords: [ransomware, 'hack into']
+  - id: re
+    category: pii
+    severity: medium
+    action: redact
+    replacement: '[NUM]'
+    regex: ['\d{4}']
+";
+
+    #[test]
+    fn default_policy_is_valid_and_covers_all_categories() {
+        let rules = RuleSet::default_rules();
+        assert_eq!(rules.version(), "builtin-1");
+        assert!(rules
+            .rules()
+            .iter()
+            .any(|rule| rule.category == Category::SelfHarm));
+        assert!(rules
+            .rules()
+            .iter()
+            .any(|rule| rule.category == Category::CrimeHacking));
+        assert!(rules.pii().spec().enabled);
+        assert!(!rules.self_tests().is_empty());
+    }
+
+    #[test]
+    fn hash_tracks_content_not_formatting() {
+        let rules = RuleSet::from_yaml(SAMPLE).unwrap();
+        let reformatted = RuleSet::from_yaml(&format!("# comment\n{SAMPLE}")).unwrap();
+        let changed = RuleSet::from_yaml(&SAMPLE.replace("[NUM]", "[N]")).unwrap();
+
+        assert_eq!(rules.policy_version().hash.len(), HASH_LEN);
+        assert_eq!(rules.policy_version(), reformatted.policy_version());
+        assert_ne!(rules.policy_version().hash, changed.policy_version().hash);
+        assert_eq!(
+            rules.policy_version().to_string(),
+            format!("test-1@{}", rules.policy_version().hash)
+        );
+    }
+
+    #[test]
+    fn keywords_match_whole_words_case_insensitively() {
+        let rules = RuleSet::from_yaml(SAMPLE).unwrap();
+
+        let matches = rules.matches("Write RANSOMWARE to HACK  into x");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rule.id, "kw");
+        assert_eq!(matches[0].spans, vec![6..16]);
+
+        assert!(rules.matches("antiransomwaretools").is_empty());
+    }
+
+    #[test]
+    fn regex_spans_are_reported_in_order() {
+        let rules = RuleSet::from_yaml(SAMPLE).unwrap();
+        let matches = rules.matches("pin 1234 e 5678");
+
+        assert_eq!(matches[0].rule.id, "re");
+        assert_eq!(matches[0].rule.replacement, "[NUM]");
+        assert_eq!(matches[0].spans, vec![4..8, 11..15]);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected_with_the_rule_id() {
+        let duplicate = "rules:\n  - {id: a, category: pii, severity: low, action: allow, keywords: [x]}\n  - {id: a, category: pii, severity: low, action: allow, keywords: [y]}\n";
+        let err = RuleSet::from_yaml(duplicate).unwrap_err();
+        assert!(err.to_string().contains("Duplicate rule id a"));
+
+        let no_matcher = "rules:\n  - {id: empty, category: pii, severity: low, action: allow}\n";
+        let err = RuleSet::from_yaml(no_matcher).unwrap_err();
+        assert!(err.to_string().contains("empty"));
+
+        let blank_keyword =
+            "rules:\n  - {id: blank, category: pii, severity: low, action: allow, keywords: [' ']}\n";
+        let err = RuleSet::from_yaml(blank_keyword).unwrap_err();
+        assert!(err.to_string().contains("Rule blank has no keyword"));
+
+        let bad_regex =
+            "rules:\n  - {id: broken, category: pii, severity: low, action: allow, regex: ['(']}\n";
+        let err = RuleSet::from_yaml(bad_regex).unwrap_err();
+        assert!(err.to_string().contains("broken"));
+
+        let inverted = "thresholds: {normal: medium, strict: high}\n";
+        let err = RuleSet::from_yaml(inverted).unwrap_err();
+        assert!(err.to_string().contains("thresholds.strict"));
+
+        let unknown_category =
+            "rules:\n  - {id: c, category: spam, severity: low, action: allow, keywords: [x]}\n";
+        assert!(RuleSet::from_yaml(unknown_category).is_err());
+    }
+}
@@ -14,14 +14,58 @@
 //!
 //! Ad ogni tick, il nodo chiama [`PriorityScheduler::schedule_tick`] che:
 //!
-//! 1. determina quali lane possono essere eseguite (based on throttle),
-//! 2. assegna slot temporali alle diverse lane,
-//! 3. restituisce una struttura [`ScheduledWork`] che indica cosa fare.
+//! 1. accoda i task periodici giunti a scadenza (training, delta,
+//!    snapshot, ...) nelle code delle rispettive lane,
+//! 2. determina quali lane possono essere eseguite (based on throttle),
+//! 3. preleva i task dalle code in weighted fair queuing sui `*_weight` di
+//!    [`SchedulerConfig`], entro il budget del tick,
+//! 4. restituisce una struttura [`ScheduledWork`] che indica cosa fare.
 //!
-//! Il nodo poi interpreta questo piano ed esegue i task appropriati.
+//! Il nodo poi esegue i task del piano in ordine, scalando dal budget il
+//! [`TaskKind::cost`] di ogni task eseguito, e salta i task non critici che
+//! non entrano più nel budget residuo: tornano in testa alle code
+//! ([`PriorityScheduler::defer`]).
+//!
+//! # Weighted fair queuing
+//!
+//! Ogni task riceve un'etichetta di fine in tempo virtuale:
+//!
+//! ```text
+//! start  = max(virtual_time, finish della sua lane)
+//! finish = start + cost / weight
+//! ```
+//!
+//! e viene servito per primo il task in testa con la `finish` più bassa
+//! (a parità, la lane più prioritaria). Le etichette persistono tra i tick,
+//! quindi ogni lane con lavoro in coda riceve nel tempo una quota del budget
+//! proporzionale al suo peso e una corsia Normal sempre piena non affama la
+//! Background; una lane rimasta inattiva riparte dal tempo virtuale corrente
+//! e non accumula credito.
+//!
+//! Se il prossimo task in ordine equo non entra nel budget residuo, il tick
+//! si chiude lì per le lane non critiche: quel task sarà il primo del tick
+//! successivo invece di essere scavalcato all'infinito da task più piccoli.
+//! Un task più costoso dell'intero budget del tick (es. training sotto
+//! throttle) viene invece saltato finché l'intensità non risale, senza
+//! bloccare la sua lane: la lane è rappresentata dal primo task in coda che
+//! entra nel budget (es. il delta accodato dietro al training).
+//!
+//! I task critici sono sempre ammessi e non consumano il budget in fase di
+//! pianificazione: se vengono davvero eseguiti, il nodo ne scala il costo
+//! dal residuo.
 
+use anyhow::{bail, Result};
+use serde::Deserialize;
 use std::collections::VecDeque;
 
+use crate::adaptive_throttle::ThrottleLevel;
+
+/// Tolleranza nei confronti tra costi e budget (somme di frazioni decimali).
+const COST_EPSILON: f64 = 1e-9;
+
+/// Lane in ordine di priorità.
+const LANES: [Lane; 3] = [Lane::Critical, Lane::Normal, Lane::Background];
+
 /// Corsia di priorità per i task del nodo.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Lane {
@@ -73,6 +117,15 @@ impl Lane {
             Self::Background => "Background",
         }
     }
+
+    /// Posizione della lane in [`LANES`] (0 = più prioritaria).
+    const fn index(self) -> usize {
+        match self {
+            Self::Critical => 0,
+            Self::Normal => 1,
+            Self::Background => 2,
+        }
+    }
 }
 
 impl std::fmt::Display for Lane {
@@ -136,6 +189,7 @@ impl TaskKind {
     ///
     /// Usato per bilanciare il carico tra lane.
     #[must_use]
+    #[allow(clippy::match_same_arms)]
     pub const fn cost(&self) -> f64 {
         match self {
             Self::UserInference => 0.3,
@@ -164,7 +218,7 @@ pub struct ScheduledWork {
     /// Budget temporale stimato per questo tick (frazione 0.0-1.0 del tick time).
     ///
     /// 1.0 = tick "pieno", 0.5 = metà tick, ecc.
-    /// Usato in combinazione con throttle intensity.
+    /// Già scalato per l'intensità del throttle al momento dello scheduling.
     pub budget: f64,
 
     /// Indica se le lane background sono attive in questo tick.
@@ -174,7 +228,7 @@ pub struct ScheduledWork {
 impl ScheduledWork {
     /// Crea un piano di lavoro vuoto.
     #[must_use]
-    pub fn empty() -> Self {
+    pub const fn empty() -> Self {
         Self {
             tasks: Vec::new(),
             budget: 0.0,
@@ -184,25 +238,39 @@ impl ScheduledWork {
 
     /// Restituisce `true` se il piano contiene almeno un task.
     #[must_use]
-    pub fn has_work(&self) -> bool {
+    pub const fn has_work(&self) -> bool {
         !self.tasks.is_empty()
     }
 
     /// Restituisce il numero totale di task schedulati.
     #[must_use]
-    pub fn task_count(&self) -> usize {
+    pub const fn task_count(&self) -> usize {
         self.tasks.len()
     }
 
     /// Restituisce il costo computazionale totale stimato (somma dei costi).
     #[must_use]
     pub fn total_cost(&self) -> f64 {
-        self.tasks.iter().map(|t| t.cost()).sum()
+        self.tasks.iter().map(TaskKind::cost).sum()
+    }
+
+    /// Restituisce `true` se `task` può essere eseguito con il budget residuo.
+    ///
+    /// I task della lane Critical sono sempre ammessi: la risposta all'utente
+    /// non viene mai sacrificata per rispettare il budget, ma il loro costo
+    /// viene comunque scalato dal residuo.
+    #[must_use]
+    pub fn fits(task: TaskKind, remaining_budget: f64) -> bool {
+        task.lane() == Lane::Critical || task.cost() <= remaining_budget + COST_EPSILON
     }
 }
 
 /// Configurazione dello scheduler.
-#[derive(Debug, Clone)]
+///
+/// In YAML (sezione `scheduler:` di `samaritan.yaml`) i campi assenti
+/// mantengono il valore di default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SchedulerConfig {
     /// Peso della lane Critical (default: 10).
     pub critical_weight: u32,
@@ -219,6 +287,42 @@ pub struct SchedulerConfig {
     pub max_budget_per_tick: f64,
 }
 
+impl SchedulerConfig {
+    /// Verifica che i valori siano coerenti.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna un errore che nomina il primo campo non valido.
+    pub fn validate(&self) -> Result<()> {
+        for (field, weight) in [
+            ("critical_weight", self.critical_weight),
+            ("normal_weight", self.normal_weight),
+            ("background_weight", self.background_weight),
+        ] {
+            if weight == 0 {
+                bail!("{field} must be at least 1");
+            }
+        }
+        if !(self.max_budget_per_tick > 0.0 && self.max_budget_per_tick <= 1.0) {
+            bail!(
+                "max_budget_per_tick must be greater than 0 and at most 1 (got {})",
+                self.max_budget_per_tick
+            );
+        }
+        Ok(())
+    }
+
+    /// Peso configurato per `lane`.
+    #[must_use]
+    pub const fn weight(&self, lane: Lane) -> u32 {
+        match lane {
+            Lane::Critical => self.critical_weight,
+            Lane::Normal => self.normal_weight,
+            Lane::Background => self.background_weight,
+        }
+    }
+}
+
 impl Default for SchedulerConfig {
     fn default() -> Self {
         Self {
@@ -230,7 +334,7 @@ impl Default for SchedulerConfig {
     }
 }
 
-/// Scheduler a priorità per il NeuroNode.
+/// Scheduler a priorità per il `NeuroNode`.
 ///
 /// Mantiene code separate per ogni lane e decide quali task eseguire
 /// in base a priorità, throttle e budget temporale.
@@ -242,6 +346,18 @@ pub struct PriorityScheduler {
     critical_queue: VecDeque<TaskKind>,
     normal_queue: VecDeque<TaskKind>,
     background_queue: VecDeque<TaskKind>,
+    // Weighted fair queuing
+    virtual_time: f64,
+    /// Etichetta di fine dell'ultimo task servito, per lane (indice
+    /// [`Lane::index`]).
+    finish_tags: [f64; 3],
+    /// Etichette `(start, finish)` del task in testa, per lane, con il task
+    /// a cui si riferiscono: fissate la prima volta che il task viene
+    /// considerato.
+    head_tags: [Option<(TaskKind, f64, f64)>; 3],
+
+    /// Lane Normal e Background sospese dall'operatore.
+    background_paused: bool,
 
     // Statistiche
     ticks_scheduled: u64,
@@ -257,86 +373,220 @@ impl PriorityScheduler {
 
     /// Crea un nuovo scheduler con configurazione custom.
     #[must_use]
-    pub fn with_config(config: SchedulerConfig) -> Self {
+    pub const fn with_config(config: SchedulerConfig) -> Self {
         Self {
             config,
             critical_queue: VecDeque::new(),
             normal_queue: VecDeque::new(),
             background_queue: VecDeque::new(),
+            virtual_time: 0.0,
+            finish_tags: [0.0; 3],
+            head_tags: [None; 3],
+            background_paused: false,
             ticks_scheduled: 0,
             tasks_executed: 0,
         }
     }
 
+    /// Configurazione corrente.
+    #[must_use]
+    pub const fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Sostituisce la configurazione; i task in coda restano in coda.
+    pub const fn set_config(&mut self, config: SchedulerConfig) {
+        self.config = config;
+    }
+
     /// Schedula il lavoro per un singolo tick.
     ///
     /// # Parametri
     ///
-    /// * `tick_number` - numero del tick corrente (per logging/debug)
+    /// * `tick_number` - numero del tick corrente (determina le cadenze periodiche)
+    /// * `level` - livello di throttle corrente: in `Survival` (o con le
+    ///   lane non critiche sospese, vedi [`Self::pause_background`]) vengono
+    ///   schedulati solo i task della lane Critical
+    /// * `intensity` - intensità del throttle (0.0-1.0), scala il budget
     ///
     /// # Nota
     ///
-    /// Nella versione attuale, lo scheduler è "stateless" nel senso che
-    /// non mantiene una coda di task reale. I task sono implicitamente
-    /// definiti dalla logica del [`NeuroNode::tick`].
-    ///
-    /// Questa funzione restituisce un piano "suggerito" piuttosto che
-    /// una lista di task effettivi da eseguire.
-    pub fn schedule_tick(&mut self, tick_number: u64) -> ScheduledWork {
+    /// I task periodici vengono accodati alla loro cadenza anche quando la
+    /// loro lane non può essere eseguita, ma al più una volta: un training
+    /// già in coda non viene duplicato, e riparte appena la lane torna
+    /// attiva. Il piano contiene i task prelevati dalle code (vedi
+    /// [weighted fair queuing](self#weighted-fair-queuing)), ordinati per lane;
+    /// il costo dei task non critici non supera `budget`.
+    pub fn schedule_tick(
+        &mut self,
+        tick_number: u64,
+        level: ThrottleLevel,
+        intensity: f64,
+    ) -> ScheduledWork {
         self.ticks_scheduled = self.ticks_scheduled.wrapping_add(1);
-
-        // Per ora: versione semplificata che costruisce un piano base
-        // In futuro: potrebbe guardare code effettive, storia recente, ecc.
+        self.enqueue_periodic(tick_number);
 
         let mut work = ScheduledWork {
             tasks: Vec::new(),
-            budget: self.config.max_budget_per_tick,
+            budget: self.config.max_budget_per_tick * intensity.clamp(0.0, 1.0),
             background_active: false,
         };
 
+        let lanes = if !level.allows_background() || self.background_paused {
+            &LANES[..1]
+        } else {
+            &LANES[..]
+        };
+
+        let mut remaining = work.budget;
+        let mut budget_closed = false;
+        loop {
+            let lanes = if budget_closed { &lanes[..1] } else { lanes };
+            let Some((lane, index, start, finish)) = self.next_lane(lanes, work.budget) else {
+                break;
+            };
+            let task = self.queue(lane)[index];
+            if !ScheduledWork::fits(task, remaining) {
+                budget_closed = true;
+                continue;
+            }
+
+            self.take(lane, index);
+            self.virtual_time = start;
+            self.finish_tags[lane.index()] = finish;
+            if lane != Lane::Critical {
+                remaining -= task.cost();
+            }
+            work.tasks.push(task);
+            self.tasks_executed = self.tasks_executed.wrapping_add(1);
+        }
+
+        work.tasks.sort_by_key(|task| task.lane().index());
+        work.background_active = work.tasks.iter().any(|task| task.lane().is_background());
+        work
+    }
+
+    /// Lane da servire per prima tra `lanes`, con la posizione in coda del
+    /// suo task in testa e le sue etichette `(start, finish)`: quella con la
+    /// `finish` più bassa (a parità, la più prioritaria).
+    ///
+    /// Il task in testa di una lane è il primo in coda che entra in
+    /// `budget`, l'intero budget del tick: i task più costosi restano al
+    /// loro posto senza bloccare quelli accodati dietro. Sono escluse le
+    /// lane senza task che entrano nel budget.
+    fn next_lane(&mut self, lanes: &[Lane], budget: f64) -> Option<(Lane, usize, f64, f64)> {
+        let mut best: Option<(Lane, usize, f64, f64)> = None;
+        for &lane in lanes {
+            let Some((index, head)) = self
+                .queue(lane)
+                .iter()
+                .copied()
+                .enumerate()
+                .find(|&(_, task)| ScheduledWork::fits(task, budget))
+            else {
+                continue;
+            };
+            let (_, start, finish) = match self.head_tags[lane.index()] {
+                Some(tags @ (task, ..)) if task == head => tags,
+                _ => {
+                    let start = self.virtual_time.max(self.finish_tags[lane.index()]);
+                    let weight = f64::from(self.config.weight(lane).max(1));
+                    let tags = (head, start, start + head.cost() / weight);
+                    self.head_tags[lane.index()] = Some(tags);
+                    tags
+                }
+            };
+            if best.is_none_or(|(.., best_finish)| finish < best_finish) {
+                best = Some((lane, index, start, finish));
+            }
+        }
+        best
+    }
+
+    /// Accoda i task periodici la cui cadenza cade in `tick_number`.
+    fn enqueue_periodic(&mut self, tick_number: u64) {
         // Critical lane: sempre attiva
         // (Il NeuroNode decide se c'è effettivamente input utente da processare)
-        work.tasks.push(TaskKind::UserInference);
-        work.tasks.push(TaskKind::PolicyEvaluation);
-        work.tasks.push(TaskKind::UserDelivery);
+        self.enqueue_once(TaskKind::UserInference);
+        self.enqueue_once(TaskKind::PolicyEvaluation);
+        self.enqueue_once(TaskKind::UserDelivery);
 
-        // Normal lane: training e delta (modulato da throttle nel tick reale)
-        if tick_number % 10 == 0 {
+        // Normal lane: training e delta
+        if tick_number.is_multiple_of(10) {
             // Training ogni 10 tick
-            work.tasks.push(TaskKind::LocalTraining);
+            self.enqueue_once(TaskKind::LocalTraining);
         }
-        if tick_number % 100 == 0 {
+        if tick_number.is_multiple_of(100) {
             // Delta computation ogni 100 tick
-            work.tasks.push(TaskKind::DeltaComputation);
-            work.tasks.push(TaskKind::DeltaSubmission);
+            self.enqueue_once(TaskKind::DeltaComputation);
+            self.enqueue_once(TaskKind::DeltaSubmission);
         }
 
         // Background lane: snapshot, meta, update
-        if tick_number % 10_000 == 0 {
-            work.tasks.push(TaskKind::SnapshotCreation);
-            work.background_active = true;
+        if tick_number.is_multiple_of(10_000) {
+            self.enqueue_once(TaskKind::SnapshotCreation);
         }
-        if tick_number % 1_000 == 0 {
-            work.tasks.push(TaskKind::MetricsSampling);
-            work.background_active = true;
+        if tick_number.is_multiple_of(1_000) {
+            self.enqueue_once(TaskKind::MetricsSampling);
         }
-        if tick_number % 50_000 == 0 {
-            work.tasks.push(TaskKind::UpdateCheck);
-            work.background_active = true;
+        if tick_number.is_multiple_of(50_000) {
+            self.enqueue_once(TaskKind::UpdateCheck);
         }
+    }
 
-        work
+    /// Accoda `task` solo se non è già in attesa nella sua lane.
+    fn enqueue_once(&mut self, task: TaskKind) {
+        if !self.queue(task.lane()).contains(&task) {
+            self.enqueue(task);
+        }
+    }
+
+    /// Coda della lane indicata.
+    const fn queue(&self, lane: Lane) -> &VecDeque<TaskKind> {
+        match lane {
+            Lane::Critical => &self.critical_queue,
+            Lane::Normal => &self.normal_queue,
+            Lane::Background => &self.background_queue,
+        }
+    }
+
+    /// Coda della lane indicata, in scrittura.
+    const fn queue_mut(&mut self, lane: Lane) -> &mut VecDeque<TaskKind> {
+        match lane {
+            Lane::Critical => &mut self.critical_queue,
+            Lane::Normal => &mut self.normal_queue,
+            Lane::Background => &mut self.background_queue,
+        }
+    }
+
+    /// Rimuove il task in posizione `index` nella coda di `lane`; il
+    /// prossimo task in testa riceverà nuove etichette.
+    fn take(&mut self, lane: Lane, index: usize) {
+        self.head_tags[lane.index()] = None;
+        self.queue_mut(lane).remove(index);
     }
 
     /// Enqueue un task in una delle code.
     ///
-    /// Questo metodo è disponibile per uso futuro, quando lo scheduler
-    /// gestirà code di task effettive invece di essere stateless.
+    /// Il task verrà pianificato da [`Self::schedule_tick`] nei prossimi
+    /// tick, secondo il peso della sua lane.
     pub fn enqueue(&mut self, task: TaskKind) {
-        match task.lane() {
-            Lane::Critical => self.critical_queue.push_back(task),
-            Lane::Normal => self.normal_queue.push_back(task),
-            Lane::Background => self.background_queue.push_back(task),
+        self.queue_mut(task.lane()).push_back(task);
+    }
+
+    /// Rimette in testa alle rispettive code i task di un piano che non sono
+    /// stati eseguiti (es. perché fuori dal budget residuo), nell'ordine
+    /// dato. L'etichetta di fine della lane torna indietro del loro costo,
+    /// così non perdono il turno.
+    pub fn defer(&mut self, tasks: &[TaskKind]) {
+        for &task in tasks.iter().rev() {
+            let lane = task.lane();
+            self.queue_mut(lane).push_front(task);
+            self.head_tags[lane.index()] = None;
+            let weight = f64::from(self.config.weight(lane).max(1));
+            let tag = &mut self.finish_tags[lane.index()];
+            *tag = (*tag - task.cost() / weight).max(self.virtual_time);
+            self.tasks_executed = self.tasks_executed.saturating_sub(1);
         }
     }
 
@@ -367,6 +617,23 @@ impl PriorityScheduler {
         task
     }
 
+    /// Sospende le lane Normal e Background: finché non viene chiamato
+    /// [`Self::resume_background`] vengono schedulati solo task critici.
+    pub const fn pause_background(&mut self) {
+        self.background_paused = true;
+    }
+
+    /// Riattiva le lane Normal e Background.
+    pub const fn resume_background(&mut self) {
+        self.background_paused = false;
+    }
+
+    /// Restituisce `true` se le lane non critiche sono sospese.
+    #[must_use]
+    pub const fn is_background_paused(&self) -> bool {
+        self.background_paused
+    }
+
     /// Restituisce il numero di task pendenti in tutte le code.
     #[must_use]
     pub fn pending_tasks(&self) -> usize {
@@ -379,7 +646,9 @@ impl PriorityScheduler {
         self.ticks_scheduled
     }
 
-    /// Restituisce il numero totale di task eseguiti finora.
+    /// Restituisce il numero totale di task eseguiti finora (pianificati
+    /// da [`Self::schedule_tick`] e non rimandati, o prelevati con i
+    /// `dequeue_*`).
     #[must_use]
     pub const fn tasks_executed(&self) -> u64 {
         self.tasks_executed
@@ -390,6 +659,9 @@ impl PriorityScheduler {
         self.critical_queue.clear();
         self.normal_queue.clear();
         self.background_queue.clear();
+        self.virtual_time = 0.0;
+        self.finish_tags = [0.0; 3];
+        self.head_tags = [None; 3];
     }
 }
 
@@ -400,6 +672,7 @@ impl Default for PriorityScheduler {
 }
 
 #[cfg(test)]
+#[allow(clippy::float_cmp)]
 mod tests {
     use super::*;
 
@@ -474,7 +747,7 @@ mod tests {
     #[test]
     fn schedule_tick_returns_valid_work() {
         let mut sched = PriorityScheduler::new();
-        let work = sched.schedule_tick(0);
+        let work = sched.schedule_tick(0, ThrottleLevel::Normal, 1.0);
 
         assert!(work.has_work());
         assert!(work.budget > 0.0);
@@ -484,7 +757,7 @@ mod tests {
     #[test]
     fn schedule_tick_includes_critical_tasks() {
         let mut sched = PriorityScheduler::new();
-        let work = sched.schedule_tick(0);
+        let work = sched.schedule_tick(0, ThrottleLevel::Normal, 1.0);
 
         // Critical lane dovrebbe essere sempre presente
         let has_critical = work
@@ -499,13 +772,199 @@ mod tests {
         let mut sched = PriorityScheduler::new();
 
         // Tick normale: no background
-        let work1 = sched.schedule_tick(5);
+        let work1 = sched.schedule_tick(5, ThrottleLevel::Normal, 1.0);
         assert!(!work1.background_active);
 
-        // Tick 10_000: snapshot (background)
-        let work2 = sched.schedule_tick(10_000);
-        assert!(work2.background_active);
-        assert!(work2.tasks.contains(&TaskKind::SnapshotCreation));
+        // Tick 10_000: snapshot (background), pianificato appena il budget
+        // lasciato dal training lo consente
+        let work2 = sched.schedule_tick(10_000, ThrottleLevel::Normal, 1.0);
+        assert!(work2.tasks.contains(&TaskKind::LocalTraining));
+        let work3 = sched.schedule_tick(10_001, ThrottleLevel::Normal, 1.0);
+        assert!(work3.background_active);
+        assert!(work3.tasks.contains(&TaskKind::SnapshotCreation));
+    }
+
+    #[test]
+    fn survival_schedules_only_critical_tasks() {
+        let mut sched = PriorityScheduler::new();
+        let work = sched.schedule_tick(0, ThrottleLevel::Survival, 0.0);
+
+        assert!(work.has_work());
+        assert!(work.tasks.iter().all(|t| t.lane() == Lane::Critical));
+        assert!(!work.background_active);
+    }
+
+    #[test]
+    fn paused_background_schedules_only_critical_tasks() {
+        let mut sched = PriorityScheduler::new();
+        sched.pause_background();
+        let work = sched.schedule_tick(0, ThrottleLevel::Normal, 1.0);
+
+        assert!(sched.is_background_paused());
+        assert!(work.tasks.iter().all(|t| t.lane() == Lane::Critical));
+
+        // I task periodici accodati durante la pausa ripartono alla ripresa.
+        sched.resume_background();
+        let work = sched.schedule_tick(1, ThrottleLevel::Normal, 1.0);
+        assert!(work.tasks.contains(&TaskKind::LocalTraining));
+    }
+
+    #[test]
+    fn budget_is_scaled_by_intensity() {
+        let mut sched = PriorityScheduler::new();
+
+        let full = sched.schedule_tick(1, ThrottleLevel::Normal, 1.0);
+        let half = sched.schedule_tick(1, ThrottleLevel::Throttled, 0.5);
+
+        assert!((full.budget - 0.9).abs() < 1e-9);
+        assert!((half.budget - 0.45).abs() < 1e-9);
+    }
+
+    /// Costo dei task non critici di un piano.
+    fn non_critical_cost(work: &ScheduledWork) -> f64 {
+        work.tasks
+            .iter()
+            .filter(|task| task.lane() != Lane::Critical)
+            .map(TaskKind::cost)
+            .sum()
+    }
+
+    /// Numero di task di `lane` in un piano.
+    fn count(work: &ScheduledWork, lane: Lane) -> usize {
+        work.tasks.iter().filter(|task| task.lane() == lane).count()
+    }
+
+    #[test]
+    fn periodic_tasks_are_coalesced_while_lanes_are_idle() {
+        let mut sched = PriorityScheduler::new();
+
+        // In Survival training e metriche restano in coda, una volta sola.
+        for tick in [0, 10, 20, 1_000] {
+            let work = sched.schedule_tick(tick, ThrottleLevel::Survival, 0.0);
+            assert_eq!(work.tasks.len(), 3);
+        }
+        assert_eq!(sched.pending_tasks(), 6);
+
+        let work = sched.schedule_tick(1, ThrottleLevel::Normal, 1.0);
+        assert_eq!(count(&work, Lane::Normal), 1);
+        assert!(non_critical_cost(&work) <= work.budget);
+    }
+
+    #[test]
+    fn plan_respects_budget_and_lane_order() {
+        let mut sched = PriorityScheduler::new();
+        for _ in 0..10 {
+            sched.enqueue(TaskKind::LocalTraining);
+            sched.enqueue(TaskKind::SnapshotCreation);
+        }
+
+        for intensity in [1.0, 0.5, 0.1] {
+            let work = sched.schedule_tick(1, ThrottleLevel::Normal, intensity);
+            assert!(non_critical_cost(&work) <= work.budget + 1e-9);
+            let lanes: Vec<_> = work.tasks.iter().map(|task| task.lane().index()).collect();
+            assert!(lanes.is_sorted(), "{:?}", work.tasks);
+        }
+    }
+
+    #[test]
+    fn background_is_not_starved_by_a_busy_normal_lane() {
+        let mut sched = PriorityScheduler::new();
+        let mut normal = 0;
+        let mut trainings = 0;
+
+        for tick in 1..=59 {
+            // Corsia Normal sempre piena: da sola riempirebbe ogni budget.
+            while sched.normal_queue.len() < 20 {
+                sched.enqueue(TaskKind::DeltaSubmission);
+            }
+            sched.enqueue(TaskKind::UpdateCheck);
+
+            let work = sched.schedule_tick(tick, ThrottleLevel::Normal, 1.0);
+            assert_eq!(count(&work, Lane::Background), 1, "tick {tick}");
+            assert!(non_critical_cost(&work) <= work.budget + 1e-9);
+            normal += count(&work, Lane::Normal);
+            trainings += usize::from(work.tasks.contains(&TaskKind::LocalTraining));
+        }
+        assert!(normal > 5 * 59, "{normal}");
+        // Anche il training periodico (0.8) non resta indietro ai task piccoli.
+        assert_eq!(trainings, 5);
+        assert_eq!(sched.background_queue.len(), 0);
+    }
+
+    #[test]
+    fn lane_weights_split_the_budget() {
+        let config = SchedulerConfig {
+            normal_weight: 3,
+            background_weight: 3,
+            max_budget_per_tick: 0.6,
+            ..SchedulerConfig::default()
+        };
+        let mut sched = PriorityScheduler::with_config(config);
+
+        for tick in 1..=10 {
+            for _ in 0..5 {
+                sched.enqueue(TaskKind::DeltaSubmission);
+                sched.enqueue(TaskKind::AdrApplication);
+            }
+            let work = sched.schedule_tick(tick, ThrottleLevel::Normal, 1.0);
+            // Pesi uguali e costi uguali (0.1): metà budget a testa.
+            assert_eq!(count(&work, Lane::Normal), 3, "{:?}", work.tasks);
+            assert_eq!(count(&work, Lane::Background), 3, "{:?}", work.tasks);
+            sched.clear_all();
+        }
+    }
+
+    #[test]
+    fn deferred_tasks_keep_their_turn() {
+        let mut sched = PriorityScheduler::new();
+        sched.enqueue(TaskKind::DeltaComputation);
+        sched.enqueue(TaskKind::DeltaSubmission);
+
+        let work = sched.schedule_tick(1, ThrottleLevel::Normal, 1.0);
+        let normal: Vec<_> = work
+            .tasks
+            .iter()
+            .copied()
+            .filter(|task| task.lane() == Lane::Normal)
+            .collect();
+        assert_eq!(
+            normal,
+            [TaskKind::DeltaComputation, TaskKind::DeltaSubmission]
+        );
+        let executed = sched.tasks_executed();
+
+        // Il nodo non ha avuto budget per i task normali: tornano in testa.
+        sched.defer(&normal);
+        assert_eq!(sched.tasks_executed(), executed - 2);
+        sched.enqueue(TaskKind::LocalTraining);
+        assert_eq!(sched.dequeue_normal(), Some(TaskKind::DeltaComputation));
+        assert_eq!(sched.dequeue_normal(), Some(TaskKind::DeltaSubmission));
+    }
+
+    #[test]
+    fn oversized_head_does_not_block_its_lane() {
+        let mut sched = PriorityScheduler::new();
+        sched.enqueue(TaskKind::LocalTraining);
+        sched.enqueue(TaskKind::DeltaComputation);
+
+        // Budget throttled (0.45): il training (0.8) non entra mai, il
+        // delta accodato dietro sì.
+        let work = sched.schedule_tick(1, ThrottleLevel::Throttled, 0.5);
+        assert!(work.tasks.contains(&TaskKind::DeltaComputation));
+        assert!(!work.tasks.contains(&TaskKind::LocalTraining));
+
+        // Il training resta in testa e riparte appena l'intensità risale.
+        assert_eq!(sched.normal_queue, [TaskKind::LocalTraining]);
+        let work = sched.schedule_tick(1, ThrottleLevel::Normal, 1.0);
+        assert!(work.tasks.contains(&TaskKind::LocalTraining));
+    }
+
+    #[test]
+    fn fits_always_admits_critical_tasks() {
+        assert!(ScheduledWork::fits(TaskKind::UserInference, 0.0));
+        assert!(ScheduledWork::fits(TaskKind::LocalTraining, 0.8));
+        assert!(!ScheduledWork::fits(TaskKind::LocalTraining, 0.5));
+        assert!(!ScheduledWork::fits(TaskKind::MetricsSampling, 0.0));
     }
 
     #[test]
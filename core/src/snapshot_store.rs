@@ -1,19 +1,309 @@
 //! Snapshot storage for model versions.
+//!
+//! Ogni snapshot è un manifest JSON `<id>.json` nella directory dello store,
+//! con id numerici crescenti (`000001`, `000002`, …) così che l'ordine
+//! lessicografico coincida con quello di creazione, più i pesi del modello
+//! in `<id>.safetensors` se il backend li espone
+//! ([`InferenceBackend::export_weights`]). Vengono conservati solo gli
+//! ultimi [`DEFAULT_RETAINED_SNAPSHOTS`] snapshot.
+//!
+//! Il rollback ripristina pesi e limite di token; uno snapshot senza pesi
+//! non può essere ripristinato.
 
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::neural_engine::safetensors::SafeTensors;
+use crate::neural_engine::{InferenceBackend, NeuralEngine};
+
+/// Numero di snapshot conservati di default.
+pub const DEFAULT_RETAINED_SNAPSHOTS: usize = 16;
+
+/// Metadati di uno snapshot salvato.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Identificativo dello snapshot.
+    pub id: String,
+    /// Istante di creazione (millisecondi Unix).
+    pub created_ms: u64,
+    /// Limite di token generati del motore neurale al momento dello snapshot.
+    pub max_new_tokens: usize,
+    /// `true` se lo snapshot contiene i pesi del modello (`<id>.safetensors`).
+    #[serde(default)]
+    pub weights: bool,
+}
+
+/// Store degli snapshot del modello (per rollback).
 #[derive(Debug)]
 pub struct SnapshotStore {
-    _data_dir: PathBuf,
+    data_dir: PathBuf,
+    retained: usize,
 }
 
 impl SnapshotStore {
+    /// Apre (o crea) lo store degli snapshot sotto `data_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory non può essere creata.
     pub async fn open(data_dir: PathBuf) -> Result<Self> {
-        Ok(Self { _data_dir: data_dir })
+        tokio::fs::create_dir_all(&data_dir)
+            .await
+            .with_context(|| format!("Unable to create snapshot dir {}", data_dir.display()))?;
+
+        Ok(Self {
+            data_dir,
+            retained: DEFAULT_RETAINED_SNAPSHOTS,
+        })
+    }
+
+    /// Imposta quanti snapshot conservare (almeno 1).
+    pub fn set_retained(&mut self, retained: usize) {
+        self.retained = retained.max(1);
+    }
+
+    /// Crea uno snapshot dello stato corrente del motore neurale, rimuovendo
+    /// i più vecchi oltre il limite di conservazione.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se lo snapshot non può essere scritto.
+    pub async fn create_snapshot<B: InferenceBackend + ?Sized + Sync>(
+        &mut self,
+        engine: &NeuralEngine<B>,
+    ) -> Result<SnapshotInfo> {
+        let mut existing = self.list().await?;
+        let next = existing
+            .last()
+            .and_then(|snapshot| snapshot.id.parse::<u64>().ok())
+            .map_or(1, |last| last + 1);
+        let created_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+            });
+        let id = format!("{next:06}");
+        let weights = engine.backend().export_weights();
+        if let Some(weights) = &weights {
+            let path = self.weights_path(&id);
+            tokio::fs::write(&path, weights.to_bytes()?)
+                .await
+                .with_context(|| format!("Unable to write snapshot {}", path.display()))?;
+        }
+        let info = SnapshotInfo {
+            id,
+            created_ms,
+            max_new_tokens: engine.max_new_tokens(),
+            weights: weights.is_some(),
+        };
+
+        let path = self.data_dir.join(format!("{}.json", info.id));
+        let raw = serde_json::to_vec_pretty(&info).context("Unable to encode snapshot")?;
+        tokio::fs::write(&path, raw)
+            .await
+            .with_context(|| format!("Unable to write snapshot {}", path.display()))?;
+
+        let excess = (existing.len() + 1).saturating_sub(self.retained);
+        for old in existing.drain(..excess) {
+            if old.weights {
+                remove(&self.weights_path(&old.id)).await?;
+            }
+            remove(&self.data_dir.join(format!("{}.json", old.id))).await?;
+        }
+        Ok(info)
+    }
+
+    /// Riporta il motore neurale allo stato dello snapshot `id`: pesi del
+    /// modello e limite di token.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se lo snapshot non esiste o non è leggibile, se non
+    /// contiene i pesi del modello o se il backend non può ripristinarli.
+    pub async fn restore_snapshot<B: InferenceBackend + ?Sized>(
+        &self,
+        id: &str,
+        engine: &mut NeuralEngine<B>,
+    ) -> Result<SnapshotInfo> {
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("Invalid snapshot id {id:?}");
+        }
+        let path = self.data_dir.join(format!("{id}.json"));
+        let raw = match tokio::fs::read(&path).await {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                bail!("Unknown snapshot {id}")
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read snapshot {}", path.display()))
+            }
+        };
+        let info: SnapshotInfo = serde_json::from_slice(&raw)
+            .with_context(|| format!("Invalid snapshot {}", path.display()))?;
+
+        if !info.weights {
+            bail!("Snapshot {id} has no model weights: rollback is not supported for this backend");
+        }
+        let path = self.weights_path(id);
+        let raw = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("Unable to read snapshot weights {}", path.display()))?;
+        let weights = SafeTensors::parse(&raw)
+            .with_context(|| format!("Invalid snapshot weights {}", path.display()))?;
+        engine
+            .backend_mut()
+            .import_weights(weights)
+            .with_context(|| format!("Unable to restore snapshot {id}"))?;
+
+        engine.set_max_new_tokens(info.max_new_tokens);
+        Ok(info)
+    }
+
+    /// File dei pesi dello snapshot `id`.
+    fn weights_path(&self, id: &str) -> PathBuf {
+        self.data_dir.join(format!("{id}.safetensors"))
+    }
+
+    /// Snapshot salvati, dal più vecchio al più recente.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se la directory o un manifest non sono leggibili.
+    pub async fn list(&self) -> Result<Vec<SnapshotInfo>> {
+        let mut entries = tokio::fs::read_dir(&self.data_dir)
+            .await
+            .with_context(|| format!("Unable to read snapshot dir {}", self.data_dir.display()))?;
+        let mut snapshots = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let raw = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("Unable to read snapshot {}", path.display()))?;
+            let info: SnapshotInfo = serde_json::from_slice(&raw)
+                .with_context(|| format!("Invalid snapshot {}", path.display()))?;
+            snapshots.push(info);
+        }
+        snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(snapshots)
     }
+}
+
+/// Rimuove un file dello store.
+async fn remove(path: &Path) -> Result<()> {
+    tokio::fs::remove_file(path)
+        .await
+        .with_context(|| format!("Unable to prune snapshot {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neural_engine::cpu::{CpuMlpBackend, MlpWeights};
+    use crate::neural_engine::{Tensor, TensorSpec};
+    use crate::tokenizer::Tokenizer;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn snapshots_are_listed_restored_and_pruned() {
+        let dir =
+            std::env::temp_dir().join(format!("samaritan-snapshots-{}", uuid::Uuid::new_v4()));
+        let mut store = SnapshotStore::open(dir.clone()).await.unwrap();
+        let original = MlpWeights::seeded(16, 4, 8, 7);
+        let mut engine = NeuralEngine::new(
+            CpuMlpBackend::from_weights(original.clone()).unwrap(),
+            Arc::new(Tokenizer::byte_level()),
+        );
+
+        assert!(store.list().await.unwrap().is_empty());
+        store.create_snapshot(&engine).await.unwrap();
+        engine.set_max_new_tokens(3);
+        engine
+            .backend_mut()
+            .import_weights(MlpWeights::seeded(16, 4, 8, 8).to_safetensors())
+            .unwrap();
+        let second = store.create_snapshot(&engine).await.unwrap();
+
+        let snapshots = store.list().await.unwrap();
+        let ids: Vec<_> = snapshots.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["000001", "000002"]);
+        assert_eq!(snapshots[1], second);
+        assert_eq!(second.max_new_tokens, 3);
+        assert!(second.weights);
+
+        let restored = store.restore_snapshot("000001", &mut engine).await.unwrap();
+        assert_eq!(engine.max_new_tokens(), restored.max_new_tokens);
+        assert_ne!(engine.max_new_tokens(), 3);
+        assert_eq!(engine.backend().weights(), &original);
+        assert!(engine
+            .backend_mut()
+            .import_weights(MlpWeights::seeded(8, 4, 8, 7).to_safetensors())
+            .is_err());
+        assert!(store
+            .restore_snapshot("../000001", &mut engine)
+            .await
+            .is_err());
+        assert!(store.restore_snapshot("000009", &mut engine).await.is_err());
+
+        store.set_retained(2);
+        store.create_snapshot(&engine).await.unwrap();
+        let ids: Vec<_> = store
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, ["000002", "000003"]);
+        assert!(!dir.join("000001.safetensors").exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// Backend che non espone i pesi del modello.
+    struct Opaque(CpuMlpBackend);
+
+    impl InferenceBackend for Opaque {
+        fn load(path: &Path) -> Result<Self> {
+            CpuMlpBackend::load(path).map(Self)
+        }
+
+        fn input_schema(&self) -> &[TensorSpec] {
+            self.0.input_schema()
+        }
+
+        fn output_schema(&self) -> &[TensorSpec] {
+            self.0.output_schema()
+        }
+
+        fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+            self.0.infer(inputs)
+        }
+    }
+
+    #[tokio::test]
+    async fn rollback_without_weights_is_refused() {
+        let dir =
+            std::env::temp_dir().join(format!("samaritan-snapshots-{}", uuid::Uuid::new_v4()));
+        let mut store = SnapshotStore::open(dir.clone()).await.unwrap();
+        let mut engine = NeuralEngine::new(
+            Opaque(CpuMlpBackend::from_weights(MlpWeights::seeded(16, 4, 8, 7)).unwrap()),
+            Arc::new(Tokenizer::byte_level()),
+        );
+
+        let snapshot = store.create_snapshot(&engine).await.unwrap();
+        assert!(!snapshot.weights);
+        let err = store
+            .restore_snapshot(&snapshot.id, &mut engine)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{err:#}");
 
-    pub async fn create_snapshot<B>(&mut self, _engine: &crate::neural_engine::NeuralEngine<B>) -> Result<()> {
-        Ok(())
+        std::fs::remove_dir_all(dir).ok();
     }
 }
@@ -0,0 +1,988 @@
+//! Tokenizer compatible with the Hugging Face `tokenizer.json` format.
+//!
+//! Il [`Tokenizer`] converte il testo in token id per il [`crate::neural_engine`]
+//! e viceversa. Viene caricato dal file `tokenizer.json` accanto al modello
+//! (vedi [`Tokenizer::load_for_model`]) e supporta il sottoinsieme del
+//! formato usato dai modelli più comuni:
+//!
+//! - modelli `BPE` (anche con `byte_fallback`) e `WordPiece`,
+//! - pre-tokenizer `ByteLevel` (GPT-2), `Metaspace` (SentencePiece) e
+//!   `Whitespace`/`BertPreTokenizer`,
+//! - normalizzazione lowercase (`Lowercase`, `BertNormalizer`),
+//! - token aggiunti (`added_tokens`), riconosciuti prima della
+//!   pre-tokenizzazione.
+//!
+//! Se il file manca, il nodo usa un tokenizer byte-level (un token per
+//! byte UTF-8), sufficiente per modelli addestrati direttamente sui byte.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+/// Nome del file del tokenizer, cercato nella directory del modello.
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// Token riconosciuti come fine sequenza, in ordine di preferenza.
+const EOS_CANDIDATES: [&str; 6] = [
+    "</s>",
+    "<|endoftext|>",
+    "<|end_of_text|>",
+    "<|eot_id|>",
+    "<eos>",
+    "[SEP]",
+];
+
+/// Carattere usato da `Metaspace` al posto dello spazio.
+const METASPACE: char = '\u{2581}';
+
+/// Token aggiunto al vocabolario (speciale o meno).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddedToken {
+    /// Id del token.
+    pub id: u32,
+    /// Testo del token, riconosciuto letteralmente nell'input.
+    pub content: String,
+    /// Se `true`, il token viene omesso nella decodifica del testo.
+    pub special: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Model {
+    /// Un token per byte UTF-8 (id = valore del byte).
+    Bytes,
+    Bpe {
+        /// `(sinistra, destra) → (rank, id del merge)`.
+        merges: HashMap<(u32, u32), (usize, u32)>,
+        unk_id: Option<u32>,
+        byte_fallback: bool,
+    },
+    WordPiece {
+        unk_id: u32,
+        prefix: String,
+        max_chars: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreTokenizer {
+    ByteLevel { add_prefix_space: bool },
+    Metaspace { add_prefix_space: bool },
+    Whitespace,
+}
+
+/// Simbolo intermedio della BPE: noto al vocabolario oppure no.
+enum Symbol {
+    Known(u32),
+    Unknown(String),
+}
+
+/// Segmento dell'input dopo il riconoscimento dei token aggiunti.
+enum Segment<'a> {
+    Added(u32),
+    Text(&'a str),
+}
+
+/// Tokenizer BPE/WordPiece/byte-level.
+#[derive(Debug, Clone)]
+pub struct Tokenizer {
+    model: Model,
+    pre_split: PreTokenizer,
+    lowercase: bool,
+    vocab: HashMap<String, u32>,
+    id_to_token: HashMap<u32, String>,
+    /// Ordinati per lunghezza decrescente (match più lungo per primo).
+    added: Vec<AddedToken>,
+    eos_id: Option<u32>,
+    byte_to_char: [char; 256],
+    char_to_byte: HashMap<char, u8>,
+}
+
+#[derive(Deserialize)]
+struct TokenizerJson {
+    #[serde(default)]
+    added_tokens: Vec<AddedTokenJson>,
+    #[serde(default)]
+    normalizer: Option<Value>,
+    #[serde(default)]
+    pre_tokenizer: Option<Value>,
+    model: ModelJson,
+}
+
+#[derive(Deserialize)]
+struct AddedTokenJson {
+    id: u32,
+    content: String,
+    #[serde(default)]
+    special: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum ModelJson {
+    #[serde(rename = "BPE")]
+    Bpe {
+        vocab: HashMap<String, u32>,
+        #[serde(default)]
+        merges: Vec<MergeJson>,
+        #[serde(default)]
+        unk_token: Option<String>,
+        #[serde(default)]
+        byte_fallback: bool,
+    },
+    WordPiece {
+        vocab: HashMap<String, u32>,
+        #[serde(default = "default_unk_token")]
+        unk_token: String,
+        #[serde(default = "default_subword_prefix")]
+        continuing_subword_prefix: String,
+        #[serde(default = "default_max_input_chars")]
+        max_input_chars_per_word: usize,
+    },
+}
+
+/// I merge sono `"a b"` nei file più vecchi, `["a", "b"]` nei più recenti.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MergeJson {
+    Joined(String),
+    Pair(String, String),
+}
+
+fn default_unk_token() -> String {
+    "[UNK]".to_owned()
+}
+
+fn default_subword_prefix() -> String {
+    "##".to_owned()
+}
+
+const fn default_max_input_chars() -> usize {
+    100
+}
+
+impl Tokenizer {
+    /// Tokenizer byte-level: un token per byte, vocabolario di 256 id.
+    #[must_use]
+    pub fn byte_level() -> Self {
+        Self::build(
+            Model::Bytes,
+            PreTokenizer::Whitespace,
+            false,
+            HashMap::new(),
+            Vec::new(),
+        )
+    }
+
+    /// Carica `tokenizer.json` dalla directory di `model_path`, oppure
+    /// ripiega sul tokenizer [`Tokenizer::byte_level`] se il file manca.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file esiste ma non è valido.
+    pub fn load_for_model(model_path: &Path) -> Result<Self> {
+        let path = model_path.with_file_name(TOKENIZER_FILE);
+        if path.exists() {
+            Self::from_file(&path)
+        } else {
+            warn!(
+                "No {} found next to {}, falling back to byte-level tokenizer",
+                TOKENIZER_FILE,
+                model_path.display()
+            );
+            Ok(Self::byte_level())
+        }
+    }
+
+    /// Carica un tokenizer da un file `tokenizer.json`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il file non è leggibile o non è valido.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read tokenizer {}", path.display()))?;
+        Self::from_json(&raw).with_context(|| format!("Invalid tokenizer {}", path.display()))
+    }
+
+    /// Costruisce un tokenizer dal contenuto di un `tokenizer.json`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il JSON non è valido o usa un modello non
+    /// supportato.
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let file: TokenizerJson = serde_json::from_str(raw)?;
+
+        let (lowercase, pre_tokenizer) =
+            parse_pipeline(file.normalizer.as_ref(), file.pre_tokenizer.as_ref());
+
+        let (model, vocab) = match file.model {
+            ModelJson::Bpe {
+                vocab,
+                merges,
+                unk_token,
+                byte_fallback,
+            } => {
+                let mut ranks = HashMap::with_capacity(merges.len());
+                for (rank, merge) in merges.into_iter().enumerate() {
+                    let (left, right) = match merge {
+                        MergeJson::Pair(left, right) => (left, right),
+                        MergeJson::Joined(joined) => {
+                            let (left, right) = joined
+                                .split_once(' ')
+                                .ok_or_else(|| anyhow!("Malformed merge {joined:?}"))?;
+                            (left.to_owned(), right.to_owned())
+                        }
+                    };
+                    // Merge verso token fuori vocabolario: mai applicabili.
+                    let ids = (
+                        vocab.get(&left),
+                        vocab.get(&right),
+                        vocab.get(&format!("{left}{right}")),
+                    );
+                    if let (Some(&l), Some(&r), Some(&merged)) = ids {
+                        ranks.entry((l, r)).or_insert((rank, merged));
+                    }
+                }
+                let unk_id = unk_token.and_then(|unk| vocab.get(&unk).copied());
+                let model = Model::Bpe {
+                    merges: ranks,
+                    unk_id,
+                    byte_fallback,
+                };
+                (model, vocab)
+            }
+            ModelJson::WordPiece {
+                vocab,
+                unk_token,
+                continuing_subword_prefix,
+                max_input_chars_per_word,
+            } => {
+                let unk_id = *vocab.get(&unk_token).ok_or_else(|| {
+                    anyhow!("WordPiece unk token {unk_token:?} not in vocabulary")
+                })?;
+                let model = Model::WordPiece {
+                    unk_id,
+                    prefix: continuing_subword_prefix,
+                    max_chars: max_input_chars_per_word,
+                };
+                (model, vocab)
+            }
+        };
+
+        let added = file
+            .added_tokens
+            .into_iter()
+            .map(|token| AddedToken {
+                id: token.id,
+                content: token.content,
+                special: token.special,
+            })
+            .collect();
+
+        Ok(Self::build(model, pre_tokenizer, lowercase, vocab, added))
+    }
+
+    fn build(
+        model: Model,
+        pre_tokenizer: PreTokenizer,
+        lowercase: bool,
+        vocab: HashMap<String, u32>,
+        mut added: Vec<AddedToken>,
+    ) -> Self {
+        added.retain(|token| !token.content.is_empty());
+        added.sort_by_key(|token| std::cmp::Reverse(token.content.len()));
+
+        let mut id_to_token: HashMap<u32, String> = vocab
+            .iter()
+            .map(|(token, &id)| (id, token.clone()))
+            .collect();
+        for token in &added {
+            id_to_token.insert(token.id, token.content.clone());
+        }
+
+        let eos_id = EOS_CANDIDATES.iter().find_map(|candidate| {
+            added
+                .iter()
+                .find(|token| token.special && token.content == *candidate)
+                .map(|token| token.id)
+        });
+
+        let byte_to_char = byte_level_alphabet();
+        let char_to_byte = byte_to_char
+            .iter()
+            .zip(0..=u8::MAX)
+            .map(|(&c, b)| (c, b))
+            .collect();
+
+        Self {
+            model,
+            pre_split: pre_tokenizer,
+            lowercase,
+            vocab,
+            id_to_token,
+            added,
+            eos_id,
+            byte_to_char,
+            char_to_byte,
+        }
+    }
+
+    /// Dimensione del vocabolario (id massimo + 1, token aggiunti inclusi).
+    #[must_use]
+    pub fn vocab_size(&self) -> usize {
+        match self.model {
+            Model::Bytes => 256,
+            _ => self
+                .id_to_token
+                .keys()
+                .max()
+                .map_or(0, |&max| max as usize + 1),
+        }
+    }
+
+    /// Id del token di fine sequenza, se il vocabolario ne definisce uno.
+    #[must_use]
+    pub const fn eos_token_id(&self) -> Option<u32> {
+        self.eos_id
+    }
+
+    /// Restituisce l'id di un token del vocabolario.
+    #[must_use]
+    pub fn token_to_id(&self, token: &str) -> Option<u32> {
+        self.added
+            .iter()
+            .find(|added| added.content == token)
+            .map(|added| added.id)
+            .or_else(|| self.vocab.get(token).copied())
+    }
+
+    /// Restituisce il token corrispondente a un id.
+    #[must_use]
+    pub fn id_to_token(&self, id: u32) -> Option<&str> {
+        self.id_to_token.get(&id).map(String::as_str)
+    }
+
+    /// Codifica `text` in token id.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il testo contiene simboli non rappresentabili e il
+    /// modello non ha né token sconosciuto né `byte_fallback`.
+    pub fn encode(&self, text: &str) -> Result<Vec<u32>> {
+        let mut ids = Vec::new();
+        for segment in self.split_added(text) {
+            match segment {
+                Segment::Added(id) => ids.push(id),
+                Segment::Text(text) => self.encode_text(text, &mut ids)?,
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Decodifica `ids` in testo.
+    ///
+    /// Con `skip_special_tokens` i token aggiunti speciali (es. EOS) sono
+    /// omessi. Le sequenze di byte non UTF-8 sono sostituite con `U+FFFD`.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se un id non appartiene al vocabolario.
+    pub fn decode(&self, ids: &[u32], skip_special_tokens: bool) -> Result<String> {
+        if matches!(self.model, Model::Bytes) {
+            let bytes = ids
+                .iter()
+                .map(|&id| {
+                    u8::try_from(id).map_err(|_| anyhow!("Token id {id} out of byte vocabulary"))
+                })
+                .collect::<Result<Vec<u8>>>()?;
+            return Ok(String::from_utf8_lossy(&bytes).into_owned());
+        }
+
+        let mut pieces = Vec::with_capacity(ids.len());
+        for &id in ids {
+            if let Some(added) = self.added.iter().find(|token| token.id == id) {
+                if !(skip_special_tokens && added.special) {
+                    pieces.push((added.content.as_str(), true));
+                }
+                continue;
+            }
+            let token = self
+                .id_to_token
+                .get(&id)
+                .ok_or_else(|| anyhow!("Token id {id} out of vocabulary"))?;
+            pieces.push((token.as_str(), false));
+        }
+
+        Ok(match (&self.model, self.pre_split) {
+            (Model::WordPiece { prefix, .. }, _) => decode_word_piece(&pieces, prefix),
+            (_, PreTokenizer::ByteLevel { .. }) => {
+                let mut bytes = Vec::new();
+                for (piece, added) in pieces {
+                    if added {
+                        bytes.extend_from_slice(piece.as_bytes());
+                        continue;
+                    }
+                    for c in piece.chars() {
+                        match self.char_to_byte.get(&c) {
+                            Some(&b) => bytes.push(b),
+                            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                        }
+                    }
+                }
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            (_, pre_tokenizer) => {
+                let mut bytes = Vec::new();
+                for (piece, added) in pieces {
+                    match parse_byte_token(piece) {
+                        Some(b) if !added => bytes.push(b),
+                        _ => bytes.extend_from_slice(piece.replace(METASPACE, " ").as_bytes()),
+                    }
+                }
+                let text = String::from_utf8_lossy(&bytes).into_owned();
+                match pre_tokenizer {
+                    PreTokenizer::Metaspace {
+                        add_prefix_space: true,
+                    } => text.strip_prefix(' ').map(str::to_owned).unwrap_or(text),
+                    _ => text,
+                }
+            }
+        })
+    }
+
+    /// Separa i token aggiunti dal resto del testo.
+    fn split_added<'a>(&self, text: &'a str) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            if let Some(token) = self
+                .added
+                .iter()
+                .find(|token| rest.starts_with(&token.content))
+            {
+                if start < pos {
+                    segments.push(Segment::Text(&text[start..pos]));
+                }
+                segments.push(Segment::Added(token.id));
+                pos += token.content.len();
+                start = pos;
+            } else {
+                pos += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        if start < text.len() {
+            segments.push(Segment::Text(&text[start..]));
+        }
+        segments
+    }
+
+    fn encode_text(&self, text: &str, ids: &mut Vec<u32>) -> Result<()> {
+        if matches!(self.model, Model::Bytes) {
+            ids.extend(text.bytes().map(u32::from));
+            return Ok(());
+        }
+
+        let text = if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text.to_owned()
+        };
+
+        match self.pre_split {
+            PreTokenizer::ByteLevel { add_prefix_space } => {
+                let text = if add_prefix_space && !text.starts_with(' ') {
+                    format!(" {text}")
+                } else {
+                    text
+                };
+                for word in split_byte_level(&text) {
+                    let mapped: String = word
+                        .bytes()
+                        .map(|b| self.byte_to_char[usize::from(b)])
+                        .collect();
+                    self.encode_word(&mapped, ids)?;
+                }
+            }
+            PreTokenizer::Metaspace { add_prefix_space } => {
+                let mut text = text.replace(' ', &METASPACE.to_string());
+                if add_prefix_space && !text.starts_with(METASPACE) {
+                    text.insert(0, METASPACE);
+                }
+                for word in split_metaspace(&text) {
+                    self.encode_word(word, ids)?;
+                }
+            }
+            PreTokenizer::Whitespace => {
+                for word in split_whitespace_punctuation(&text) {
+                    self.encode_word(word, ids)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_word(&self, word: &str, ids: &mut Vec<u32>) -> Result<()> {
+        match &self.model {
+            Model::Bytes => ids.extend(word.bytes().map(u32::from)),
+            Model::Bpe {
+                merges,
+                unk_id,
+                byte_fallback,
+            } => {
+                for symbol in self.bpe(word, merges) {
+                    match symbol {
+                        Symbol::Known(id) => ids.push(id),
+                        Symbol::Unknown(text) => {
+                            let fallback: Option<Vec<u32>> = if *byte_fallback {
+                                text.bytes()
+                                    .map(|b| self.vocab.get(&format!("<0x{b:02X}>")).copied())
+                                    .collect()
+                            } else {
+                                None
+                            };
+                            match (fallback, unk_id) {
+                                (Some(bytes), _) => ids.extend(bytes),
+                                (None, Some(unk)) => ids.push(*unk),
+                                (None, None) => bail!("Symbol {text:?} is not in the vocabulary"),
+                            }
+                        }
+                    }
+                }
+            }
+            Model::WordPiece {
+                unk_id,
+                prefix,
+                max_chars,
+            } => {
+                if word.chars().count() > *max_chars {
+                    ids.push(*unk_id);
+                    return Ok(());
+                }
+                let mut pieces = Vec::new();
+                let mut start = 0;
+                while start < word.len() {
+                    let mut end = word.len();
+                    let mut found = None;
+                    while start < end {
+                        let piece = &word[start..end];
+                        let candidate = if start > 0 {
+                            self.vocab.get(&format!("{prefix}{piece}"))
+                        } else {
+                            self.vocab.get(piece)
+                        };
+                        if let Some(&id) = candidate {
+                            found = Some(id);
+                            break;
+                        }
+                        end -= piece.chars().next_back().map_or(1, char::len_utf8);
+                    }
+                    let Some(id) = found else {
+                        // Parola non scomponibile: un solo token sconosciuto.
+                        ids.push(*unk_id);
+                        return Ok(());
+                    };
+                    pieces.push(id);
+                    start = end;
+                }
+                ids.extend(pieces);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applica i merge BPE in ordine di rank alla parola.
+    fn bpe(&self, word: &str, merges: &HashMap<(u32, u32), (usize, u32)>) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = word
+            .chars()
+            .map(|c| {
+                let text = c.to_string();
+                self.vocab
+                    .get(&text)
+                    .map_or(Symbol::Unknown(text), |&id| Symbol::Known(id))
+            })
+            .collect();
+
+        loop {
+            let best = symbols
+                .windows(2)
+                .enumerate()
+                .filter_map(|(i, pair)| match pair {
+                    [Symbol::Known(l), Symbol::Known(r)] => merges
+                        .get(&(*l, *r))
+                        .map(|&(rank, merged)| (rank, i, merged)),
+                    _ => None,
+                })
+                .min();
+            let Some((_, i, merged_id)) = best else {
+                return symbols;
+            };
+            symbols[i] = Symbol::Known(merged_id);
+            symbols.remove(i + 1);
+        }
+    }
+}
+
+/// Ricava lowercase e pre-tokenizzazione da `normalizer` e `pre_tokenizer`.
+///
+/// Senza un pre-tokenizer esplicito, un normalizer che sostituisce gli
+/// spazi con `▁` (stile Llama) equivale a `Metaspace`.
+fn parse_pipeline(
+    normalizer: Option<&Value>,
+    pre_tokenizer: Option<&Value>,
+) -> (bool, PreTokenizer) {
+    let mut lowercase = false;
+    let mut metaspace_normalizer = false;
+    let mut prepend_normalizer = false;
+    if let Some(normalizer) = normalizer {
+        visit_components(
+            normalizer,
+            "normalizers",
+            &mut |component| match component["type"].as_str() {
+                Some("Lowercase") => lowercase = true,
+                Some("BertNormalizer") => {
+                    lowercase |= component["lowercase"].as_bool().unwrap_or(true);
+                }
+                Some("Replace") => {
+                    metaspace_normalizer |= component["content"] == METASPACE.to_string();
+                }
+                Some("Prepend") => prepend_normalizer = true,
+                _ => {}
+            },
+        );
+    }
+
+    let mut pre_tokenizer_kind = None;
+    if let Some(value) = pre_tokenizer {
+        visit_components(value, "pretokenizers", &mut |component| {
+            let parsed = match component["type"].as_str() {
+                Some("ByteLevel") => PreTokenizer::ByteLevel {
+                    add_prefix_space: component["add_prefix_space"].as_bool().unwrap_or(true),
+                },
+                Some("Metaspace") => PreTokenizer::Metaspace {
+                    add_prefix_space: component["add_prefix_space"]
+                        .as_bool()
+                        .unwrap_or_else(|| component["prepend_scheme"] != "never"),
+                },
+                _ => return,
+            };
+            pre_tokenizer_kind.get_or_insert(parsed);
+        });
+    }
+    let resolved = pre_tokenizer_kind.unwrap_or(if metaspace_normalizer {
+        PreTokenizer::Metaspace {
+            add_prefix_space: prepend_normalizer,
+        }
+    } else {
+        PreTokenizer::Whitespace
+    });
+
+    (lowercase, resolved)
+}
+
+/// Visita un componente e, se è una `Sequence`, i suoi figli in `key`.
+fn visit_components(value: &Value, key: &str, f: &mut impl FnMut(&Value)) {
+    if value["type"] == "Sequence" {
+        if let Some(children) = value[key].as_array() {
+            for child in children {
+                visit_components(child, key, f);
+            }
+        }
+    } else {
+        f(value);
+    }
+}
+
+/// Alfabeto byte-level di GPT-2: ogni byte è mappato su un carattere
+/// stampabile, così i token non contengono spazi o caratteri di controllo.
+fn byte_level_alphabet() -> [char; 256] {
+    let mut table = ['\0'; 256];
+    let mut next_free = 256_u32;
+    for b in 0..=u8::MAX {
+        let printable = matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+        let code = if printable {
+            u32::from(b)
+        } else {
+            next_free += 1;
+            next_free - 1
+        };
+        table[usize::from(b)] = char::from_u32(code).unwrap_or('\u{FFFD}');
+    }
+    table
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_alphabetic() {
+        CharClass::Letter
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else {
+        CharClass::Other
+    }
+}
+
+/// Pre-tokenizzazione in stile GPT-2: lettere, cifre e punteggiatura
+/// formano parole distinte, ciascuna preceduta dall'eventuale spazio.
+fn split_byte_level(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map_or(text.len(), |&(pos, _)| pos);
+    let mut words = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i].1;
+        if c.is_whitespace() {
+            let next_is_word = chars.get(i + 1).is_some_and(|&(_, n)| !n.is_whitespace());
+            if c == ' ' && next_is_word {
+                i += 1;
+            } else {
+                let mut end = i;
+                while end < chars.len() && chars[end].1.is_whitespace() {
+                    end += 1;
+                }
+                // L'ultimo spazio di una sequenza va con la parola successiva.
+                if end < chars.len() && end - 1 > i && chars[end - 1].1 == ' ' {
+                    end -= 1;
+                }
+                words.push(&text[offset(start)..offset(end)]);
+                i = end;
+                continue;
+            }
+        }
+
+        let class = char_class(chars[i].1);
+        while i < chars.len() && !chars[i].1.is_whitespace() && char_class(chars[i].1) == class {
+            i += 1;
+        }
+        words.push(&text[offset(start)..offset(i)]);
+    }
+    words
+}
+
+/// Pre-tokenizzazione in stile BERT: spazi come separatori, ogni carattere
+/// di punteggiatura è una parola a sé.
+fn split_whitespace_punctuation(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for chunk in text.split_whitespace() {
+        let mut start = 0;
+        for (pos, c) in chunk.char_indices() {
+            if c.is_ascii_punctuation() || (!c.is_alphanumeric() && !c.is_whitespace()) {
+                if start < pos {
+                    words.push(&chunk[start..pos]);
+                }
+                words.push(&chunk[pos..pos + c.len_utf8()]);
+                start = pos + c.len_utf8();
+            }
+        }
+        if start < chunk.len() {
+            words.push(&chunk[start..]);
+        }
+    }
+    words
+}
+
+/// Riconosce i token di byte fallback nella forma `<0xAB>`.
+fn parse_byte_token(token: &str) -> Option<u8> {
+    let hex = token.strip_prefix("<0x")?.strip_suffix('>')?;
+    if hex.len() == 2 {
+        u8::from_str_radix(hex, 16).ok()
+    } else {
+        None
+    }
+}
+
+/// Ricompone i sotto-token `WordPiece`, senza spazio prima della
+/// punteggiatura.
+fn decode_word_piece(pieces: &[(&str, bool)], prefix: &str) -> String {
+    let mut text = String::new();
+    for &(piece, _) in pieces {
+        if let Some(rest) = piece.strip_prefix(prefix).filter(|_| !text.is_empty()) {
+            text.push_str(rest);
+            continue;
+        }
+        let is_punctuation = piece.chars().all(|c| c.is_ascii_punctuation());
+        if !text.is_empty() && !is_punctuation {
+            text.push(' ');
+        }
+        text.push_str(piece);
+    }
+    text
+}
+
+/// Pre-tokenizzazione `Metaspace`: ogni parola inizia con il separatore.
+fn split_metaspace(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (pos, _) in text.match_indices(METASPACE) {
+        if pos > start {
+            parts.push(&text[start..pos]);
+        }
+        start = pos;
+    }
+    if start < text.len() {
+        parts.push(&text[start..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// BPE byte-level giocattolo che conosce "hello" e " world".
+    fn gpt2_like() -> Tokenizer {
+        let vocab: HashMap<&str, u32> = [
+            "h", "e", "l", "o", "Ġ", "w", "r", "d", "he", "ll", "hell", "hello", "Ġw", "or",
+            "Ġwor", "Ġworl", "Ġworld",
+        ]
+        .into_iter()
+        .zip(0..)
+        .collect();
+        let raw = json!({
+            "added_tokens": [{ "id": 17, "content": "<|endoftext|>", "special": true }],
+            "pre_tokenizer": { "type": "ByteLevel", "add_prefix_space": false },
+            "model": {
+                "type": "BPE",
+                "vocab": vocab,
+                "merges": ["h e", "l l", "he ll", "hell o", "Ġ w", "o r", "Ġw or", ["Ġwor", "l"], ["Ġworl", "d"]],
+            }
+        });
+        Tokenizer::from_json(&raw.to_string()).unwrap()
+    }
+
+    fn bert_like() -> Tokenizer {
+        let raw = json!({
+            "added_tokens": [
+                { "id": 0, "content": "[UNK]", "special": true },
+                { "id": 1, "content": "[SEP]", "special": true },
+            ],
+            "normalizer": { "type": "BertNormalizer", "lowercase": true },
+            "pre_tokenizer": { "type": "BertPreTokenizer" },
+            "model": {
+                "type": "WordPiece",
+                "unk_token": "[UNK]",
+                "vocab": { "[UNK]": 0, "[SEP]": 1, "play": 2, "##ing": 3, "the": 4, "game": 5, "!": 6 },
+            }
+        });
+        Tokenizer::from_json(&raw.to_string()).unwrap()
+    }
+
+    #[test]
+    fn byte_level_bpe_applies_merges_and_roundtrips() {
+        let tokenizer = gpt2_like();
+        let ids = tokenizer.encode("hello world").unwrap();
+
+        assert_eq!(ids, vec![11, 16]);
+        assert_eq!(tokenizer.decode(&ids, true).unwrap(), "hello world");
+        assert_eq!(tokenizer.vocab_size(), 18);
+    }
+
+    #[test]
+    fn added_tokens_are_matched_and_skipped_on_decode() {
+        let tokenizer = gpt2_like();
+        let ids = tokenizer.encode("hello<|endoftext|>").unwrap();
+
+        assert_eq!(ids, vec![11, 17]);
+        assert_eq!(tokenizer.eos_token_id(), Some(17));
+        assert_eq!(tokenizer.decode(&ids, true).unwrap(), "hello");
+        assert_eq!(tokenizer.decode(&ids, false).unwrap(), "hello<|endoftext|>");
+    }
+
+    #[test]
+    fn bpe_without_unk_rejects_unknown_symbols() {
+        assert!(gpt2_like().encode("hello!").is_err());
+    }
+
+    #[test]
+    fn word_piece_splits_subwords_and_lowercases() {
+        let tokenizer = bert_like();
+        let ids = tokenizer.encode("Playing the GAME!").unwrap();
+
+        assert_eq!(ids, vec![2, 3, 4, 5, 6]);
+        assert_eq!(tokenizer.decode(&ids, true).unwrap(), "playing the game!");
+        assert_eq!(tokenizer.encode("chess").unwrap(), vec![0]);
+        assert_eq!(tokenizer.eos_token_id(), Some(1));
+    }
+
+    #[test]
+    fn metaspace_with_byte_fallback_roundtrips() {
+        let mut vocab: HashMap<String, u32> = HashMap::new();
+        for (id, token) in ["<unk>", "▁", "c", "i", "a", "o", "ci", "ao", "▁ciao", "▁ci"]
+            .into_iter()
+            .enumerate()
+        {
+            vocab.insert(token.to_owned(), u32::try_from(id).unwrap());
+        }
+        for b in 0..=u8::MAX {
+            let id = u32::try_from(vocab.len()).unwrap();
+            vocab.insert(format!("<0x{b:02X}>"), id);
+        }
+        let raw = json!({
+            "pre_tokenizer": { "type": "Metaspace", "replacement": "▁", "prepend_scheme": "always" },
+            "model": {
+                "type": "BPE",
+                "vocab": vocab,
+                "merges": ["c i", "a o", "▁ ci", "▁ci ao"],
+                "unk_token": "<unk>",
+                "byte_fallback": true,
+            }
+        });
+        let tokenizer = Tokenizer::from_json(&raw.to_string()).unwrap();
+
+        let ids = tokenizer.encode("ciao è").unwrap();
+        // "▁ciao", "▁", poi i due byte UTF-8 di "è".
+        assert_eq!(ids[..2], [8, 1]);
+        assert_eq!(ids.len(), 4);
+        assert_eq!(tokenizer.decode(&ids, true).unwrap(), "ciao è");
+    }
+
+    #[test]
+    fn byte_level_fallback_encodes_utf8_bytes() {
+        let tokenizer = Tokenizer::byte_level();
+        let ids = tokenizer.encode("però").unwrap();
+
+        assert_eq!(ids.len(), 5);
+        assert_eq!(tokenizer.vocab_size(), 256);
+        assert_eq!(tokenizer.decode(&ids, true).unwrap(), "però");
+        assert!(tokenizer.decode(&[300], true).is_err());
+    }
+
+    #[test]
+    fn load_for_model_reads_sibling_file_or_falls_back() {
+        let dir =
+            std::env::temp_dir().join(format!("samaritan-tokenizer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model_path = dir.join("model.safetensors");
+
+        let fallback = Tokenizer::load_for_model(&model_path).unwrap();
+        assert_eq!(fallback.vocab_size(), 256);
+
+        std::fs::write(dir.join(TOKENIZER_FILE), "{ not json").unwrap();
+        assert!(Tokenizer::load_for_model(&model_path).is_err());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn byte_level_split_attaches_spaces_to_words() {
+        assert_eq!(
+            split_byte_level("hi  there, 42!"),
+            vec!["hi", " ", " there", ",", " 42", "!"]
+        );
+        assert_eq!(split_byte_level("a\nb"), vec!["a", "\n", "b"]);
+    }
+}
@@ -3,16 +3,27 @@
 use anyhow::Result;
 use std::path::PathBuf;
 
+/// Agent per aggiornamenti binari (download, verifica, switch).
 #[derive(Debug)]
 pub struct UpdateAgent {
     _data_dir: PathBuf,
 }
 
 impl UpdateAgent {
-    pub fn new(data_dir: PathBuf) -> Self {
-        Self { _data_dir: data_dir }
+    /// Crea un agent che lavora sotto `data_dir`.
+    #[must_use]
+    pub const fn new(data_dir: PathBuf) -> Self {
+        Self {
+            _data_dir: data_dir,
+        }
     }
 
+    /// Controlla se sono disponibili aggiornamenti binari.
+    ///
+    /// # Errors
+    ///
+    /// Ritorna errore se il controllo fallisce.
+    #[allow(clippy::unused_async)]
     pub async fn check_for_updates(&self) -> Result<()> {
         Ok(())
     }
@@ -0,0 +1,142 @@
+//! Helper condivisi dagli integration test: un backend di inferenza finto e
+//! configurabile e le data dir temporanee.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use samaritan_core::clock::ManualClock;
+use samaritan_core::neural_engine::{DType, InferenceBackend, Tensor, TensorSpec};
+use samaritan_core::tokenizer::Tokenizer;
+
+/// Data dir univoca sotto la directory temporanea di sistema.
+pub fn temp_data_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("samaritan-it-{}", uuid::Uuid::new_v4()))
+}
+
+/// Tokenizer con le sole lettere `a`, `b`, `c` e lo spazio, adatto a
+/// [`FakeBackend::cyclic`] con un vocabolario di 4 token.
+pub fn letters() -> Tokenizer {
+    Tokenizer::from_json(r#"{"model":{"type":"BPE","vocab":{"a":0,"b":1,"c":2,"▁":3}}}"#).unwrap()
+}
+
+/// Come [`FakeBackend`] sceglie il prossimo token.
+enum Next {
+    /// Sempre lo stesso token.
+    Fixed(usize),
+    /// Il token successivo all'ultimo del contesto, modulo il vocabolario.
+    Cyclic,
+    /// Il byte dello script all'indice `calls` (spazi dopo la fine).
+    Script(&'static str),
+}
+
+/// Backend finto e deterministico: conta le inferenze e può simulare
+/// un'inferenza lenta o un backend guasto.
+///
+/// Con un orologio simulato il ritardo fa avanzare l'orologio invece di
+/// dormire.
+pub struct FakeBackend {
+    /// Inferenze eseguite, warmup del bootstrap compreso.
+    pub calls: Arc<AtomicUsize>,
+    /// Durata di ogni inferenza, in millisecondi.
+    pub delay_ms: Arc<AtomicU64>,
+    /// Se vero, ogni inferenza fallisce.
+    pub broken: Arc<AtomicBool>,
+    vocab: usize,
+    next: Next,
+    clock: Option<ManualClock>,
+    inputs: Vec<TensorSpec>,
+    outputs: Vec<TensorSpec>,
+}
+
+impl FakeBackend {
+    /// Backend con un vocabolario di `vocab` token che genera sempre `token`.
+    pub fn fixed(vocab: usize, token: usize) -> Self {
+        Self::new(vocab, Next::Fixed(token))
+    }
+
+    /// Backend che scrive "abc abc…" con [`letters`]: il prossimo token è
+    /// sempre `(ultimo + 1) % vocab`.
+    pub fn cyclic(vocab: usize) -> Self {
+        Self::new(vocab, Next::Cyclic)
+    }
+
+    /// Backend byte-level che scrive `script` un byte per inferenza; lo script
+    /// riparte azzerando `calls`.
+    pub fn scripted(script: &'static str) -> Self {
+        Self::new(256, Next::Script(script))
+    }
+
+    fn new(vocab: usize, next: Next) -> Self {
+        Self {
+            calls: Arc::default(),
+            delay_ms: Arc::default(),
+            broken: Arc::default(),
+            vocab,
+            next,
+            clock: None,
+            inputs: vec![TensorSpec::new(
+                "input_ids",
+                DType::I64,
+                vec![Some(1), None],
+            )],
+            outputs: vec![TensorSpec::new(
+                "logits",
+                DType::F32,
+                vec![Some(1), Some(vocab)],
+            )],
+        }
+    }
+
+    /// Fa avanzare `clock` del ritardo invece di dormire.
+    pub fn with_clock(mut self, clock: ManualClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+}
+
+impl InferenceBackend for FakeBackend {
+    fn load(path: &Path) -> Result<Self> {
+        bail!(
+            "FakeBackend is built in memory, not loaded from {}",
+            path.display()
+        )
+    }
+
+    fn input_schema(&self) -> &[TensorSpec] {
+        &self.inputs
+    }
+
+    fn output_schema(&self) -> &[TensorSpec] {
+        &self.outputs
+    }
+
+    fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+        if self.broken.load(Ordering::SeqCst) {
+            bail!("backend unavailable");
+        }
+        let step = self.calls.fetch_add(1, Ordering::SeqCst);
+        let delay = Duration::from_millis(self.delay_ms.load(Ordering::SeqCst));
+        match &self.clock {
+            Some(clock) => clock.advance(delay),
+            None => std::thread::sleep(delay),
+        }
+        let mut logits = vec![0.0; self.vocab];
+        let token = match self.next {
+            Next::Fixed(token) => token,
+            Next::Cyclic => {
+                let last = inputs[0].as_i64().unwrap().last().copied();
+                last.map_or(0, |last| (usize::try_from(last).unwrap() + 1) % self.vocab)
+            }
+            Next::Script(script) => {
+                usize::from(script.as_bytes().get(step).copied().unwrap_or(b' '))
+            }
+        };
+        logits[token] = 1.0;
+        Ok(vec![Tensor::f32("logits", vec![1, self.vocab], logits)])
+    }
+}
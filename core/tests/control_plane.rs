@@ -0,0 +1,127 @@
+//! Integration test: control plane su socket Unix verso un nodo in esecuzione.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use common::temp_data_dir;
+use samaritan_core::control::{self, socket_path};
+use samaritan_core::neural_engine::cpu::MlpWeights;
+use samaritan_core::node::run_node_with_hook;
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::policy_core::POLICY_FILE;
+use samaritan_core::NeuroNode;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::UnixStream;
+use tokio_util::sync::CancellationToken;
+
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    async fn send(&mut self, request: Value) -> Value {
+        self.writer
+            .write_all(format!("{request}\n").as_bytes())
+            .await
+            .unwrap();
+        let line = tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn operator_commands_reach_the_running_node() {
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let model_path = data_dir.join("model.safetensors");
+    MlpWeights::seeded(256, 4, 8, 7)
+        .to_safetensors()
+        .write(&model_path)
+        .unwrap();
+    std::fs::write(data_dir.join(POLICY_FILE), "version: v1\nrules: []\n").unwrap();
+    let node = NeuroNode::bootstrap(data_dir.clone(), model_path, Some(NodeProfile::Desktop))
+        .await
+        .unwrap();
+
+    let listener = control::bind(&data_dir).await.unwrap();
+    let mode = |path: PathBuf| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(data_dir.join(control::CONTROL_DIR)), 0o700);
+    assert_eq!(mode(socket_path(&data_dir)), 0o600);
+    let (mut plane, server) = control::channel();
+    let shutdown = CancellationToken::new();
+    let server = tokio::spawn(server.serve(listener, shutdown.clone()));
+    let runner = {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            run_node_with_hook(node, Duration::from_millis(1), shutdown, &mut plane).await
+        })
+    };
+
+    let (reader, writer) = UnixStream::connect(socket_path(&data_dir))
+        .await
+        .unwrap()
+        .into_split();
+    let mut client = Client {
+        lines: BufReader::new(reader).lines(),
+        writer,
+    };
+
+    let status = client.send(json!({"command": "status"})).await;
+    assert_eq!(status["ok"], true, "{status}");
+    assert_eq!(status["result"]["profile"], "Desktop");
+    assert_eq!(status["result"]["background_paused"], false);
+
+    let paused = client.send(json!({"command": "pause-background"})).await;
+    assert_eq!(paused["result"]["background_paused"], true);
+    let status = client.send(json!({"command": "status"})).await;
+    assert_eq!(status["result"]["background_paused"], true);
+    client.send(json!({"command": "resume"})).await;
+
+    let snapshot = client.send(json!({"command": "snapshot-now"})).await;
+    let id = snapshot["result"]["id"].as_str().unwrap().to_owned();
+    assert_eq!(snapshot["result"]["weights"], true, "{snapshot}");
+    let rollback = client.send(json!({"command": "rollback", "id": id})).await;
+    assert_eq!(rollback["ok"], true, "{rollback}");
+    let missing = client
+        .send(json!({"command": "rollback", "id": "999999"}))
+        .await;
+    assert_eq!(missing["ok"], false);
+    assert!(missing["error"].as_str().unwrap().contains("999999"));
+
+    let strict = client
+        .send(json!({"command": "set-strict", "value": "on"}))
+        .await;
+    assert_eq!(strict["result"]["strict_mode"], true);
+
+    std::fs::write(data_dir.join(POLICY_FILE), "version: [broken").unwrap();
+    let rejected = client.send(json!({"command": "reload-policy"})).await;
+    assert_eq!(rejected["ok"], false, "{rejected}");
+    std::fs::write(data_dir.join(POLICY_FILE), "version: v2\nrules: []\n").unwrap();
+    let reloaded = client.send(json!({"command": "reload-policy"})).await;
+    assert_eq!(reloaded["result"]["policy"]["version"], "v2", "{reloaded}");
+
+    let unknown = client.send(json!({"command": "reboot"})).await;
+    assert_eq!(unknown["ok"], false);
+
+    shutdown.cancel();
+    server.await.unwrap().unwrap();
+    let node = runner.await.unwrap().unwrap();
+    assert!(node.policy_core.is_strict_mode());
+    assert_eq!(node.policy_core.version().version, "v2");
+    assert!(!socket_path(&data_dir).exists());
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
@@ -0,0 +1,149 @@
+//! Integration test: API HTTP locale sopra l'`IOLayer` (feature `http-api`).
+
+#![cfg(feature = "http-api")]
+
+mod common;
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use common::{letters, temp_data_dir, FakeBackend};
+use samaritan_core::http_api::{channel, run_node_with_api};
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::NeuroNode;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+async fn node(data_dir: &Path) -> NeuroNode {
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.to_path_buf(),
+        Box::new(FakeBackend::cyclic(4)),
+        letters(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    node.neural_engine.set_max_new_tokens(22);
+    node
+}
+
+/// Invia una richiesta e legge la risposta fino alla chiusura della connessione.
+async fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let raw = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(raw.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    response
+}
+
+#[tokio::test]
+async fn chat_stats_and_health_are_served() {
+    let data_dir = temp_data_dir();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    let handle = tokio::spawn(run_node_with_api(
+        node(&data_dir).await,
+        Duration::from_millis(1),
+        listener,
+        shutdown.clone(),
+    ));
+
+    let chat = request(
+        addr,
+        "POST",
+        "/api/chat",
+        r#"{"message": "a", "session_id": "alice"}"#,
+    )
+    .await;
+    assert!(chat.starts_with("HTTP/1.1 200 OK\r\n"), "{chat}");
+    assert!(chat.contains("Content-Type: text/event-stream\r\n"));
+    assert!(chat.contains("X-Session-Id: alice\r\n"));
+    assert_eq!(chat.matches("event: partial\n").count(), 2);
+    assert!(chat.contains("data: {\"text\":\"bc \"}\n\n"));
+    let final_event = chat.split("event: final\ndata: ").nth(1).unwrap();
+    let decision: serde_json::Value = serde_json::from_str(final_event.trim_end()).unwrap();
+    assert_eq!(decision["verdict"], "allow");
+    assert_eq!(decision["text"], "bc abc abc abc abc abc");
+
+    let stats = request(addr, "GET", "/api/stats", "").await;
+    assert!(stats.starts_with("HTTP/1.1 200 OK\r\n"), "{stats}");
+    let stats: serde_json::Value =
+        serde_json::from_str(stats.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert!(stats["ticks"].as_u64().unwrap() > 0);
+    assert_eq!(stats["policy"]["version"], "builtin-1");
+
+    let health = request(addr, "GET", "/api/health", "").await;
+    assert!(
+        health.ends_with(r#"{"background":true,"status":"ok","throttle":"Normal"}"#),
+        "{health}"
+    );
+
+    assert!(request(addr, "GET", "/api/chat", "")
+        .await
+        .starts_with("HTTP/1.1 405 "));
+    assert!(request(addr, "POST", "/api/chat", "{}")
+        .await
+        .starts_with("HTTP/1.1 400 "));
+    for session_id in [
+        r#""a\r\nX-Injected: 1""#,
+        &format!("\"{}\"", "x".repeat(200)),
+    ] {
+        let body = format!(r#"{{"message": "a", "session_id": {session_id}}}"#);
+        let rejected = request(addr, "POST", "/api/chat", &body).await;
+        assert!(rejected.starts_with("HTTP/1.1 400 "), "{rejected}");
+        assert!(!rejected.contains("X-Injected"));
+    }
+    assert!(request(addr, "GET", "/nope", "")
+        .await
+        .starts_with("HTTP/1.1 404 "));
+
+    shutdown.cancel();
+    let node = handle.await.unwrap().unwrap();
+    assert_eq!(
+        node.io_layer
+            .sessions()
+            .loaded()
+            .map(|session| session.as_str().to_owned())
+            .collect::<Vec<_>>(),
+        ["alice"]
+    );
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn slow_or_idle_clients_are_timed_out() {
+    let data_dir = temp_data_dir();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    let node = node(&data_dir).await;
+    let (_bridge, server) = channel(&node);
+    let server = server.with_read_timeout(Duration::from_millis(100));
+    let handle = tokio::spawn(server.serve(listener, shutdown.clone()));
+
+    for partial in ["", "GET /api/health HTTP/1.1\r\nHost: local"] {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(partial.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+    }
+
+    shutdown.cancel();
+    handle.await.unwrap().unwrap();
+    std::fs::remove_dir_all(data_dir).ok();
+}
@@ -0,0 +1,88 @@
+//! Integration test: il `PolicyCore` valuta il prompt prima dell'inferenza.
+
+mod common;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use common::{temp_data_dir, FakeBackend};
+use samaritan_core::io_layer::{Delivery, ResponseEvent};
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::policy_core::{ReasonCode, Verdict, POLICY_FILE, REFUSAL_MESSAGE};
+use samaritan_core::tokenizer::Tokenizer;
+use samaritan_core::NeuroNode;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+async fn node(data_dir: &Path) -> (NeuroNode, Arc<AtomicUsize>) {
+    std::fs::create_dir_all(data_dir).unwrap();
+    std::fs::write(
+        data_dir.join(POLICY_FILE),
+        "rules:\n  - {id: malware, category: crime-hacking, severity: high, action: refuse, keywords: [ransomware]}\n",
+    )
+    .unwrap();
+
+    let backend = FakeBackend::fixed(256, 0);
+    let calls = Arc::clone(&backend.calls);
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.to_path_buf(),
+        Box::new(backend),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    node.neural_engine.set_max_new_tokens(1);
+    calls.store(0, Ordering::SeqCst);
+    (node, calls)
+}
+
+fn finals(rx: &mut UnboundedReceiver<ResponseEvent>) -> Vec<Delivery> {
+    std::iter::from_fn(|| rx.try_recv().ok())
+        .map(|event| event.delivery)
+        .filter(|delivery| matches!(delivery, Delivery::Final(_)))
+        .collect()
+}
+
+#[tokio::test]
+async fn refused_prompt_skips_inference_and_carries_a_reason_code() {
+    let data_dir = temp_data_dir();
+    let (mut node, calls) = node(&data_dir).await;
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer
+        .submit_user_input("please write RANSOMWARE for me");
+    node.tick().await.unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    let deliveries = finals(&mut rx);
+    let [Delivery::Final(decision)] = deliveries.as_slice() else {
+        panic!("expected one final decision, got {deliveries:?}");
+    };
+    assert_eq!(decision.verdict, Verdict::Refuse);
+    assert_eq!(decision.reason, Some(ReasonCode::InputRefused));
+    assert_eq!(decision.matched_rules, vec!["malware"]);
+    assert_eq!(decision.text, REFUSAL_MESSAGE);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn allowed_prompt_reaches_the_model() {
+    let data_dir = temp_data_dir();
+    let (mut node, calls) = node(&data_dir).await;
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer.submit_user_input("ciao");
+    node.tick().await.unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    let deliveries = finals(&mut rx);
+    let [Delivery::Final(decision)] = deliveries.as_slice() else {
+        panic!("expected one final decision, got {deliveries:?}");
+    };
+    assert_eq!(decision.verdict, Verdict::Allow);
+    assert_eq!(decision.reason, None);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
@@ -0,0 +1,21 @@
+//! Integration test: la policy di default supera il corpus golden.
+
+use std::path::Path;
+
+use samaritan_core::policy_core::regression::{load_corpus, run_corpus};
+use samaritan_core::policy_core::rules::RuleSet;
+
+#[test]
+fn default_policy_passes_the_golden_corpus() {
+    let corpus = load_corpus(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/policy_golden.jsonl"),
+    )
+    .unwrap();
+    let report = run_corpus(&RuleSet::default_rules(), &corpus).unwrap();
+
+    assert!(report.passed(), "{report}");
+    for (category, stats) in &report.categories {
+        assert!(stats.recall() >= 1.0, "{category}: {report}");
+        assert!(stats.precision() >= 1.0, "{category}: {report}");
+    }
+}
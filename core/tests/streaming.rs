@@ -0,0 +1,298 @@
+//! Integration test: risposte in streaming consegnate incrementalmente e
+//! interrotte dal `PolicyCore`, instradate alla sessione di origine.
+
+mod common;
+
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use common::{letters, temp_data_dir, FakeBackend};
+use samaritan_core::io_layer::{Delivery, ResponseEvent, SessionId};
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::policy_core::{PolicyDecision, ReasonCode, Verdict, POLICY_FILE};
+use samaritan_core::tokenizer::Tokenizer;
+use samaritan_core::NeuroNode;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Risposta con un numero di carta valido (Luhn) generato a gruppi.
+const SCRIPT: &str = "Pay with 4111 1111 1111 1111 and then we are done.";
+
+async fn node(data_dir: &Path) -> NeuroNode {
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.to_path_buf(),
+        Box::new(FakeBackend::cyclic(4)),
+        letters(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    node.neural_engine.set_max_new_tokens(22);
+    node
+}
+
+fn drain(rx: &mut UnboundedReceiver<ResponseEvent>) -> Vec<Delivery> {
+    std::iter::from_fn(|| rx.try_recv().ok())
+        .map(|event| event.delivery)
+        .collect()
+}
+
+#[tokio::test]
+async fn partial_output_is_delivered_before_the_final_decision() {
+    let data_dir = temp_data_dir();
+    let mut node = node(&data_dir).await;
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer.submit_user_input("a");
+    node.tick().await.unwrap();
+
+    let deliveries = drain(&mut rx);
+    let partials: String = deliveries
+        .iter()
+        .filter_map(|delivery| match delivery {
+            Delivery::Partial(text) => Some(text.as_str()),
+            Delivery::Final(_) | Delivery::Overloaded(_) => None,
+        })
+        .collect();
+    // La policy di default ha keyword di quattro parole: le ultime quattro
+    // restano trattenute fino alla decisione finale.
+    assert_eq!(deliveries.len(), 3);
+    assert_eq!(partials, "bc abc ");
+    assert_eq!(
+        deliveries.last(),
+        Some(&Delivery::Final(PolicyDecision {
+            policy: Some(node.policy_core.version().clone()),
+            ..PolicyDecision::allow("bc abc abc abc abc abc")
+        }))
+    );
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn policy_cuts_the_stream_mid_generation() {
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join(POLICY_FILE),
+        "rules:\n  - {id: no-ca, category: crime-hacking, severity: high, action: refuse, regex: ['c a']}\n",
+    )
+    .unwrap();
+    let mut node = node(&data_dir).await;
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer.submit_user_input("a");
+    node.tick().await.unwrap();
+
+    let deliveries = drain(&mut rx);
+    assert_eq!(deliveries[0], Delivery::Partial("bc ".to_owned()));
+    let Delivery::Final(decision) = &deliveries[1] else {
+        panic!("expected final decision, got {:?}", deliveries[1]);
+    };
+    assert_eq!(decision.verdict, Verdict::Refuse);
+    assert_eq!(decision.matched_rules, vec!["no-ca"]);
+    assert_eq!(deliveries.len(), 2);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn redaction_withholds_partials_until_the_final_decision() {
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join(POLICY_FILE),
+        "rules:\n  - {id: no-ab, category: pii, severity: low, action: redact, regex: [ab]}\n",
+    )
+    .unwrap();
+    let mut node = node(&data_dir).await;
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer.submit_user_input("a");
+    node.tick().await.unwrap();
+
+    let deliveries = drain(&mut rx);
+    assert_eq!(deliveries[0], Delivery::Partial("bc ".to_owned()));
+    let Delivery::Final(decision) = &deliveries[1] else {
+        panic!("expected final decision, got {:?}", deliveries[1]);
+    };
+    assert_eq!(decision.verdict, Verdict::Redact);
+    assert!(decision.text.starts_with("bc [REDACTED]c [REDACTED]c"));
+    assert_eq!(deliveries.len(), 2);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn card_numbers_never_reach_the_client_in_partials() {
+    let data_dir = temp_data_dir();
+    let backend = FakeBackend::scripted(SCRIPT);
+    let step = Arc::clone(&backend.calls);
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(backend),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    node.neural_engine.set_max_new_tokens(SCRIPT.len());
+    step.store(0, Ordering::SeqCst);
+    let mut rx = node.io_layer.subscribe_responses();
+
+    node.io_layer.submit_user_input("pay");
+    node.tick().await.unwrap();
+
+    let deliveries = drain(&mut rx);
+    let partials: String = deliveries
+        .iter()
+        .filter_map(|delivery| match delivery {
+            Delivery::Partial(text) => Some(text.as_str()),
+            Delivery::Final(_) | Delivery::Overloaded(_) => None,
+        })
+        .collect();
+    assert!(partials.starts_with("Pay "), "{partials:?}");
+    assert!(
+        !partials.contains(|c: char| c.is_ascii_digit()),
+        "{partials:?}"
+    );
+    let Some(Delivery::Final(decision)) = deliveries.last() else {
+        panic!("expected final decision, got {deliveries:?}");
+    };
+    assert_eq!(decision.verdict, Verdict::Redact);
+    assert_eq!(decision.matched_rules, vec!["pii-card-number"]);
+    assert!(!decision.text.contains("4111"));
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn inference_failures_are_delivered_without_stopping_the_node() {
+    let data_dir = temp_data_dir();
+    let backend = FakeBackend::cyclic(4);
+    let broken = Arc::clone(&backend.broken);
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(backend),
+        letters(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    let alice = SessionId::new("alice");
+    let mut rx = node.io_layer.subscribe_session(alice.clone());
+
+    broken.store(true, Ordering::SeqCst);
+    node.io_layer.submit_session_input(alice.clone(), "a");
+    node.tick().await.unwrap();
+
+    let Some(Delivery::Final(decision)) = drain(&mut rx).pop() else {
+        panic!("expected a final decision");
+    };
+    assert!(decision.is_blocked());
+    assert_eq!(decision.reason, Some(ReasonCode::InferenceFailed));
+    assert!(node
+        .io_layer
+        .sessions_mut()
+        .history(&alice)
+        .await
+        .turns
+        .is_empty());
+
+    broken.store(false, Ordering::SeqCst);
+    node.io_layer.submit_session_input(alice.clone(), "a");
+    node.tick().await.unwrap();
+    let Some(Delivery::Final(decision)) = drain(&mut rx).pop() else {
+        panic!("expected a final decision");
+    };
+    assert!(decision.is_allowed());
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn unencodable_prompts_fail_only_their_session() {
+    let data_dir = temp_data_dir();
+    let mut node = node(&data_dir).await;
+    let alice = SessionId::new("alice");
+    let bob = SessionId::new("bob");
+    let mut alice_rx = node.io_layer.subscribe_session(alice.clone());
+    let mut bob_rx = node.io_layer.subscribe_session(bob.clone());
+
+    // `z` non è nel vocabolario e il tokenizer non ha `unk`.
+    node.io_layer.submit_session_input(alice.clone(), "z");
+    node.io_layer.submit_session_input(bob.clone(), "a");
+    for _ in 0..2 {
+        node.tick().await.unwrap();
+    }
+
+    let Some(Delivery::Final(decision)) = drain(&mut alice_rx).pop() else {
+        panic!("expected a final decision for alice");
+    };
+    assert_eq!(decision.reason, Some(ReasonCode::InferenceFailed));
+    let Some(Delivery::Final(decision)) = drain(&mut bob_rx).pop() else {
+        panic!("expected a final decision for bob");
+    };
+    assert!(decision.is_allowed());
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn bootstrap_rejects_a_tokenizer_larger_than_the_model() {
+    let data_dir = temp_data_dir();
+    let Err(err) = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(FakeBackend::cyclic(4)),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    else {
+        panic!("bootstrap must fail");
+    };
+
+    assert!(
+        err.to_string().contains("larger than the model output"),
+        "{err:#}"
+    );
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn responses_are_routed_to_the_originating_session() {
+    let data_dir = temp_data_dir();
+    let mut node = node(&data_dir).await;
+    let alice = SessionId::new("alice");
+    let bob = SessionId::new("bob");
+    let mut alice_rx = node.io_layer.subscribe_session(alice.clone());
+    let mut bob_rx = node.io_layer.subscribe_session(bob.clone());
+
+    node.io_layer.submit_session_input(alice.clone(), "a");
+    node.io_layer.submit_session_input(bob.clone(), "b");
+    for _ in 0..4 {
+        node.tick().await.unwrap();
+    }
+
+    let version = node.policy_core.version().clone();
+    let final_of = |deliveries: Vec<Delivery>| deliveries.into_iter().last();
+    assert_eq!(
+        final_of(drain(&mut alice_rx)),
+        Some(Delivery::Final(PolicyDecision {
+            policy: Some(version.clone()),
+            ..PolicyDecision::allow("bc abc abc abc abc abc")
+        }))
+    );
+    assert_eq!(
+        final_of(drain(&mut bob_rx)),
+        Some(Delivery::Final(PolicyDecision {
+            policy: Some(version),
+            ..PolicyDecision::allow("c abc abc abc abc abc ")
+        }))
+    );
+    let history = node.io_layer.sessions_mut().history(&bob).await;
+    assert_eq!(history.turns.len(), 2);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
@@ -0,0 +1,117 @@
+//! Integration test: la latenza misurata dei tick guida l'`AdaptiveThrottle`.
+//!
+//! Un backend finto e lento viene iniettato nel `NeuroNode`: man mano che
+//! l'inferenza rallenta, il nodo deve passare da Normal a Throttled e poi
+//! a Survival. Con un `ManualClock` lo stesso percorso è riprodotto senza
+//! attese reali e con latenze esatte.
+
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{temp_data_dir, FakeBackend};
+use samaritan_core::adaptive_throttle::ThrottleLevel;
+use samaritan_core::clock::ManualClock;
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::tokenizer::Tokenizer;
+use samaritan_core::NeuroNode;
+
+async fn tick_with_input(node: &mut NeuroNode, ticks: usize) {
+    for _ in 0..ticks {
+        node.io_layer.submit_user_input("ping");
+        node.tick().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn slow_inference_moves_node_to_throttled_then_survival() {
+    let data_dir = temp_data_dir();
+    let backend = FakeBackend::fixed(256, 1);
+    let delay_ms = Arc::clone(&backend.delay_ms);
+
+    // Heavy: target 10ms, survival oltre 100ms.
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(backend),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::HeavyCpu),
+    )
+    .await
+    .unwrap();
+    // Un token per richiesta: il ritardo simulato vale per l'intera inferenza.
+    node.neural_engine.set_max_new_tokens(1);
+
+    // Backend veloce: il nodo resta Normal.
+    tick_with_input(&mut node, 5).await;
+    assert_eq!(
+        node.adaptive_throttle.current_level(),
+        ThrottleLevel::Normal
+    );
+    assert!(node.adaptive_throttle.allow_background());
+
+    // Inferenza sopra il target ma sotto la soglia di survival.
+    delay_ms.store(40, Ordering::Relaxed);
+    tick_with_input(&mut node, 3).await;
+    assert_eq!(
+        node.adaptive_throttle.current_level(),
+        ThrottleLevel::Throttled
+    );
+    assert!(node.adaptive_throttle.last_critical_latency_ms() >= 40.0);
+    assert!(node.adaptive_throttle.last_latency_ms() >= 40.0);
+
+    // Inferenza oltre la soglia di survival: niente più background.
+    delay_ms.store(150, Ordering::Relaxed);
+    tick_with_input(&mut node, 2).await;
+    assert_eq!(
+        node.adaptive_throttle.current_level(),
+        ThrottleLevel::Survival
+    );
+    assert!(!node.adaptive_throttle.allow_background());
+    assert!(node.adaptive_throttle.avg_latency_ms() > 10.0);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
+
+#[tokio::test]
+async fn simulated_clock_measures_exact_tick_latency() {
+    let data_dir = temp_data_dir();
+    let clock = ManualClock::new();
+    let backend = FakeBackend::fixed(256, 1).with_clock(clock.clone());
+    let delay_ms = Arc::clone(&backend.delay_ms);
+
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(backend),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::HeavyCpu),
+    )
+    .await
+    .unwrap();
+    node.neural_engine.set_max_new_tokens(1);
+    node.set_clock(clock.shared());
+    let started = clock.offset();
+
+    // Il tempo del nodo avanza solo con l'inferenza simulata.
+    delay_ms.store(40, Ordering::Relaxed);
+    tick_with_input(&mut node, 3).await;
+    assert_eq!(
+        node.adaptive_throttle.current_level(),
+        ThrottleLevel::Throttled
+    );
+    assert_eq!(node.adaptive_throttle.last_latency_ms(), 40.0);
+    assert_eq!(node.adaptive_throttle.last_critical_latency_ms(), 40.0);
+    assert_eq!(node.uptime(), Duration::from_millis(120));
+
+    delay_ms.store(150, Ordering::Relaxed);
+    tick_with_input(&mut node, 1).await;
+    assert_eq!(
+        node.adaptive_throttle.current_level(),
+        ThrottleLevel::Survival
+    );
+    assert_eq!(node.adaptive_throttle.last_latency_ms(), 150.0);
+    assert_eq!(clock.offset() - started, Duration::from_millis(270));
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
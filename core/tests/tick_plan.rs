@@ -0,0 +1,53 @@
+//! Integration test: `NeuroNode::tick` esegue il piano del `PriorityScheduler`.
+
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use common::{temp_data_dir, FakeBackend};
+use samaritan_core::node_profile::NodeProfile;
+use samaritan_core::tokenizer::Tokenizer;
+use samaritan_core::NeuroNode;
+
+#[tokio::test]
+async fn each_tick_drains_queued_inputs_within_the_critical_budget() {
+    let data_dir = temp_data_dir();
+    let backend = FakeBackend::fixed(256, 1);
+    let calls = Arc::clone(&backend.calls);
+
+    let mut node = NeuroNode::bootstrap_with_backend(
+        data_dir.clone(),
+        Box::new(backend),
+        Tokenizer::byte_level(),
+        Some(NodeProfile::Desktop),
+    )
+    .await
+    .unwrap();
+    // Un token per richiesta: una chiamata al backend per inferenza.
+    node.neural_engine.set_max_new_tokens(1);
+    // Il bootstrap esegue già il warmup del backend.
+    let warmup = calls.load(Ordering::Relaxed);
+
+    for prompt in ["uno", "due", "tre", "quattro", "cinque"] {
+        node.io_layer.submit_user_input(prompt);
+    }
+
+    // Budget critico 0.9, un giro costa 0.36: due input per tick.
+    node.tick().await.unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed) - warmup, 2);
+    assert_eq!(node.io_layer.queue_len(), 3);
+
+    node.tick().await.unwrap();
+    node.tick().await.unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed) - warmup, 5);
+    assert_eq!(node.io_layer.queue_len(), 0);
+
+    // Coda vuota: il task UserInference non ha nulla da fare.
+    node.tick().await.unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed) - warmup, 5);
+    assert_eq!(node.tick_counter, 4);
+    assert_eq!(node.scheduler.ticks_scheduled(), 4);
+
+    std::fs::remove_dir_all(data_dir).ok();
+}
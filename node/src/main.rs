@@ -0,0 +1,402 @@
+//! `samaritan-node`: avvia un nodo da `samaritan.yaml` e lo collega a una
+//! REPL sul terminale.
+//!
+//! ```text
+//! samaritan-node [--config samaritan.yaml]
+//! ```
+//!
+//! Ogni riga normale viene accodata nell'`IOLayer` (sessione locale) e la
+//! risposta viene stampata in streaming, un frammento alla volta mentre il
+//! modello genera, da un task dedicato. Le righe che iniziano con `/` sono
+//! comandi (vedi [`HELP`]); EOF o `/quit` fermano il nodo. Un comando
+//! fallito stampa l'errore su stderr senza fermare il nodo.
+//!
+//! Sui sistemi Unix il nodo accetta anche i comandi del control plane su
+//! `<data_dir>/control/control.sock` (vedi `samaritan_core::control`).
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use anyhow::{bail, Context, Result};
+use samaritan_core::io_layer::{Delivery, PolicyDecision, ResponseEvent};
+use samaritan_core::node::{build_node, run_node_with_hook, NodeConfig, TickHook};
+use samaritan_core::NeuroNode;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio_util::sync::CancellationToken;
+
+const USAGE: &str = "usage: samaritan-node [--config <samaritan.yaml>]";
+
+/// Campioni massimi di una traccia registrata con `/trace start`.
+const TRACE_SAMPLES: usize = 10_000;
+
+const HELP: &str = "\
+Commands:
+  <message>                 talk with the node
+  /stats                    node and policy metrics
+  /reset_stats              reset the policy reload counters
+  /throttle                 throttle level and tick latency
+  /autotune [on|off]        show or switch online PID tuning
+  /trace start              record tick latencies for samaritan-autotune
+  /trace save <file>        stop recording and write the trace (JSONL)
+  /policy [strict|normal]   show or switch the policy mode
+  /snapshots                list saved snapshots
+  /help                     show this help
+  /quit                     stop the node";
+
+fn main() -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<()> {
+    let mut config_path: Option<PathBuf> = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--config") => config_path = Some(args.next().context(USAGE)?.into()),
+            Some("-h" | "--help") => {
+                println!("{USAGE}\n\n{HELP}");
+                return Ok(());
+            }
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+
+    let config = match &config_path {
+        Some(path) => NodeConfig::from_yaml(path)?,
+        None => NodeConfig::load_default()?,
+    };
+    let mut node = build_node(&config).await?;
+    let shutdown = CancellationToken::new();
+    let printer = Arc::new(Mutex::new(ReplyPrinter::default()));
+    let printing =
+        spawn_response_printer(node.io_layer.subscribe_responses(), Arc::clone(&printer));
+    let repl = Repl {
+        lines: spawn_stdin_reader(),
+        printer,
+        shutdown: shutdown.clone(),
+    };
+    let control = start_control_plane(&config, &shutdown).await;
+
+    println!(
+        "=== Samaritan node {} ({:?}) ===\n{HELP}",
+        hex::encode(&node.id[..8]),
+        node.profile
+    );
+    lock(&repl.printer).prompt();
+
+    let mut hooks = (repl, control);
+    let result = run_node_with_hook(
+        node,
+        config.runtime.tick_interval(),
+        shutdown.clone(),
+        &mut hooks,
+    )
+    .await;
+    shutdown.cancel();
+    // Il nodo è stato rilasciato: il task di stampa termina dopo le ultime
+    // risposte.
+    let result = result.map(drop);
+    printing.await.ok();
+    println!();
+    result
+}
+
+/// La REPL come [`TickHook`]: prima di ogni tick esegue i comandi e accoda
+/// i messaggi letti da stdin. Le risposte sono stampate da
+/// [`spawn_response_printer`].
+struct Repl {
+    lines: mpsc::UnboundedReceiver<String>,
+    printer: Arc<Mutex<ReplyPrinter>>,
+    shutdown: CancellationToken,
+}
+
+impl TickHook for Repl {
+    async fn before_tick(&mut self, node: &mut NeuroNode) -> Result<()> {
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.shutdown.cancel();
+                    break;
+                }
+            };
+            match line.trim() {
+                "" => lock(&self.printer).prompt(),
+                command if command.starts_with('/') => {
+                    match run_command(node, command).await {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            self.shutdown.cancel();
+                            break;
+                        }
+                        // Un comando fallito non ferma il nodo.
+                        Err(err) => eprintln!("error: {err:#}"),
+                    }
+                    lock(&self.printer).prompt();
+                }
+                message => {
+                    // Se scartato, l'avviso arriva come `Delivery::Overloaded`.
+                    node.io_layer.submit_user_input(message);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Avvia il control plane su `<data_dir>/control/control.sock`; se il
+/// socket non è disponibile la REPL continua senza.
+#[cfg(unix)]
+async fn start_control_plane(
+    config: &NodeConfig,
+    shutdown: &CancellationToken,
+) -> Option<samaritan_core::control::ControlPlane> {
+    use samaritan_core::control;
+
+    match control::bind(&config.data_dir).await {
+        Ok(listener) => {
+            let (plane, server) = control::channel();
+            tokio::spawn(server.serve(listener, shutdown.clone()));
+            Some(plane)
+        }
+        Err(err) => {
+            eprintln!("warning: control plane disabled: {err:#}");
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unused_async)]
+async fn start_control_plane(_config: &NodeConfig, _shutdown: &CancellationToken) -> Option<()> {
+    None
+}
+
+/// Stampa le risposte appena arrivano, anche durante un tick: i frammenti
+/// parziali compaiono mentre il modello genera. Termina quando il nodo
+/// viene rilasciato.
+fn spawn_response_printer(
+    mut responses: mpsc::UnboundedReceiver<ResponseEvent>,
+    printer: Arc<Mutex<ReplyPrinter>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(event) = responses.recv().await {
+            lock(&printer).print(&event);
+        }
+    })
+}
+
+/// Blocca il printer; un panic durante una stampa non lo rende inutilizzabile.
+fn lock(printer: &Mutex<ReplyPrinter>) -> MutexGuard<'_, ReplyPrinter> {
+    printer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Legge stdin riga per riga su un thread dedicato (la lettura è bloccante).
+fn spawn_stdin_reader() -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Esegue un comando; ritorna `false` se la REPL deve terminare.
+async fn run_command(node: &mut NeuroNode, command: &str) -> Result<bool> {
+    let mut words = command.split_whitespace();
+    match (words.next().unwrap_or_default(), words.next()) {
+        ("/quit" | "/exit", None) => return Ok(false),
+        ("/help", None) => println!("{HELP}"),
+        ("/stats", None) => {
+            let stats = node.meta_observer.stats();
+            println!("ticks:            {}", node.tick_counter);
+            println!("uptime:           {:.0?}", node.uptime());
+            match &stats.policy {
+                Some(policy) => println!("policy:           {policy}"),
+                None => println!("policy:           -"),
+            }
+            println!("policy reloads:   {}", stats.policy_reloads);
+            println!("reload failures:  {}", stats.policy_reload_failures);
+            println!(
+                "sessions:         {}",
+                node.io_layer.sessions().loaded().count()
+            );
+        }
+        ("/reset_stats", None) => {
+            node.meta_observer.reset_stats();
+            println!("policy reload counters reset");
+        }
+        ("/throttle", None) => {
+            let throttle = &node.adaptive_throttle;
+            println!("level:            {}", throttle.current_level());
+            println!("intensity:        {:.2}", throttle.current_intensity());
+            println!("last latency:     {:.2} ms", throttle.last_latency_ms());
+            println!("avg latency:      {:.2} ms", throttle.avg_latency_ms());
+            println!("pid gains:        {}", throttle.config().gains());
+            let load = throttle.system_load();
+            let show = |value: Option<f64>, scale: f64| {
+                value.map_or_else(|| "-".to_owned(), |v| format!("{:.0}%", v * scale))
+            };
+            println!("cpu usage:        {}", show(load.cpu_usage, 100.0));
+            println!("ram available:    {}", show(load.mem_available, 100.0));
+            println!(
+                "pressure cpu/mem/io: {} / {} / {}",
+                show(load.cpu_pressure, 1.0),
+                show(load.memory_pressure, 1.0),
+                show(load.io_pressure, 1.0)
+            );
+        }
+        ("/autotune", mode) => {
+            match mode {
+                Some("on") => node.adaptive_throttle.set_online_tuning(true),
+                Some("off") => node.adaptive_throttle.set_online_tuning(false),
+                Some(other) => {
+                    println!("unknown autotune mode {other:?} (expected on or off)");
+                    return Ok(true);
+                }
+                None => {}
+            }
+            let throttle = &node.adaptive_throttle;
+            match throttle.online_tuner() {
+                Some(tuner) => println!(
+                    "online tuning on: {} ({} adjustments, from {})",
+                    tuner.gains(),
+                    tuner.adjustments(),
+                    tuner.base()
+                ),
+                None => println!("online tuning off: {}", throttle.config().gains()),
+            }
+        }
+        ("/trace", Some("start")) if words.next().is_none() => {
+            node.adaptive_throttle.start_trace(TRACE_SAMPLES);
+            println!("recording up to {TRACE_SAMPLES} ticks");
+        }
+        ("/trace", Some("save")) => {
+            let Some(path) = words.next() else {
+                println!("usage: /trace save <file>");
+                return Ok(true);
+            };
+            match node.adaptive_throttle.take_trace() {
+                Some(trace) => match trace.save(path.as_ref()).await {
+                    Ok(()) => println!("saved {} ticks to {path}", trace.len()),
+                    Err(err) => println!("error: {err:#}"),
+                },
+                None => println!("no trace recording (try /trace start)"),
+            }
+        }
+        ("/policy", mode) => {
+            match mode {
+                Some("strict") => node.policy_core.set_strict_mode(true, "repl"),
+                Some("normal") => node.policy_core.set_strict_mode(false, "repl"),
+                Some(other) => {
+                    println!("unknown policy mode {other:?} (expected strict or normal)");
+                    return Ok(true);
+                }
+                None => {}
+            }
+            let mode = if node.policy_core.is_strict_mode() {
+                "strict"
+            } else {
+                "normal"
+            };
+            println!("policy {} ({mode})", node.policy_core.version());
+        }
+        ("/snapshots", None) => {
+            let snapshots = node.snapshot_store.list().await?;
+            if snapshots.is_empty() {
+                println!("no snapshots");
+            }
+            for snapshot in snapshots {
+                println!(
+                    "{}  created_ms={}  max_new_tokens={}  weights={}",
+                    snapshot.id, snapshot.created_ms, snapshot.max_new_tokens, snapshot.weights
+                );
+            }
+        }
+        _ => println!("unknown command {command:?} (try /help)"),
+    }
+    Ok(true)
+}
+
+/// Stampa le risposte in streaming, correggendo il testo se la decisione
+/// finale differisce da quanto già mostrato.
+#[derive(Debug, Default)]
+struct ReplyPrinter {
+    shown: String,
+}
+
+impl ReplyPrinter {
+    fn print(&mut self, event: &ResponseEvent) {
+        match &event.delivery {
+            Delivery::Partial(text) => {
+                if self.shown.is_empty() {
+                    print!("< ");
+                }
+                print!("{text}");
+                self.shown.push_str(text);
+                flush();
+            }
+            Delivery::Final(decision) => {
+                self.finish(decision);
+                self.prompt();
+            }
+            Delivery::Overloaded(message) => {
+                println!("< [overloaded] {message}");
+                self.prompt();
+            }
+        }
+    }
+
+    fn finish(&mut self, decision: &PolicyDecision) {
+        let shown = std::mem::take(&mut self.shown);
+        match decision.text.strip_prefix(shown.as_str()) {
+            Some(rest) if decision.is_allowed() => {
+                if shown.is_empty() {
+                    print!("< ");
+                }
+                println!("{rest}");
+            }
+            _ => {
+                if !shown.is_empty() {
+                    println!();
+                }
+                let reason = decision
+                    .reason
+                    .map_or_else(String::new, |reason| format!(", {reason}"));
+                println!("< [{:?}{reason}] {}", decision.verdict, decision.text);
+            }
+        }
+    }
+
+    fn prompt(&self) {
+        if self.shown.is_empty() {
+            print!("> ");
+            flush();
+        }
+    }
+}
+
+fn flush() {
+    std::io::stdout().flush().ok();
+}